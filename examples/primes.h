@@ -0,0 +1,3 @@
+extern int primes[], count;
+
+int is_prime(int n);
@@ -0,0 +1,20 @@
+extern crate semic;
+
+use std::io;
+use std::process;
+
+/// Language server for editors, talking over stdin and stdout
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    match semic::lsp::serve(stdin.lock(), stdout.lock()) {
+        Ok(true) => (),
+        // exiting without being shut down first is an error
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
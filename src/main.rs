@@ -4,6 +4,7 @@ extern crate semic;
 use getopts::Options;
 use std::env;
 use std::process;
+use std::str::FromStr;
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -12,6 +13,17 @@ fn print_usage(program: &str, opts: Options) {
     print!("{}", opts.usage(&brief));
 }
 
+/// Parse the number given to a limit flag, if any, or exit
+fn limit<T: FromStr>(matches: &getopts::Matches, name: &str) -> Option<T> {
+    matches.opt_str(name).map(|x| match x.parse() {
+        Ok(n) => n,
+        Err(_) => {
+            println!("Error: invalid value '{}' for --{} (expected a number)", x, name);
+            process::exit(1);
+        }
+    })
+}
+
 fn main() {
     // parse opts
     let args: Vec<String> = env::args().collect();
@@ -20,6 +32,21 @@ fn main() {
     let mut opts = Options::new();
     opts.optflag("d", "debug", "interactive debug");
     opts.optflag("v", "verbose", "print debug information");
+    opts.optmulti("", "sanitize", "run-time checks: 'uninit' reports reads of unwritten memory", "CHECK");
+    opts.optopt("", "backend", "how to run: 'tree' (default) walks the AST, 'vm' compiles to bytecode", "BACKEND");
+    opts.optopt("", "overflow", "signed integer overflow: 'wrap' (default) or 'trap'", "POLICY");
+    opts.optopt("", "max-steps", "stop after executing this many statements", "N");
+    opts.optopt("", "max-depth", "stop when calls nest deeper than this below main", "N");
+    opts.optopt("", "max-cells", "stop when declared arrays total more cells than this", "N");
+    opts.optflagopt("", "profile", "print how often each line ran and the time spent in each function, and write it as JSON to FILE if given", "FILE");
+    opts.optopt("", "coverage", "write the lines and branches that ran to FILE in LCOV format", "FILE");
+    opts.optflagopt("", "trace", "print each statement run and value assigned, or write them to FILE as JSON lines", "FILE");
+    opts.optmulti("W", "", "report lint: 'unused', 'unreachable', 'missing-return', 'shadow-global', or 'error' to fail on warnings", "LINT");
+    opts.optmulti("A", "", "allow lint, as for -W", "LINT");
+    opts.optopt("", "error-format", "how to report errors: 'human' (default) or 'json'", "FORMAT");
+    opts.optflag("", "format", "format the program in place instead of running it");
+    opts.optflag("", "check", "with --format, only check that the program is formatted");
+    opts.optflag("", "dap", "serve the Debug Adapter Protocol on stdin and stdout, for editors to debug the programs they launch");
     opts.optflag("h", "help", "print this help menu");
 
     let matches = match opts.parse(&args[1..]) {
@@ -34,10 +61,88 @@ fn main() {
         print_usage(&program, opts);
         return;
     }
+    // debug adapter, which is told the program by the editor
+    if matches.opt_present("dap") {
+        let input = Box::new(std::io::BufReader::new(std::io::stdin()));
+        if let Err(err) = semic::dap::serve(input, Box::new(std::io::stdout())) {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
     // debug
     let debug = matches.opt_present("d");
     // verbose
     let verbose = matches.opt_present("v");
+    // run settings
+    let mut context = semic::engine::Context::new();
+    // sanitizers
+    for x in matches.opt_strs("sanitize").iter().flat_map(|x| x.split(',')) {
+        match x {
+            "uninit" => context.sanitize_uninit = true,
+            _ => {
+                println!("Error: unknown sanitizer '{}' (expected 'uninit')", x);
+                process::exit(1);
+            }
+        };
+    }
+    // backend
+    if let Some(x) = matches.opt_str("backend") {
+        context.backend = match x.as_str() {
+            "tree" => semic::engine::Backend::Tree,
+            "vm" => semic::engine::Backend::Vm,
+            _ => {
+                println!("Error: unknown backend '{}' (expected 'tree' or 'vm')", x);
+                process::exit(1);
+            }
+        };
+    }
+    // overflow
+    if let Some(x) = matches.opt_str("overflow") {
+        context.overflow = match x.as_str() {
+            "wrap" => semic::engine::Overflow::Wrap,
+            "trap" => semic::engine::Overflow::Trap,
+            _ => {
+                println!("Error: unknown overflow policy '{}' (expected 'wrap' or 'trap')", x);
+                process::exit(1);
+            }
+        };
+    }
+    // limits
+    context.limits.steps = limit(&matches, "max-steps");
+    context.limits.depth = limit(&matches, "max-depth");
+    context.limits.cells = limit(&matches, "max-cells");
+    // profile
+    context.profile = matches.opt_present("profile");
+    let profile_path = matches.opt_str("profile");
+    // coverage
+    let coverage_path = matches.opt_str("coverage");
+    context.coverage = coverage_path.is_some();
+    // lints
+    let lints = matches.opt_strs("W").into_iter().map(|x| (x, semic::lint::Level::Warn))
+        .chain(matches.opt_strs("A").into_iter().map(|x| (x, semic::lint::Level::Allow)));
+    for (x, level) in lints {
+        match (x.as_str(), semic::lint::Lint::from_name(&x)) {
+            ("error", _) if level == semic::lint::Level::Warn => context.warnings_as_errors = true,
+            (_, Some(lint)) => context.lints.push((lint, level)),
+            _ => {
+                println!("Error: unknown lint '{}'", x);
+                process::exit(1);
+            }
+        };
+    }
+    // error format
+    let json = match matches.opt_str("error-format") {
+        None => false,
+        Some(x) => match x.as_str() {
+            "human" => false,
+            "json" => true,
+            _ => {
+                println!("Error: unknown error format '{}' (expected 'human' or 'json')", x);
+                process::exit(1);
+            }
+        },
+    };
     // program
     let path = if !matches.free.is_empty() {
         matches.free[0].clone()
@@ -61,9 +166,98 @@ fn main() {
     let mut prog = String::new();
     file.read_to_string(&mut prog).unwrap();
 
+    // with the files it includes
+    let (sources, loaded) = semic::load(&path, &prog);
+
+    // trace
+    if matches.opt_present("trace") {
+        let tracer = match matches.opt_str("trace") {
+            Some(p) => match File::create(&p) {
+                Ok(f) => semic::trace::Tracer::new(&sources, Box::new(f), true),
+                Err(err) => {
+                    println!("Error: failed creating trace '{}' ({})", p, err.to_string());
+                    process::exit(1);
+                }
+            },
+            None => semic::trace::Tracer::new(&sources, Box::new(std::io::stdout()), false),
+        };
+        context.trace(tracer);
+    }
+
+    let printer = semic::error::ErrorPrinter::from_map(sources.clone());
+
+    // format
+    if matches.opt_present("format") {
+        let formatted = match semic::format(&prog) {
+            Ok(x) => x,
+            Err(err) => {
+                printer.print_err(err);
+                process::exit(1);
+            }
+        };
+        if formatted == prog {
+            process::exit(0);
+        }
+        if matches.opt_present("check") {
+            println!("{} is not formatted", path);
+            process::exit(1);
+        }
+        if let Err(err) = File::create(&path).and_then(|mut f| f.write_all(formatted.as_bytes())) {
+            println!("Error: failed writing file '{}' ({})", path, err.to_string());
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    let report = |diags: &Vec<semic::error::Diagnostic>| {
+        for d in diags.iter() {
+            if json {
+                println!("{}", d.to_json());
+            } else {
+                printer.print_diag(d);
+            }
+        }
+    };
+
+    // warnings
+    let warnings = semic::lint(&path, &prog, &context);
+    report(&warnings);
+    if warnings.iter().any(|d| d.severity == semic::error::Severity::Error) {
+        process::exit(1);
+    }
+
     // run
-    process::exit(match semic::run(path, prog, argv, debug, verbose) {
+    let code = match semic::run(path.clone(), prog.clone(), argv, debug, verbose, &context) {
         Ok(_) => 0,
-        Err(()) => 1
-    });
+        Err(diags) => {
+            report(&diags);
+            1
+        }
+    };
+
+    // profile, when the program got to run
+    let profile = context.profile();
+    if context.profile && !profile.funcs.is_empty() {
+        print!("\n{}", profile.listing(&sources));
+        if let Some(p) = profile_path {
+            let written = File::create(&p).and_then(|mut f| writeln!(f, "{}", profile.to_json(&sources)));
+            if let Err(err) = written {
+                println!("Error: failed writing profile '{}' ({})", p, err.to_string());
+                process::exit(1);
+            }
+        }
+    }
+
+    // coverage, also of a run that failed
+    if let Some(p) = coverage_path {
+        if let Ok(ref ast) = loaded {
+            let lcov = context.coverage().lcov(ast, &sources);
+            if let Err(err) = File::create(&p).and_then(|mut f| f.write_all(lcov.as_bytes())) {
+                println!("Error: failed writing coverage '{}' ({})", p, err.to_string());
+                process::exit(1);
+            }
+        }
+    }
+
+    process::exit(code);
 }
@@ -1,54 +1,68 @@
-use std::str::Chars;
 use std::fmt::{Debug, Formatter, Error};
 
 
-pub type CProg<'input> = Vec<CProgElem<'input>>;
+pub type CProg = Vec<CProgElem>;
 
 #[derive(Clone)]
-pub enum CProgElem<'input> {
-    Decl(CLoc, CType, CIdent<'input>, Option<CExpr<'input>>),
-    Proto(CLoc, CProto<'input>),
-    Func(CLoc, CFunc<'input>),
+pub enum CProgElem {
+    Decl(CLoc, CType, CIdent, Option<CExpr>),
+    /// Variable defined in another file, with a `Ref` type for arrays
+    Extern(CLoc, CType, CIdent),
+    Proto(CLoc, CProto),
+    Func(CLoc, CFunc),
+    /// File to include, as written. Loading a program replaces these with
+    /// what the files declare
+    Include(CLoc, CString),
     Error,
 }
 
 #[derive(Clone, Debug)]
-pub struct CProto<'input> {
+pub struct CProto {
     pub ret: Option<CType>,
-    pub name: CIdent<'input>,
-    pub params: Vec<(CType, CIdent<'input>)>,
+    pub name: CIdent,
+    pub params: Vec<(CType, CIdent)>,
 }
 
 #[derive(Clone, Debug)]
-pub struct CFunc<'input> {
-    pub proto: CProto<'input>,
-    pub body: CStmt<'input>,
+pub struct CFunc {
+    pub proto: CProto,
+    pub body: CStmt,
 }
 
 #[derive(Clone)]
-pub enum CStmt<'input> {
-    Decl(CLoc, CType, CIdent<'input>, Option<CExpr<'input>>),
-    Assign(CLoc, CIdent<'input>, Option<CExpr<'input>>, CExpr<'input>),
-    Call(CLoc, CIdent<'input>, Vec<Box<CExpr<'input>>>),
-    Return(CLoc, Option<CExpr<'input>>),
-    Block(CLoc, Vec<Box<CStmt<'input>>>),
-    If(CLoc, CExpr<'input>, Box<CStmt<'input>>, Option<Box<CStmt<'input>>>),
-    While(CLoc, CExpr<'input>, Box<CStmt<'input>>),
-    Print(CLoc, Option<CString<'input>>, CExpr<'input>),
+pub enum CStmt {
+    Decl(CLoc, CType, CIdent, Option<CExpr>),
+    Assign(CLoc, CIdent, Option<CExpr>, CExpr),
+    Call(CLoc, CIdent, Vec<Box<CExpr>>),
+    Return(CLoc, Option<CExpr>),
+    Block(CLoc, Vec<Box<CStmt>>),
+    /// Declarations written as one statement, e.g. `int a, b = 1;`, each
+    /// followed by the `Assign` of its initializer if it has one
+    Decls(CLoc, Vec<Box<CStmt>>),
+    If(CLoc, CExpr, Box<CStmt>, Option<Box<CStmt>>),
+    While(CLoc, CExpr, Box<CStmt>),
+    DoWhile(CLoc, Box<CStmt>, CExpr),
+    Switch(CLoc, CExpr, Vec<Box<CStmt>>),
+    Case(CLoc, Option<CExpr>),
+    Break(CLoc),
+    Print(CLoc, Option<CString>, CExpr),
     Error,
 }
 
 #[derive(Clone)]
-pub enum CExpr<'input> {
+pub enum CExpr {
     Int(CLoc, CInt),
     Float(CLoc, CFloat),
-    Str(CLoc, CString<'input>),
+    Str(CLoc, CString),
     Char(CLoc, CChar),
-    Ident(CLoc, CIdent<'input>),
-    UnOp(CLoc, COp, Box<CExpr<'input>>),
-    BinOp(CLoc, COp, Box<CExpr<'input>>, Box<CExpr<'input>>),
-    Call(CLoc, CIdent<'input>, Vec<Box<CExpr<'input>>>),
-    Index(CLoc, CIdent<'input>, Box<CExpr<'input>>),
+    Ident(CLoc, CIdent),
+    UnOp(CLoc, COp, Box<CExpr>),
+    BinOp(CLoc, COp, Box<CExpr>, Box<CExpr>),
+    Call(CLoc, CIdent, Vec<Box<CExpr>>),
+    Index(CLoc, CIdent, Box<CExpr>),
+    Cast(CLoc, CType, Box<CExpr>),
+    Ternary(CLoc, Box<CExpr>, Box<CExpr>, Box<CExpr>),
+    Comma(CLoc, Box<CExpr>, Box<CExpr>),
     Error,
 }
 
@@ -86,34 +100,36 @@ pub type CLoc = (usize, usize);
 
 pub type CInt = i32;
 pub type CFloat = f32;
-pub type CString<'input> = Chars<'input>;
+pub type CString = String;
 pub type CChar = char;
 
-pub type CIdent<'input> = &'input str;
+pub type CIdent = String;
 
 
 // debug trait
 
-impl<'input> Debug for CProgElem<'input> {
+impl Debug for CProgElem {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         use self::CProgElem::*;
         match *self {
-            Decl(_, ref t, id, ref eo) => match eo {
+            Decl(_, ref t, ref id, ref eo) => match eo {
                 &Some(ref e) => write!(fmt, "{:?} {}[{:?}]", t, id, e),
                 &None => write!(fmt, "{:?} {}", t, id),
             },
+            Extern(_, ref t, ref id) => write!(fmt, "extern {:?} {}", t, id),
             Proto(_, ref x) => write!(fmt, "{:?}", x),
             Func(_, ref x) => write!(fmt, "{:#?}", x),
+            Include(_, ref path) => write!(fmt, "include {:?}", path),
             Error => write!(fmt, "error"),
         }
     }
 }
 
-impl<'input> Debug for CStmt<'input> {
+impl Debug for CStmt {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         use self::CStmt::*;
         match *self {
-            Decl(_, ref t, id, ref eo) => match *eo {
+            Decl(_, ref t, ref id, ref eo) => match *eo {
                 Some(ref e) => write!(fmt, "{:?} {}[{:?}]", t, id, e),
                 None => write!(fmt, "{:?} {}", t, id),
             },
@@ -135,13 +151,23 @@ impl<'input> Debug for CStmt<'input> {
                     None => write!(fmt, "return"),
                 }
             }
-            Block(_, ref stmts) => write!(fmt, "{:#?}", stmts),
+            Block(_, ref stmts) |
+            Decls(_, ref stmts) => write!(fmt, "{:#?}", stmts),
             If(_, ref cond, ref stmt, ref opt) => match opt.clone() {
                 Some(ref stmt2) => write!(fmt, "if {:?} {:?} else {:?}", cond, stmt, stmt2),
                 None => write!(fmt, "if {:?} {:?}", cond, stmt),
             },
             While(_, ref cond, ref stmt) =>
                 write!(fmt, "while {:?} {:?}", cond, stmt),
+            DoWhile(_, ref stmt, ref cond) =>
+                write!(fmt, "do {:?} while {:?}", stmt, cond),
+            Switch(_, ref e, ref stmts) =>
+                write!(fmt, "switch {:?} {:#?}", e, stmts),
+            Case(_, ref eo) => match *eo {
+                Some(ref e) => write!(fmt, "case {:?}", e),
+                None => write!(fmt, "default"),
+            },
+            Break(_) => write!(fmt, "break"),
             Print(_, ref fmto, ref e) => match *fmto {
                 Some(ref s) => write!(fmt, "printf({:?}, {:?})", s, e),
                 None => write!(fmt, "printf({:?})", e),
@@ -151,7 +177,7 @@ impl<'input> Debug for CStmt<'input> {
     }
 }
 
-impl<'input> Debug for CExpr<'input> {
+impl Debug for CExpr {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         use self::CExpr::*;
         match *self {
@@ -173,6 +199,9 @@ impl<'input> Debug for CExpr<'input> {
             Index(_, ref i, ref e) => {
                 write!(fmt, "{}[{:?}]", i, e)
             },
+            Cast(_, ref t, ref e) => write!(fmt, "(({:?}) {:?})", t, e),
+            Ternary(_, ref c, ref l, ref r) => write!(fmt, "({:?} ? {:?} : {:?})", c, l, r),
+            Comma(_, ref l, ref r) => write!(fmt, "({:?}, {:?})", l, r),
             Error => write!(fmt, "error"),
         }
     }
@@ -0,0 +1,180 @@
+//! Functions building the AST in code, for tools that generate or transform
+//! programs. Built nodes have no location in any source, so they get
+//! `(0, 0)` as the parser tests do.
+//!
+//! # Examples
+//!
+//! ```
+//! use semic::ast::{CType, COp};
+//! use semic::build::*;
+//!
+//! let prog = vec![
+//!     func(proto(Some(CType::Int), "main", vec![]), vec![
+//!         decl(CType::Int, "x"),
+//!         assign("x", binop(COp::Mul, int(6), int(7))),
+//!         ret(Some(ident("x"))),
+//!     ]),
+//! ];
+//! assert_eq!("int main(void) {\n    int x;\n    x = 6 * 7;\n    return x;\n}\n",
+//!            semic::pretty::format_prog(&prog, None));
+//! ```
+
+use ast::*;
+
+const LOC: CLoc = (0, 0);
+
+fn boxed(exprs: Vec<CExpr>) -> Vec<Box<CExpr>> {
+    exprs.into_iter().map(Box::new).collect()
+}
+
+// program elements
+
+/// Global variable
+pub fn global(t: CType, name: &str) -> CProgElem {
+    CProgElem::Decl(LOC, t, name.to_owned(), None)
+}
+
+/// Global array of the size
+pub fn global_array(t: CType, name: &str, size: CExpr) -> CProgElem {
+    CProgElem::Decl(LOC, t, name.to_owned(), Some(size))
+}
+
+/// Signature of a function, returning nothing if `ret` is `None`
+pub fn proto(ret: Option<CType>, name: &str, params: Vec<(CType, &str)>) -> CProto {
+    CProto {
+        ret: ret,
+        name: name.to_owned(),
+        params: params.into_iter().map(|(t, id)| (t, id.to_owned())).collect(),
+    }
+}
+
+/// Declaration of a function defined later
+pub fn func_decl(proto: CProto) -> CProgElem {
+    CProgElem::Proto(LOC, proto)
+}
+
+/// Function with the statements as its body
+pub fn func(proto: CProto, body: Vec<CStmt>) -> CProgElem {
+    CProgElem::Func(LOC, CFunc { proto: proto, body: block(body) })
+}
+
+// statements
+
+pub fn decl(t: CType, name: &str) -> CStmt {
+    CStmt::Decl(LOC, t, name.to_owned(), None)
+}
+
+pub fn decl_array(t: CType, name: &str, size: CExpr) -> CStmt {
+    CStmt::Decl(LOC, t, name.to_owned(), Some(size))
+}
+
+/// Declaration with an initializer, like `int x = 1;` is parsed
+pub fn decl_init(t: CType, name: &str, e: CExpr) -> CStmt {
+    CStmt::Decls(LOC, vec![Box::new(decl(t, name)), Box::new(assign(name, e))])
+}
+
+pub fn assign(name: &str, e: CExpr) -> CStmt {
+    CStmt::Assign(LOC, name.to_owned(), None, e)
+}
+
+/// Assign an element of an array
+pub fn assign_index(name: &str, index: CExpr, e: CExpr) -> CStmt {
+    CStmt::Assign(LOC, name.to_owned(), Some(index), e)
+}
+
+/// Call whose result is not used
+pub fn call_stmt(name: &str, args: Vec<CExpr>) -> CStmt {
+    CStmt::Call(LOC, name.to_owned(), boxed(args))
+}
+
+pub fn ret(e: Option<CExpr>) -> CStmt {
+    CStmt::Return(LOC, e)
+}
+
+pub fn block(stmts: Vec<CStmt>) -> CStmt {
+    CStmt::Block(LOC, stmts.into_iter().map(Box::new).collect())
+}
+
+pub fn if_stmt(cond: CExpr, then: CStmt, otherwise: Option<CStmt>) -> CStmt {
+    CStmt::If(LOC, cond, Box::new(then), otherwise.map(Box::new))
+}
+
+pub fn while_stmt(cond: CExpr, body: CStmt) -> CStmt {
+    CStmt::While(LOC, cond, Box::new(body))
+}
+
+pub fn do_while(body: CStmt, cond: CExpr) -> CStmt {
+    CStmt::DoWhile(LOC, Box::new(body), cond)
+}
+
+/// Switch over the statements, with `case_label` marking where each case
+/// starts
+pub fn switch(e: CExpr, stmts: Vec<CStmt>) -> CStmt {
+    CStmt::Switch(LOC, e, stmts.into_iter().map(Box::new).collect())
+}
+
+/// Case label, or the default label if `e` is `None`
+pub fn case_label(e: Option<CExpr>) -> CStmt {
+    CStmt::Case(LOC, e)
+}
+
+pub fn break_stmt() -> CStmt {
+    CStmt::Break(LOC)
+}
+
+/// Print the value, with the format string as written in the source, so
+/// escapes like `\n` are two characters
+pub fn printf(fmt: Option<&str>, e: CExpr) -> CStmt {
+    CStmt::Print(LOC, fmt.map(|s| s.to_owned()), e)
+}
+
+// expressions
+
+pub fn int(i: CInt) -> CExpr {
+    CExpr::Int(LOC, i)
+}
+
+pub fn float(f: CFloat) -> CExpr {
+    CExpr::Float(LOC, f)
+}
+
+pub fn chr(c: CChar) -> CExpr {
+    CExpr::Char(LOC, c)
+}
+
+/// String literal, with escapes as in [`printf`](fn.printf.html)
+pub fn string(s: &str) -> CExpr {
+    CExpr::Str(LOC, s.to_owned())
+}
+
+pub fn ident(name: &str) -> CExpr {
+    CExpr::Ident(LOC, name.to_owned())
+}
+
+pub fn unop(op: COp, e: CExpr) -> CExpr {
+    CExpr::UnOp(LOC, op, Box::new(e))
+}
+
+pub fn binop(op: COp, l: CExpr, r: CExpr) -> CExpr {
+    CExpr::BinOp(LOC, op, Box::new(l), Box::new(r))
+}
+
+pub fn call(name: &str, args: Vec<CExpr>) -> CExpr {
+    CExpr::Call(LOC, name.to_owned(), boxed(args))
+}
+
+pub fn index(name: &str, e: CExpr) -> CExpr {
+    CExpr::Index(LOC, name.to_owned(), Box::new(e))
+}
+
+pub fn cast(t: CType, e: CExpr) -> CExpr {
+    CExpr::Cast(LOC, t, Box::new(e))
+}
+
+pub fn ternary(cond: CExpr, l: CExpr, r: CExpr) -> CExpr {
+    CExpr::Ternary(LOC, Box::new(cond), Box::new(l), Box::new(r))
+}
+
+pub fn comma(l: CExpr, r: CExpr) -> CExpr {
+    CExpr::Comma(LOC, Box::new(l), Box::new(r))
+}
@@ -0,0 +1,137 @@
+use std::fmt::{Debug, Formatter, Error};
+
+use ast::*;
+use env::SymVal;
+
+/// Variable with its storage resolved at compile time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Var<'input> {
+    /// Slot in the frame of the running function
+    Local(usize),
+    /// Slot in the global table
+    Global(usize),
+    /// Name that is declared nowhere, an error once used
+    Undeclared(&'input str),
+}
+
+/// Instructions of the stack machine. Locations are spans of the source,
+/// used for errors, except for `Branch` which only needs the start.
+#[derive(Clone)]
+pub enum Op<'input> {
+    /// Push a constant
+    Const(SymVal),
+    /// Discard the top of the stack
+    Pop,
+    /// Push the value of a variable
+    Load(Var<'input>, CLoc),
+    /// Pop an index and push that element of an array
+    LoadIndex(Var<'input>, CLoc),
+    /// (Re)declare a variable, popping its array size when sized
+    Decl { var: Var<'input>, sized: bool, loc: CLoc, size_loc: CLoc },
+    /// Check the index on top of the stack against the array's length
+    CheckIndex(Var<'input>, CLoc),
+    /// Pop a value, and an index when indexed, and assign it converted to
+    /// the type of the variable or its elements
+    Store { var: Var<'input>, indexed: bool, t: CType, loc: CLoc, val_loc: CLoc },
+    /// Promote the top of the stack to a number for arithmetic
+    Operand(CLoc),
+    BinOp(COp, CLoc),
+    UnOp(COp, CLoc),
+    Cast(CType, CLoc),
+    /// Convert the top of the stack to a bool, as conditions do
+    Truth(CLoc),
+    /// Convert the top of the stack to an integer to switch on
+    CaseValue(CLoc),
+    /// Pop the values of the case labels and the switched on value, then
+    /// jump to the first matching label, the default or the end
+    Switch { targets: Vec<usize>, default: Option<usize>, end: usize },
+    Jump(usize),
+    /// Pop a bool and jump if it is false
+    JumpIfFalse(usize),
+    /// Pop the arguments and call a function, which has to return a value
+    /// if it is used in an expression
+    Call { func: usize, args: usize, value: bool, loc: CLoc },
+    /// Return from the function, popping the return value if any
+    Return(bool),
+    /// End of a statement run on its own, which did not return
+    End,
+    /// Pop a value and print it
+    Print(Option<CString>, CLoc),
+    /// Stop with a run-time error
+    Fail(String, CLoc),
+    /// Count a statement against the step limit
+    Step(CLoc),
+    /// Count a statement against the step limit without reporting it, as
+    /// it is part of one that was
+    Count(CLoc),
+    /// Record which way the condition on top of the stack went, for the
+    /// statement at the location
+    Branch(usize),
+}
+
+/// Compiled function, where parameters take the first slots
+#[derive(Debug, Clone)]
+pub struct Function<'input> {
+    pub name: &'input str,
+    /// Name and type of each local slot
+    pub slots: Vec<(&'input str, CType)>,
+    pub code: Vec<Op<'input>>,
+}
+
+/// Global variable, with the value and size it starts out with
+#[derive(Debug, Clone)]
+pub struct Global<'input> {
+    pub name: &'input str,
+    pub t: CType,
+    pub size: Option<usize>,
+    pub val: Option<SymVal>,
+    pub decl: Option<CLoc>,
+}
+
+/// Compiled program. The code of `entry` runs first, which allocates the
+/// global arrays, or is the statement or expression being run on its own.
+#[derive(Debug, Clone)]
+pub struct Program<'input> {
+    pub funcs: Vec<Function<'input>>,
+    pub globals: Vec<Global<'input>>,
+    pub entry: Function<'input>,
+    /// Function called after the entry code, with argc and argv
+    pub main: Option<usize>,
+}
+
+impl<'input> Debug for Op<'input> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        use self::Op::*;
+        match *self {
+            Const(ref v) => write!(fmt, "const {:?}", v),
+            Pop => write!(fmt, "pop"),
+            Load(v, _) => write!(fmt, "load {:?}", v),
+            LoadIndex(v, _) => write!(fmt, "load_index {:?}", v),
+            Decl { var, sized, .. } => write!(fmt, "decl {:?}{}", var, if sized { " sized" } else { "" }),
+            CheckIndex(v, _) => write!(fmt, "check_index {:?}", v),
+            Store { var, indexed, ref t, .. } =>
+                write!(fmt, "store {:?}{} as {:?}", var, if indexed { " indexed" } else { "" }, t),
+            Operand(_) => write!(fmt, "operand"),
+            BinOp(op, _) => write!(fmt, "bin_op {:?}", op),
+            UnOp(op, _) => write!(fmt, "un_op {:?}", op),
+            Cast(ref t, _) => write!(fmt, "cast {:?}", t),
+            Truth(_) => write!(fmt, "truth"),
+            CaseValue(_) => write!(fmt, "case_value"),
+            Switch { ref targets, default, end } => write!(fmt, "switch {:?} {:?} {}", targets, default, end),
+            Jump(t) => write!(fmt, "jump {}", t),
+            JumpIfFalse(t) => write!(fmt, "jump_if_false {}", t),
+            Call { func, args, value, .. } =>
+                write!(fmt, "call {} {}{}", func, args, if value { " value" } else { "" }),
+            Return(v) => write!(fmt, "return{}", if v { " value" } else { "" }),
+            End => write!(fmt, "end"),
+            Print(ref f, _) => match *f {
+                Some(ref f) => write!(fmt, "print {:?}", f.as_str()),
+                None => write!(fmt, "print"),
+            },
+            Fail(ref msg, _) => write!(fmt, "fail {:?}", msg),
+            Step(_) => write!(fmt, "step"),
+            Count(_) => write!(fmt, "count"),
+            Branch(_) => write!(fmt, "branch"),
+        }
+    }
+}
@@ -4,73 +4,336 @@ use error::CError;
 
 // checker functions
 
-pub fn analyze_prog<'input, 'err>(
-    ast: &'input CProg<'input>,
+pub fn analyze_prog<'input>(
+    ast: &'input CProg,
 ) -> Result<(FuncTab<'input>, SymTab<'input>), CError>
+{
+    let (vtab, symtab, mut errors) = declare_prog(ast);
+
+    // check for main function
+    match vtab.get_func("main") {
+        None => errors.push((format!("Function 'main' missing"), None)),
+        _ => (),
+    };
+
+    // check function bodies
+    for elem in ast.iter() {
+        if let CProgElem::Func(_, ref func) = *elem {
+            errors.extend(analyze_func(func, &vtab, &symtab));
+        }
+    }
+
+    // check if local errors
+    match errors.len() {
+        0 => Ok((vtab, symtab)),
+        _ => Err(CError::CheckerError(errors)),
+    }
+}
+
+/// Tables of the functions and globals a program declares, which are
+/// filled in also when some are declared wrongly, with errors for those
+pub fn declare_prog<'input>(
+    ast: &'input CProg,
+) -> (FuncTab<'input>, SymTab<'input>, Vec<(String, Option<CLoc>)>)
 {
     let mut vtab = FuncTab::new();
     let mut symtab = SymTab::new();
-    let mut errors: Vec<(String, Option<usize>)> = vec![];
+    let mut errors: Vec<(String, Option<CLoc>)> = vec![];
+    let mut externs = vec![];
 
     // check each element
     for elem in ast.iter() {
         match *elem {
-            CProgElem::Decl((l, _), ref t, ref name, _) => {
-                match symtab.insert(*name, t.clone(), None, None, Some(l)) {
-                    Some(_) => errors.push((format!("Variable '{}' already declared", name), Some(l))),
+            CProgElem::Decl(loc, ref t, ref name, ref so) => {
+                let t = match *so {
+                    Some(_) => CType::Ref(Box::new(t.clone())),
+                    None => t.clone(),
+                };
+                match symtab.insert(name, t, None, None, Some(loc)) {
+                    Some(_) => errors.push((format!("Variable '{}' already declared", name), Some(loc))),
                     None => (),
                 };
             },
 
-            CProgElem::Func((l, _), ref func) => {
+            // checked once every definition is known
+            CProgElem::Extern(loc, ref t, ref name) => externs.push((loc, t, name)),
+
+            CProgElem::Func(loc, ref func) => {
                 let CFunc { ref proto, .. } = *func;
                 let CProto { ref name, .. } = *proto;
 
-                match vtab.insert(*name, proto, Some(func)) {
-                    Some((_, Some(_))) => errors.push((format!("Function '{}' already declared", name), Some(l))),
+                match vtab.insert(name, proto, Some(func)) {
+                    Some((_, Some(_))) => errors.push((format!("Function '{}' already declared", name), Some(loc))),
                     _ => (),
                 };
             },
 
-            CProgElem::Proto((l, _), ref proto) => {
+            CProgElem::Proto(loc, ref proto) => {
                 let CProto { ref name, .. } = *proto;
 
-                match vtab.insert(*name, proto, None) {
-                    Some(_) => errors.push((format!("Function '{}' already defined", name), Some(l))),
+                match vtab.insert(name, proto, None) {
+                    Some(_) => errors.push((format!("Function '{}' already defined", name), Some(loc))),
                     None => (),
                 };
             },
 
+            CProgElem::Include(..) |
             CProgElem::Error => (),
         };
     };
 
-    // check for main function
-    match vtab.get_func("main") {
-        None => errors.push((format!("Function 'main' missing"), None)),
-        _ => (),
-    };
+    for (loc, t, name) in externs {
+        match symtab.get_type(name) {
+            Some((ref t2, _)) if t2 == t => (),
+            Some(_) => errors.push((format!("Variable '{}' declared 'extern' with another type", name), Some(loc))),
+            None => errors.push((format!("Variable '{}' declared 'extern' but never defined", name), Some(loc))),
+        }
+    }
 
-    // check if local errors
-    match errors.len() {
-        0 => Ok((vtab, symtab)),
-        _ => Err(CError::CheckerError(errors)),
+    (vtab, symtab, errors)
+}
+
+pub fn analyze_func<'input>(
+    func: &'input CFunc,
+    vtab: &FuncTab<'input>,
+    global_symtab: &SymTab<'input>,
+) -> Vec<(String, Option<CLoc>)>
+{
+    let mut symtab = SymTab::new();
+    let mut errors = vec![];
+
+    let CFunc { ref proto, ref body } = *func;
+
+    for &(ref t, ref pid) in proto.params.iter() {
+        symtab.insert(pid, t.clone(), None, None, None);
+    }
+
+    analyze_stmt(body, vtab, global_symtab, &mut symtab, false, &mut errors);
+
+    errors
+}
+
+fn analyze_stmt<'input>(
+    stmt: &'input CStmt,
+    vtab: &FuncTab<'input>,
+    global_symtab: &SymTab<'input>,
+    symtab: &mut SymTab<'input>,
+    breakable: bool,
+    errors: &mut Vec<(String, Option<CLoc>)>,
+)
+{
+    match *stmt {
+        CStmt::Decl(loc, ref t, ref id, ref eo) => {
+            if let Some(ref e) = *eo {
+                type_of_expr(e, vtab, global_symtab, symtab, errors);
+            }
+            symtab.insert(id, t.clone(), None, None, Some(loc));
+        },
+        CStmt::Assign(_, _, ref eo, ref e) => {
+            if let Some(ref i) = *eo {
+                type_of_expr(i, vtab, global_symtab, symtab, errors);
+            }
+            type_of_expr(e, vtab, global_symtab, symtab, errors);
+        },
+        CStmt::Call(_, _, ref args) => {
+            for e in args.iter() {
+                type_of_expr(e, vtab, global_symtab, symtab, errors);
+            }
+        },
+        CStmt::Return(_, ref eo) => {
+            if let Some(ref e) = *eo {
+                type_of_expr(e, vtab, global_symtab, symtab, errors);
+            }
+        },
+        CStmt::Block(_, ref stmts) => {
+            symtab.enter_scope();
+            for s in stmts.iter() {
+                analyze_stmt(s, vtab, global_symtab, symtab, breakable, errors);
+            }
+            symtab.leave_scope();
+        },
+        CStmt::Decls(_, ref stmts) => {
+            for s in stmts.iter() {
+                analyze_stmt(s, vtab, global_symtab, symtab, breakable, errors);
+            }
+        },
+        CStmt::If(_, ref cond, ref s, ref o) => {
+            type_of_expr(cond, vtab, global_symtab, symtab, errors);
+            analyze_scoped(s, vtab, global_symtab, symtab, breakable, errors);
+            if let Some(ref es) = *o {
+                analyze_scoped(es, vtab, global_symtab, symtab, breakable, errors);
+            }
+        },
+        CStmt::While(_, ref cond, ref s) => {
+            type_of_expr(cond, vtab, global_symtab, symtab, errors);
+            analyze_scoped(s, vtab, global_symtab, symtab, true, errors);
+        },
+        CStmt::DoWhile(_, ref s, ref cond) => {
+            analyze_scoped(s, vtab, global_symtab, symtab, true, errors);
+            type_of_expr(cond, vtab, global_symtab, symtab, errors);
+        },
+        CStmt::Switch(_, ref e, ref stmts) => {
+            type_of_expr(e, vtab, global_symtab, symtab, errors);
+            symtab.enter_scope();
+
+            let mut values = vec![];
+            let mut has_default = false;
+            for s in stmts.iter() {
+                match **s {
+                    CStmt::Case(loc, Some(ref ce)) => match const_case(ce) {
+                        Some(v) => {
+                            if values.contains(&v) {
+                                errors.push((format!("Duplicate case value '{}'", v), Some(loc)));
+                            }
+                            values.push(v);
+                        },
+                        None => errors.push((format!("Case label '{:?}' is not an integer constant", ce), Some(loc))),
+                    },
+                    CStmt::Case(loc, None) => {
+                        if has_default {
+                            errors.push((format!("Multiple default labels in one switch"), Some(loc)));
+                        }
+                        has_default = true;
+                    },
+                    _ => analyze_stmt(s, vtab, global_symtab, symtab, true, errors),
+                }
+            }
+            symtab.leave_scope();
+        },
+        CStmt::Case(loc, _) =>
+            errors.push((format!("Case label not within a switch statement"), Some(loc))),
+        CStmt::Break(loc) => if !breakable {
+            errors.push((format!("Break statement not within loop or switch"), Some(loc)));
+        },
+        CStmt::Print(_, _, ref e) => {
+            type_of_expr(e, vtab, global_symtab, symtab, errors);
+        },
+        CStmt::Error => (),
     }
 }
 
-// pub fn analyze_func<'input, 'err>(
-//     func: &'input CFunc<'input>,
-// ) -> Result<SymTab, Vec<CheckErr>>
-// {
-//     let mut symtab = SymTab::new();
-//     let mut errors = vec![];
-
-//     // let CFunc { ref proto, .. } = *func;
-//     // let CProto { ref name, .. } = *proto;
-
-//     // check if local errors
-//     match errors.len() {
-//         0 => Ok(symtab),
-//         _ => Err(errors),
-//     }
-// }
+/// Check a statement under `if`, `else`, `while` or `do`, which is a scope
+/// of its own even when it is not a block
+fn analyze_scoped<'input>(
+    stmt: &'input CStmt,
+    vtab: &FuncTab<'input>,
+    global_symtab: &SymTab<'input>,
+    symtab: &mut SymTab<'input>,
+    breakable: bool,
+    errors: &mut Vec<(String, Option<CLoc>)>,
+)
+{
+    if let CStmt::Block(..) = *stmt {
+        return analyze_stmt(stmt, vtab, global_symtab, symtab, breakable, errors);
+    }
+    symtab.enter_scope();
+    analyze_stmt(stmt, vtab, global_symtab, symtab, breakable, errors);
+    symtab.leave_scope();
+}
+
+/// Infer the type of an expression, where `None` means void or unknown
+fn type_of_expr<'input>(
+    expr: &'input CExpr,
+    vtab: &FuncTab<'input>,
+    global_symtab: &SymTab<'input>,
+    symtab: &SymTab<'input>,
+    errors: &mut Vec<(String, Option<CLoc>)>,
+) -> Option<CType>
+{
+    match *expr {
+        CExpr::Int(..) => Some(CType::Int),
+        CExpr::Float(..) => Some(CType::Float),
+        CExpr::Char(..) => Some(CType::Char),
+        CExpr::Str(..) => Some(CType::Ref(Box::new(CType::Char))),
+        CExpr::Ident(_, ref id) => match symtab.get_type(id) {
+            Some((t, _)) => Some(t),
+            None => match global_symtab.get_type(id) {
+                Some((t, _)) => Some(t),
+                None => None,
+            },
+        },
+        CExpr::UnOp(_, op, ref e) => {
+            let t = type_of_expr(e, vtab, global_symtab, symtab, errors);
+            match op {
+                COp::Neg => t,
+                _ => Some(CType::Int),
+            }
+        },
+        CExpr::BinOp(_, op, ref e1, ref e2) => {
+            let t1 = type_of_expr(e1, vtab, global_symtab, symtab, errors);
+            let t2 = type_of_expr(e2, vtab, global_symtab, symtab, errors);
+            match op {
+                COp::Add | COp::Sub | COp::Mul | COp::Div => match (t1, t2) {
+                    (Some(t1), Some(t2)) => arith_type(&t1, &t2),
+                    _ => None,
+                },
+                _ => Some(CType::Int),
+            }
+        },
+        CExpr::Call(_, ref id, ref args) => {
+            for e in args.iter() {
+                type_of_expr(e, vtab, global_symtab, symtab, errors);
+            }
+            match vtab.get_proto(id) {
+                Some(proto) => proto.ret.clone(),
+                None => None,
+            }
+        },
+        CExpr::Index(_, ref id, ref e) => {
+            type_of_expr(e, vtab, global_symtab, symtab, errors);
+            match type_of_expr(&CExpr::Ident((0, 0), id.clone()), vtab, global_symtab, symtab, errors) {
+                Some(CType::Ref(t)) => Some(*t),
+                _ => None,
+            }
+        },
+        CExpr::Cast(loc, ref t, ref e) => {
+            match (type_of_expr(e, vtab, global_symtab, symtab, errors), t) {
+                (Some(CType::Ref(_)), &CType::Ref(_)) => (),
+                (Some(ref t1 @ CType::Ref(_)), _) | (Some(ref t1), &CType::Ref(_)) =>
+                    errors.push((format!("Cannot cast '{:?}' to '{:?}'", t1, t), Some(loc))),
+                _ => (),
+            };
+            Some(t.clone())
+        },
+        CExpr::Ternary(loc, ref cond, ref e1, ref e2) => {
+            type_of_expr(cond, vtab, global_symtab, symtab, errors);
+            let t1 = type_of_expr(e1, vtab, global_symtab, symtab, errors);
+            let t2 = type_of_expr(e2, vtab, global_symtab, symtab, errors);
+            match (t1, t2) {
+                (Some(t1), Some(t2)) => match arith_type(&t1, &t2) {
+                    Some(t) => Some(t),
+                    None if t1 == t2 => Some(t1),
+                    None => {
+                        errors.push((format!("Mismatched types '{:?}' and '{:?}' in conditional expression", t1, t2), Some(loc)));
+                        None
+                    },
+                },
+                _ => None,
+            }
+        },
+        CExpr::Comma(_, ref e1, ref e2) => {
+            type_of_expr(e1, vtab, global_symtab, symtab, errors);
+            type_of_expr(e2, vtab, global_symtab, symtab, errors)
+        },
+        CExpr::Error => None,
+    }
+}
+
+/// Value of a constant case label
+fn const_case<'input>(expr: &'input CExpr) -> Option<i32> {
+    match *expr {
+        CExpr::Int(_, i) => Some(i),
+        CExpr::Char(_, c) => Some(c as i32),
+        CExpr::UnOp(_, COp::Neg, ref e) => const_case(e).map(|i| -i),
+        _ => None,
+    }
+}
+
+/// Result type of arithmetic on two scalar types, if both are numbers
+fn arith_type(t1: &CType, t2: &CType) -> Option<CType> {
+    match (t1, t2) {
+        (&CType::Ref(_), _) | (_, &CType::Ref(_)) => None,
+        (&CType::Float, _) | (_, &CType::Float) => Some(CType::Float),
+        _ => Some(CType::Int),
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::HashMap;
+
+use ast::*;
+use bytecode::{Function, Global, Op, Program, Var};
+use engine::{loc_of_expr, loc_of_stmt};
+use env::{FuncTab, SymTab, SymVal};
+use error::CError;
+use resolver::{self, Resolver, Scopes};
+
+/// Lowers a checked AST to bytecode, with names resolved to slots by the
+/// resolver
+pub struct Compiler<'a, 'input: 'a> {
+    vtab: &'a FuncTab<'input>,
+    global_symtab: &'a SymTab<'input>,
+    resolver: Resolver<'a, 'input>,
+    funcs: Vec<Option<Function<'input>>>,
+    func_index: HashMap<&'input str, usize>,
+    /// Functions referenced, but not compiled yet
+    pending: Vec<(usize, &'input CFunc)>,
+}
+
+/// Names and slots of the locals a snippet was given by a symbol table
+pub type Seeded<'input> = Vec<(&'input str, usize)>;
+
+/// State while compiling a single function
+struct Scope<'input> {
+    scopes: Scopes<'input>,
+    code: Vec<Op<'input>>,
+    /// Jumps to patch at the end of each enclosing loop or switch
+    breaks: Vec<Vec<usize>>,
+}
+
+impl<'input> Scope<'input> {
+    fn new(scopes: Scopes<'input>) -> Scope<'input> {
+        Scope {
+            scopes: scopes,
+            code: vec![],
+            breaks: vec![],
+        }
+    }
+
+    fn var_of_expr(&self, expr: &CExpr, id: &'input str) -> Var<'input> {
+        var(self.scopes.of_expr(expr).unwrap_or(resolver::Scope::Undeclared), id)
+    }
+
+    fn var_of_stmt(&self, stmt: &CStmt, id: &'input str) -> Var<'input> {
+        var(self.scopes.of_stmt(stmt).unwrap_or(resolver::Scope::Undeclared), id)
+    }
+
+    /// Names and slots of the locals given by a symbol table
+    fn seeded(&self) -> Seeded<'input> {
+        self.scopes.seeded.iter().map(|&i| (self.scopes.locals[i].name, i)).collect()
+    }
+
+    /// Slots of the locals declared outside any block, which outlive the code
+    fn declared(&self) -> Vec<usize> {
+        self.scopes.locals.iter().enumerate()
+            .filter(|&(i, l)| l.within.is_none() && !self.scopes.seeded.contains(&i))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn emit(&mut self, op: Op<'input>) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    /// Point a jump emitted earlier at the next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len();
+        match self.code[at] {
+            Op::Jump(ref mut t) | Op::JumpIfFalse(ref mut t) => *t = target,
+            _ => unreachable!(),
+        }
+    }
+
+    fn into_function(self, name: &'input str) -> Function<'input> {
+        let slots = self.scopes.locals.into_iter().map(|l| (l.name, l.t)).collect();
+        Function { name: name, slots: slots, code: self.code }
+    }
+}
+
+fn var<'input>(scope: resolver::Scope, id: &'input str) -> Var<'input> {
+    match scope {
+        resolver::Scope::Local(i) => Var::Local(i),
+        resolver::Scope::Global(i) => Var::Global(i),
+        resolver::Scope::Undeclared => Var::Undeclared(id),
+    }
+}
+
+/// Compile a program with its function and global symbol tables from the checker
+pub fn compile_prog<'a, 'input>(
+    ast: &'input CProg,
+    vtab: &'a FuncTab<'input>,
+    global_symtab: &'a SymTab<'input>,
+) -> Result<Program<'input>, CError>
+{
+    let mut compiler = Compiler::new(vtab, global_symtab);
+
+    // allocate global arrays
+    let sizes: Vec<_> = ast.iter().filter_map(|elem| match *elem {
+        CProgElem::Decl(_, _, _, Some(ref e)) => Some(e),
+        _ => None,
+    }).collect();
+    let mut scope = Scope::new(compiler.resolver.global_sizes(&sizes));
+    for elem in ast.iter() {
+        if let CProgElem::Decl(loc, _, ref id, Some(ref e)) = *elem {
+            try!(compiler.expr(e, &mut scope));
+            let var = compiler.global(id);
+            let size_loc = try!(loc_of_expr(e));
+            scope.emit(Op::Decl { var: var, sized: true, loc: loc, size_loc: size_loc });
+        }
+    }
+    scope.emit(Op::End);
+
+    let main = match compiler.func("main") {
+        Some(i) => i,
+        None => return Err(CError::UnknownError("Function 'main' missing".to_owned())),
+    };
+
+    compiler.finish(scope.into_function("<init>"), Some(main))
+}
+
+/// Compile a statement on its own, where the local symbol table gives the
+/// variables in scope. Also returns the slots of those that were used, and
+/// of the locals it declares outside its blocks.
+pub fn compile_stmt<'a, 'input>(
+    stmt: &'input CStmt,
+    vtab: &'a FuncTab<'input>,
+    global_symtab: &'a SymTab<'input>,
+    local_symtab: &'a SymTab<'input>,
+) -> Result<(Program<'input>, Seeded<'input>, Vec<usize>), CError>
+{
+    let mut compiler = Compiler::new(vtab, global_symtab);
+
+    let mut scope = Scope::new(compiler.resolver.snippet_stmt(stmt, local_symtab));
+    try!(compiler.stmt(stmt, &mut scope));
+    scope.emit(Op::End);
+
+    let seeded = scope.seeded();
+    let declared = scope.declared();
+    Ok((try!(compiler.finish(scope.into_function("<stmt>"), None)), seeded, declared))
+}
+
+/// Compile an expression on its own, like `compile_stmt`
+pub fn compile_expr<'a, 'input>(
+    expr: &'input CExpr,
+    vtab: &'a FuncTab<'input>,
+    global_symtab: &'a SymTab<'input>,
+    local_symtab: &'a SymTab<'input>,
+) -> Result<(Program<'input>, Seeded<'input>), CError>
+{
+    let mut compiler = Compiler::new(vtab, global_symtab);
+
+    let mut scope = Scope::new(compiler.resolver.snippet_expr(expr, local_symtab));
+    try!(compiler.expr(expr, &mut scope));
+    scope.emit(Op::Return(true));
+
+    let seeded = scope.seeded();
+    Ok((try!(compiler.finish(scope.into_function("<expr>"), None)), seeded))
+}
+
+impl<'a, 'input> Compiler<'a, 'input> {
+    fn new(vtab: &'a FuncTab<'input>, global_symtab: &'a SymTab<'input>) -> Compiler<'a, 'input> {
+        Compiler {
+            vtab: vtab,
+            global_symtab: global_symtab,
+            resolver: Resolver::new(global_symtab),
+            funcs: vec![],
+            func_index: HashMap::new(),
+            pending: vec![],
+        }
+    }
+
+    /// Compile the functions reachable from the entry code
+    fn finish(mut self, entry: Function<'input>, main: Option<usize>) -> Result<Program<'input>, CError> {
+        while let Some((i, func)) = self.pending.pop() {
+            let f = try!(self.function(func));
+            self.funcs[i] = Some(f);
+        }
+
+        // globals start out as in the symbol table
+        let global_symtab = self.global_symtab;
+        let globals = self.resolver.globals().iter().map(|&(name, ref t)| Global {
+            name: name,
+            t: t.clone(),
+            size: global_symtab.get_type(name).and_then(|(_, s)| s),
+            val: global_symtab.get_val(name),
+            decl: global_symtab.get_decl(name),
+        }).collect();
+
+        Ok(Program {
+            funcs: self.funcs.into_iter().map(|f| f.unwrap()).collect(),
+            globals: globals,
+            entry: entry,
+            main: main,
+        })
+    }
+
+    fn function(&mut self, func: &'input CFunc) -> Result<Function<'input>, CError> {
+        let CFunc { ref proto, ref body } = *func;
+
+        let mut scope = Scope::new(self.resolver.func(func));
+        try!(self.stmt(body, &mut scope));
+        scope.emit(Op::Return(false));
+
+        Ok(scope.into_function(&proto.name))
+    }
+
+    /// Index of a function with a body, compiled later if new
+    fn func(&mut self, id: &'input str) -> Option<usize> {
+        if let Some(&i) = self.func_index.get(id) {
+            return Some(i);
+        }
+        let f = self.vtab.get_func(id)?;
+        let i = self.funcs.len();
+        self.funcs.push(None);
+        self.func_index.insert(id, i);
+        self.pending.push((i, f));
+        Some(i)
+    }
+
+    fn global(&mut self, id: &'input str) -> Var<'input> {
+        var(self.resolver.global(id), id)
+    }
+
+    fn type_of(&self, var: Var<'input>, scope: &Scope<'input>) -> CType {
+        match var {
+            Var::Local(i) => scope.scopes.locals[i].t.clone(),
+            Var::Global(i) => self.resolver.globals()[i].1.clone(),
+            // never assigned, as that fails first
+            Var::Undeclared(_) => CType::Int,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &'input CStmt, scope: &mut Scope<'input>) -> Result<(), CError> {
+        // loops jump back here, to count each time round
+        let top = scope.code.len();
+        if let Some(loc) = loc_of_stmt(stmt) {
+            scope.emit(Op::Step(loc));
+        }
+
+        match *stmt {
+            CStmt::Decl(..) => try!(self.decl(stmt, scope)),
+            CStmt::Assign(..) => try!(self.assign(stmt, scope)),
+            CStmt::Call(loc, ref id, ref args) => try!(self.call(loc, id, args, false, scope)),
+            CStmt::Return(_, ref eo) => {
+                if let Some(ref e) = *eo {
+                    try!(self.expr(e, scope));
+                }
+                scope.emit(Op::Return(eo.is_some()));
+            },
+            CStmt::Block(_, ref stmts) => {
+                for s in stmts.iter() {
+                    try!(self.stmt(s, scope));
+                }
+            },
+            CStmt::Decls(_, ref stmts) => {
+                for (i, s) in stmts.iter().enumerate() {
+                    // reported once, as the statement it was written as
+                    match (i, loc_of_stmt(s)) {
+                        (0, Some(loc)) => scope.emit(Op::Step(loc)),
+                        (_, Some(loc)) => scope.emit(Op::Count(loc)),
+                        _ => 0,
+                    };
+                    match **s {
+                        CStmt::Decl(..) => try!(self.decl(s, scope)),
+                        CStmt::Assign(..) => try!(self.assign(s, scope)),
+                        _ => return Err(CError::UnknownError(format!("unexpected stmt '{:?}' in declaration", s))),
+                    }
+                }
+            },
+            CStmt::If(loc, ref cond, ref s, ref o) => {
+                try!(self.expr(cond, scope));
+                scope.emit(Op::Truth(loc));
+                scope.emit(Op::Branch(loc.0));
+                let jump_else = scope.emit(Op::JumpIfFalse(0));
+                try!(self.stmt(s, scope));
+                match *o {
+                    Some(ref es) => {
+                        let jump_end = scope.emit(Op::Jump(0));
+                        scope.patch(jump_else);
+                        try!(self.stmt(es, scope));
+                        scope.patch(jump_end);
+                    },
+                    None => scope.patch(jump_else),
+                }
+            },
+            CStmt::While(loc, ref cond, ref s) => {
+                try!(self.expr(cond, scope));
+                scope.emit(Op::Truth(loc));
+                scope.emit(Op::Branch(loc.0));
+                let jump_end = scope.emit(Op::JumpIfFalse(0));
+
+                scope.breaks.push(vec![]);
+                try!(self.stmt(s, scope));
+                scope.emit(Op::Jump(top));
+
+                scope.patch(jump_end);
+                self.patch_breaks(scope);
+            },
+            CStmt::DoWhile(loc, ref s, ref cond) => {
+                scope.breaks.push(vec![]);
+                try!(self.stmt(s, scope));
+
+                try!(self.expr(cond, scope));
+                scope.emit(Op::Truth(try!(loc_of_expr(cond))));
+                scope.emit(Op::Branch(loc.0));
+                let jump_end = scope.emit(Op::JumpIfFalse(0));
+                scope.emit(Op::Jump(top));
+
+                scope.patch(jump_end);
+                self.patch_breaks(scope);
+            },
+            CStmt::Switch(_, ref e, ref stmts) => {
+                try!(self.expr(e, scope));
+                scope.emit(Op::CaseValue(try!(loc_of_expr(e))));
+
+                // every label is evaluated before jumping
+                for s in stmts.iter() {
+                    if let CStmt::Case(_, Some(ref ce)) = **s {
+                        try!(self.expr(ce, scope));
+                        scope.emit(Op::CaseValue(try!(loc_of_expr(ce))));
+                    }
+                }
+                let switch = scope.emit(Op::Switch { targets: vec![], default: None, end: 0 });
+
+                let mut targets = vec![];
+                let mut default = None;
+                scope.breaks.push(vec![]);
+                for s in stmts.iter() {
+                    match **s {
+                        CStmt::Case(_, Some(_)) => targets.push(scope.code.len()),
+                        CStmt::Case(_, None) => default = Some(scope.code.len()),
+                        _ => (),
+                    }
+                    try!(self.stmt(s, scope));
+                }
+
+                let end = scope.code.len();
+                scope.code[switch] = Op::Switch { targets: targets, default: default, end: end };
+                self.patch_breaks(scope);
+            },
+            CStmt::Case(..) => (),
+            CStmt::Break(_) => {
+                let jump = scope.emit(Op::Jump(0));
+                match scope.breaks.last_mut() {
+                    Some(breaks) => breaks.push(jump),
+                    None => return Err(CError::UnknownError("unexpected 'break' outside loop or switch".to_owned())),
+                }
+            },
+            CStmt::Print(loc, ref fmto, ref e) => {
+                try!(self.expr(e, scope));
+                scope.emit(Op::Print(fmto.clone(), loc));
+            },
+            CStmt::Error => return Err(CError::UnknownError(format!("unexpected stmt '{:?}' in ast", stmt))),
+        }
+
+        Ok(())
+    }
+
+    fn decl(&mut self, stmt: &'input CStmt, scope: &mut Scope<'input>) -> Result<(), CError> {
+        let (loc, id, eo) = match *stmt {
+            CStmt::Decl(loc, _, ref id, ref eo) => (loc, id, eo),
+            _ => unreachable!(),
+        };
+        let size_loc = match *eo {
+            Some(ref e) => {
+                try!(self.expr(e, scope));
+                try!(loc_of_expr(e))
+            },
+            None => loc,
+        };
+        let var = scope.var_of_stmt(stmt, id);
+        scope.emit(Op::Decl { var: var, sized: eo.is_some(), loc: loc, size_loc: size_loc });
+        Ok(())
+    }
+
+    fn assign(&mut self, stmt: &'input CStmt, scope: &mut Scope<'input>) -> Result<(), CError> {
+        let (loc, id, eo, e) = match *stmt {
+            CStmt::Assign(loc, ref id, ref eo, ref e) => (loc, id, eo, e),
+            _ => unreachable!(),
+        };
+        let var = scope.var_of_stmt(stmt, id);
+        if let Some(ref i) = *eo {
+            try!(self.expr(i, scope));
+            scope.emit(Op::CheckIndex(var, try!(loc_of_expr(i))));
+        }
+        try!(self.expr(e, scope));
+
+        let t = match (self.type_of(var, scope), eo.is_some()) {
+            (CType::Ref(t), true) => *t,
+            (t, _) => t,
+        };
+        let val_loc = try!(loc_of_expr(e));
+        scope.emit(Op::Store { var: var, indexed: eo.is_some(), t: t, loc: loc, val_loc: val_loc });
+        Ok(())
+    }
+
+    fn patch_breaks(&mut self, scope: &mut Scope<'input>) {
+        for jump in scope.breaks.pop().unwrap() {
+            scope.patch(jump);
+        }
+    }
+
+    fn call(
+        &mut self,
+        loc: CLoc,
+        id: &'input str,
+        args: &'input [Box<CExpr>],
+        value: bool,
+        scope: &mut Scope<'input>,
+    ) -> Result<(), CError>
+    {
+        let func = match self.func(id) {
+            Some(i) => i,
+            None => {
+                scope.emit(Op::Fail(format!("Function '{}' not initialized", id), loc));
+                return Ok(());
+            },
+        };
+
+        // arguments are evaluated until one is missing, and extra ones never
+        let params = &self.vtab.get_func(id).unwrap().proto.params;
+        for (i, p) in params.iter().enumerate() {
+            match args.get(i) {
+                Some(e) => try!(self.expr(e, scope)),
+                None => {
+                    scope.emit(Op::Fail(format!("Function '{}' missing param '{:?}'", id, p), loc));
+                    return Ok(());
+                },
+            }
+        }
+        scope.emit(Op::Call { func: func, args: params.len(), value: value, loc: loc });
+
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &'input CExpr, scope: &mut Scope<'input>) -> Result<(), CError> {
+        match *expr {
+            CExpr::Int(_, i) => { scope.emit(Op::Const(SymVal::Int(i))); },
+            CExpr::Float(_, f) => { scope.emit(Op::Const(SymVal::Float(f))); },
+            CExpr::Char(_, c) => { scope.emit(Op::Const(SymVal::Char(c))); },
+            CExpr::Str(_, ref s) => {
+                let mut arr: Vec<Box<SymVal>> = s.chars().map(|c| Box::new(SymVal::Char(c))).collect();
+                // add null char
+                arr.push(Box::new(SymVal::Char('\0')));
+                scope.emit(Op::Const(SymVal::Array(arr)));
+            },
+            CExpr::Ident(loc, ref id) => {
+                let var = scope.var_of_expr(expr, id);
+                scope.emit(Op::Load(var, loc));
+            },
+            CExpr::UnOp(loc, op, ref e) => {
+                try!(self.expr(e, scope));
+                scope.emit(Op::UnOp(op, loc));
+            },
+            // logical, where the right operand is only evaluated when needed
+            CExpr::BinOp(loc, COp::And, ref e1, ref e2) => {
+                try!(self.expr(e1, scope));
+                scope.emit(Op::Truth(loc));
+                let jump_false = scope.emit(Op::JumpIfFalse(0));
+                try!(self.expr(e2, scope));
+                scope.emit(Op::Truth(loc));
+                let jump_end = scope.emit(Op::Jump(0));
+                scope.patch(jump_false);
+                scope.emit(Op::Const(SymVal::Bool(false)));
+                scope.patch(jump_end);
+            },
+            CExpr::BinOp(loc, COp::Or, ref e1, ref e2) => {
+                try!(self.expr(e1, scope));
+                scope.emit(Op::Truth(loc));
+                let jump_rhs = scope.emit(Op::JumpIfFalse(0));
+                scope.emit(Op::Const(SymVal::Bool(true)));
+                let jump_end = scope.emit(Op::Jump(0));
+                scope.patch(jump_rhs);
+                try!(self.expr(e2, scope));
+                scope.emit(Op::Truth(loc));
+                scope.patch(jump_end);
+            },
+            CExpr::BinOp(loc, op, ref e1, ref e2) => {
+                try!(self.expr(e1, scope));
+                scope.emit(Op::Operand(loc));
+                try!(self.expr(e2, scope));
+                scope.emit(Op::Operand(loc));
+                scope.emit(Op::BinOp(op, loc));
+            },
+            CExpr::Call(loc, ref id, ref args) => try!(self.call(loc, id, args, true, scope)),
+            CExpr::Index(loc, ref id, ref e) => {
+                try!(self.expr(e, scope));
+                let var = scope.var_of_expr(expr, id);
+                scope.emit(Op::LoadIndex(var, loc));
+            },
+            CExpr::Cast(loc, ref t, ref e) => {
+                try!(self.expr(e, scope));
+                scope.emit(Op::Cast(t.clone(), loc));
+            },
+            CExpr::Ternary(loc, ref cond, ref e1, ref e2) => {
+                try!(self.expr(cond, scope));
+                scope.emit(Op::Truth(loc));
+                let jump_else = scope.emit(Op::JumpIfFalse(0));
+                try!(self.expr(e1, scope));
+                let jump_end = scope.emit(Op::Jump(0));
+                scope.patch(jump_else);
+                try!(self.expr(e2, scope));
+                scope.patch(jump_end);
+            },
+            CExpr::Comma(_, ref e1, ref e2) => {
+                // left value is discarded
+                try!(self.expr(e1, scope));
+                scope.emit(Op::Pop);
+                try!(self.expr(e2, scope));
+            },
+            CExpr::Error => return Err(CError::UnknownError(format!("unexpected expr '{:?}' in ast", expr))),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use ast::*;
+use engine::loc_of_stmt;
+use source::SourceMap;
+
+/// What ran during a run, for reporting coverage
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// Times each statement ran, by location
+    pub stmts: HashMap<usize, u64>,
+    /// Times the condition of each `if` and loop was true and false, by
+    /// location of the statement
+    pub branches: HashMap<usize, (u64, u64)>,
+    /// Calls of each function
+    pub funcs: HashMap<String, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage { stmts: HashMap::new(), branches: HashMap::new(), funcs: HashMap::new() }
+    }
+
+    pub fn stmt(&mut self, loc: usize) {
+        *self.stmts.entry(loc).or_insert(0) += 1;
+    }
+
+    pub fn branch(&mut self, loc: usize, taken: bool) {
+        let counts = self.branches.entry(loc).or_insert((0, 0));
+        match taken {
+            true => counts.0 += 1,
+            false => counts.1 += 1,
+        }
+    }
+
+    pub fn call(&mut self, name: &str) {
+        *self.funcs.entry(name.to_owned()).or_insert(0) += 1;
+    }
+
+    /// Report in LCOV format for the program, including what never ran,
+    /// with a record for each file that has functions. Each line gets the
+    /// count of its most run statement, and the true and false side of each
+    /// condition are branches 0 and 1 of a block
+    pub fn lcov(&self, ast: &CProg, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        for (id, file) in sources.files().iter().enumerate() {
+            let funcs: Vec<(usize, &CFunc)> = ast.iter().filter_map(|elem| match *elem {
+                CProgElem::Func((l, _), ref f) if sources.locate(l).map(|(i, _)| i) == Some(id) => Some((l, f)),
+                _ => None,
+            }).collect();
+            if id == 0 || !funcs.is_empty() {
+                out.push_str(&self.record(&funcs, sources, &file.name));
+            }
+        }
+        out
+    }
+
+    fn record(&self, fs: &[(usize, &CFunc)], sources: &SourceMap, filename: &str) -> String {
+        let line = |loc: usize| sources.line(loc).map_or(0, |(_, l)| l);
+
+        let mut funcs = vec![];
+        let mut stmts = vec![];
+        let mut branches = vec![];
+        for &(l, f) in fs.iter() {
+            funcs.push((line(l), f.proto.name.as_str()));
+            walk(&f.body, &mut stmts, &mut branches);
+        }
+
+        let mut out = String::new();
+        out.push_str("TN:\n");
+        out.push_str(&format!("SF:{}\n", filename));
+
+        for &(l, name) in funcs.iter() {
+            out.push_str(&format!("FN:{},{}\n", l, name));
+        }
+        let mut hit = 0;
+        for &(_, name) in funcs.iter() {
+            let n = self.funcs.get(name).cloned().unwrap_or(0);
+            if n > 0 { hit += 1; }
+            out.push_str(&format!("FNDA:{},{}\n", n, name));
+        }
+        out.push_str(&format!("FNF:{}\nFNH:{}\n", funcs.len(), hit));
+
+        let mut hit = 0;
+        for (block, &loc) in branches.iter().enumerate() {
+            let (t, f) = match self.branches.get(&loc) {
+                Some(&(t, f)) => (t.to_string(), f.to_string()),
+                // never evaluated
+                None => ("-".to_owned(), "-".to_owned()),
+            };
+            for (branch, taken) in vec![t, f].into_iter().enumerate() {
+                if taken != "-" && taken != "0" { hit += 1; }
+                out.push_str(&format!("BRDA:{},{},{},{}\n", line(loc), block, branch, taken));
+            }
+        }
+        out.push_str(&format!("BRF:{}\nBRH:{}\n", branches.len() * 2, hit));
+
+        let mut lines: Vec<(usize, u64)> = vec![];
+        for loc in stmts {
+            let (l, n) = (line(loc), self.stmts.get(&loc).cloned().unwrap_or(0));
+            match lines.iter_mut().find(|&&mut (l2, _)| l2 == l) {
+                Some(entry) => entry.1 = entry.1.max(n),
+                None => lines.push((l, n)),
+            }
+        }
+        lines.sort();
+        for &(l, n) in lines.iter() {
+            out.push_str(&format!("DA:{},{}\n", l, n));
+        }
+        let hit = lines.iter().filter(|&&(_, n)| n > 0).count();
+        out.push_str(&format!("LF:{}\nLH:{}\n", lines.len(), hit));
+
+        out.push_str("end_of_record\n");
+        out
+    }
+}
+
+/// Locations of the statements, and of those with a condition, in source
+/// order
+fn walk(stmt: &CStmt, stmts: &mut Vec<usize>, branches: &mut Vec<usize>) {
+    if let Some((l, _)) = loc_of_stmt(stmt) {
+        stmts.push(l);
+    }
+    match *stmt {
+        CStmt::If((l, _), _, ref s1, ref s2) => {
+            branches.push(l);
+            walk(s1, stmts, branches);
+            if let Some(ref s2) = *s2 {
+                walk(s2, stmts, branches);
+            }
+        },
+        CStmt::While((l, _), _, ref s) |
+        CStmt::DoWhile((l, _), ref s, _) => {
+            branches.push(l);
+            walk(s, stmts, branches);
+        },
+        CStmt::Block(_, ref ss) |
+        CStmt::Decls(_, ref ss) |
+        CStmt::Switch(_, _, ref ss) => {
+            for s in ss.iter() {
+                walk(s, stmts, branches);
+            }
+        },
+        _ => (),
+    }
+}
@@ -0,0 +1,524 @@
+//! Debug adapter, speaking the Debug Adapter Protocol over a pair of
+//! streams such as stdin and stdout, so editors can step through programs
+//! like the debugger in the terminal does.
+//!
+//! Programs run on the tree-walker, whose `Repl` hands each statement to
+//! the adapter. When it stops there, requests are answered from the symbol
+//! tables until the client resumes the program, and what the program
+//! prints is sent as output events.
+
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter, Error};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use ast::*;
+use engine::Context;
+use env::{SymTab, SymVal};
+use error::{CError, ErrorPrinter};
+use json::Json;
+use lsp::{read_message, write_message};
+use pretty;
+use resolver::Resolution;
+use source::{FileId, SourceMap};
+
+/// Id of the only thread, as programs have one
+const THREAD: usize = 1;
+/// Variables reference of the globals. The locals of a frame have the
+/// frame's index after it, counting from main
+const GLOBALS: usize = 1;
+
+/// Serve the requests read from `input` until it ends or the client
+/// disconnects, running the program it launches once it is configured
+pub fn serve(input: Box<dyn BufRead>, output: Box<dyn Write>) -> io::Result<()> {
+    let adapter = Rc::new(RefCell::new(Adapter::new(input, output)));
+
+    // breakpoints and such come before the program runs
+    adapter.borrow_mut().wait(None)?;
+    let launch = match adapter.borrow().launch.clone() {
+        Some(ref launch) if !adapter.borrow().disconnected => launch.clone(),
+        _ => return Ok(()),
+    };
+
+    let mut context = Context::new();
+    context.debug_adapter(adapter.clone());
+    let res = ::run(launch.path, launch.program, launch.args, false, false, &context);
+
+    let mut adapter = adapter.borrow_mut();
+    if let Some(err) = adapter.error.take() {
+        return Err(err);
+    }
+    if adapter.disconnected {
+        return Ok(());
+    }
+
+    // errors go where the program's output went, as they would in a terminal
+    let code = match res {
+        Ok(_) => 0,
+        Err(diags) => {
+            let printer = ErrorPrinter::from_map(adapter.sources.clone());
+            for d in diags.iter() {
+                adapter.output(&printer.format_diag(d), "stderr")?;
+            }
+            1
+        },
+    };
+    adapter.event("exited", Json::object(vec![("exitCode", Json::Num(code as f64))]))?;
+    adapter.event("terminated", Json::object(vec![]))?;
+    adapter.ended = true;
+
+    adapter.wait(None)
+}
+
+/// State of the adapter, shared by the requests before the run and the
+/// stops during it
+pub struct Adapter {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    /// Sequence number of the last message sent
+    seq: usize,
+    launch: Option<Launch>,
+    configured: bool,
+    ended: bool,
+    disconnected: bool,
+    /// Error talking to the client during the run, which stops it
+    error: Option<io::Error>,
+    /// Files of the launched program, and its functions with bodies
+    sources: SourceMap,
+    funcs: Vec<(CLoc, String)>,
+    /// Lines to stop at, by the path of their file as the client gave it
+    breakpoints: Vec<(String, Vec<usize>)>,
+    step: Step,
+    /// Function of each active call, main first, with the location of the
+    /// statement it is at
+    frames: Vec<(String, usize)>,
+    /// Depth, file and line of the last statement, as each line stops once
+    last: (usize, FileId, usize),
+}
+
+#[derive(Debug, Clone)]
+struct Launch {
+    path: String,
+    program: String,
+    args: Vec<String>,
+}
+
+/// Where to stop next
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    /// At the first statement
+    Entry,
+    /// At a breakpoint
+    Continue,
+    /// At the next line of any call
+    In,
+    /// At the next line of a call at most this deep
+    Over(usize),
+    /// At the next line of a call less deep than this
+    Out(usize),
+}
+
+/// Tables of a stopped program, which requests are answered from
+struct Paused<'a> {
+    global_symtab: &'a SymTab<'a>,
+    local_symtab: &'a SymTab<'a>,
+    resolution: &'a Resolution<'a>,
+}
+
+impl Adapter {
+    pub fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Adapter {
+        Adapter {
+            input: input,
+            output: output,
+            seq: 0,
+            launch: None,
+            configured: false,
+            ended: false,
+            disconnected: false,
+            error: None,
+            sources: SourceMap::new(),
+            funcs: vec![],
+            breakpoints: vec![],
+            step: Step::Continue,
+            frames: vec![],
+            last: (0, 0, 0),
+        }
+    }
+
+    /// Statement about to run at the location, where the program stops
+    /// when stepping or at a breakpoint, until the client resumes it
+    pub fn statement(
+        &mut self,
+        loc: usize,
+        global_symtab: &SymTab,
+        local_symtab: &SymTab,
+        resolution: &Resolution,
+    ) -> Result<(), CError>
+    {
+        // calls are as deep as the frames of the local table
+        let depth = local_symtab.depth();
+        let func = self.func_at(loc);
+        self.frames.truncate(depth);
+        while self.frames.len() < depth {
+            self.frames.push((func.clone(), loc));
+        }
+        self.frames[depth - 1] = (func, loc);
+
+        let (file, line) = match self.sources.line(loc) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        if (depth, file, line) == self.last {
+            return Ok(());
+        }
+        self.last = (depth, file, line);
+
+        let reason = match self.step {
+            Step::Entry => "entry",
+            Step::In => "step",
+            Step::Over(d) if depth <= d => "step",
+            Step::Out(d) if depth < d => "step",
+            _ if self.is_breakpoint(file, line) => "breakpoint",
+            _ => return Ok(()),
+        };
+
+        let paused = Paused { global_symtab: global_symtab, local_symtab: local_symtab, resolution: resolution };
+        let res = self.event("stopped", Json::object(vec![
+            ("reason", Json::str(reason)),
+            ("threadId", Json::Num(THREAD as f64)),
+            ("allThreadsStopped", Json::Bool(true)),
+        ])).and_then(|_| self.wait(Some(&paused)));
+
+        match res {
+            Ok(()) if !self.disconnected => Ok(()),
+            Ok(()) => Err(CError::UnknownError("Debugging stopped".to_owned())),
+            Err(err) => {
+                self.error = Some(err);
+                Err(CError::UnknownError("Debugging stopped".to_owned()))
+            },
+        }
+    }
+
+    /// Send what the program prints to the client
+    pub fn print(&mut self, text: &str) {
+        if let Err(err) = self.output(text, "stdout") {
+            self.error = Some(err);
+        }
+    }
+
+    /// Answer requests until one starts or resumes the program, or the
+    /// client disconnects
+    fn wait(&mut self, paused: Option<&Paused>) -> io::Result<()> {
+        while !self.disconnected {
+            let msg = match read_message(&mut self.input)? {
+                Some(msg) => msg,
+                None => {
+                    self.disconnected = true;
+                    break;
+                },
+            };
+            // there is no request to answer when it is not one
+            let req = match Json::parse(&msg) {
+                Ok(req) => req,
+                Err(_) => continue,
+            };
+            if req.get("type").and_then(Json::as_str) != Some("request") {
+                continue;
+            }
+            if self.request(&req, paused)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a request, giving whether the program should run on
+    fn request(&mut self, req: &Json, paused: Option<&Paused>) -> io::Result<bool> {
+        let command = req.get("command").and_then(Json::as_str).unwrap_or("");
+        let null = Json::Null;
+        let args = req.get("arguments").unwrap_or(&null);
+
+        let (res, run) = match (command, paused) {
+            ("initialize", _) => (Ok(Json::object(vec![
+                ("supportsConfigurationDoneRequest", Json::Bool(true)),
+            ])), false),
+            ("launch", _) => {
+                let res = self.launch(args);
+                let launched = res.is_ok();
+                self.respond(req, res)?;
+                // configuration requests are about the launched program
+                if launched {
+                    self.event("initialized", Json::object(vec![]))?;
+                }
+                return Ok(launched && self.configured);
+            },
+            ("setBreakpoints", _) => (Ok(self.set_breakpoints(args)), false),
+            ("setExceptionBreakpoints", _) => (Ok(Json::object(vec![("breakpoints", Json::Array(vec![]))])), false),
+            ("configurationDone", _) => {
+                self.configured = true;
+                (Ok(Json::Null), self.launch.is_some() && paused.is_none() && !self.ended)
+            },
+            ("threads", _) => (Ok(Json::object(vec![
+                ("threads", Json::Array(vec![Json::object(vec![
+                    ("id", Json::Num(THREAD as f64)),
+                    ("name", Json::str("main")),
+                ])])),
+            ])), false),
+            ("disconnect", _) => {
+                self.disconnected = true;
+                (Ok(Json::Null), false)
+            },
+            ("continue", Some(_)) => {
+                self.step = Step::Continue;
+                (Ok(Json::object(vec![("allThreadsContinued", Json::Bool(true))])), true)
+            },
+            ("next", Some(_)) => {
+                self.step = Step::Over(self.frames.len());
+                (Ok(Json::Null), true)
+            },
+            ("stepIn", Some(_)) => {
+                self.step = Step::In;
+                (Ok(Json::Null), true)
+            },
+            ("stepOut", Some(_)) => {
+                self.step = Step::Out(self.frames.len());
+                (Ok(Json::Null), true)
+            },
+            ("stackTrace", Some(_)) => (Ok(self.stack_trace()), false),
+            ("scopes", Some(_)) => (Ok(self.scopes(args)), false),
+            ("variables", Some(paused)) => (Ok(self.variables(args, paused)), false),
+            ("continue", None) | ("next", None) | ("stepIn", None) | ("stepOut", None) |
+            ("stackTrace", None) | ("scopes", None) | ("variables", None) =>
+                (Err(format!("Program is not stopped")), false),
+            _ => (Err(format!("Unknown command '{}'", command)), false),
+        };
+        self.respond(req, res)?;
+        Ok(run)
+    }
+
+    /// Load the program to run, which is read from disk with the files it
+    /// includes. Errors in it are reported once it runs
+    fn launch(&mut self, args: &Json) -> Result<Json, String> {
+        if self.launch.is_some() {
+            return Err(format!("Program already launched"));
+        }
+        let path = match args.get("program").and_then(Json::as_str) {
+            Some(path) => path.to_owned(),
+            None => return Err(format!("No program to launch")),
+        };
+
+        let mut program = String::new();
+        if let Err(err) = File::open(&path).and_then(|mut f| f.read_to_string(&mut program)) {
+            return Err(format!("Failed opening file '{}' ({})", path, err));
+        }
+
+        let (sources, loaded) = ::load(&path, &program);
+        if let Ok(ast) = loaded {
+            self.funcs = ast.iter().filter_map(|elem| match *elem {
+                CProgElem::Func(loc, ref f) => Some((loc, f.proto.name.to_string())),
+                _ => None,
+            }).collect();
+        }
+        self.sources = sources;
+
+        if args.get("stopOnEntry") == Some(&Json::Bool(true)) {
+            self.step = Step::Entry;
+        }
+        let argv = match args.get("args") {
+            Some(&Json::Array(ref argv)) => argv.iter().filter_map(Json::as_str).map(|a| a.to_owned()).collect(),
+            _ => vec![],
+        };
+        self.launch = Some(Launch { path: path, program: program, args: argv });
+        Ok(Json::Null)
+    }
+
+    /// Replace the breakpoints of a file, which are verified when the file
+    /// is part of the program and has the line
+    fn set_breakpoints(&mut self, args: &Json) -> Json {
+        let path = args.get("source").and_then(|s| s.get("path")).and_then(Json::as_str).unwrap_or("").to_owned();
+        let lines: Vec<usize> = match args.get("breakpoints") {
+            Some(&Json::Array(ref bs)) => bs.iter()
+                .filter_map(|b| b.get("line").and_then(Json::as_num))
+                .map(|l| l as usize)
+                .collect(),
+            _ => vec![],
+        };
+
+        let file = self.file_of(&path).and_then(|id| self.sources.file(id));
+        let breakpoints = lines.iter().map(|&line| {
+            let mut pairs = vec![("line", Json::Num(line as f64))];
+            match file {
+                Some(f) if line > 0 && line <= f.line_count() => pairs.push(("verified", Json::Bool(true))),
+                Some(_) => {
+                    pairs.push(("verified", Json::Bool(false)));
+                    pairs.push(("message", Json::str("No such line")));
+                },
+                None => {
+                    pairs.push(("verified", Json::Bool(false)));
+                    pairs.push(("message", Json::str("File is not part of the program")));
+                },
+            }
+            Json::object(pairs)
+        }).collect();
+
+        self.breakpoints.retain(|&(ref p, _)| Path::new(p) != Path::new(&path));
+        self.breakpoints.push((path, lines));
+        Json::object(vec![("breakpoints", Json::Array(breakpoints))])
+    }
+
+    /// Active calls, innermost first
+    fn stack_trace(&self) -> Json {
+        let frames = self.frames.iter().enumerate().rev().map(|(i, &(ref name, loc))| {
+            let mut pairs = vec![("id", Json::Num((i + 1) as f64)), ("name", Json::str(name))];
+            let place = self.sources.locate(loc).and_then(|(id, l)| {
+                let f = self.sources.file(id)?;
+                Some((f, f.line_col(l)?))
+            });
+            match place {
+                Some((f, (line, column))) => {
+                    let name = Path::new(&f.name).file_name().map_or(f.name.clone(), |n| n.to_string_lossy().into_owned());
+                    pairs.push(("source", Json::object(vec![("name", Json::str(&name)), ("path", Json::str(&f.name))])));
+                    pairs.push(("line", Json::Num(line as f64)));
+                    pairs.push(("column", Json::Num(column as f64)));
+                },
+                None => {
+                    pairs.push(("line", Json::Num(0.0)));
+                    pairs.push(("column", Json::Num(0.0)));
+                },
+            }
+            Json::object(pairs)
+        }).collect();
+
+        Json::object(vec![
+            ("stackFrames", Json::Array(frames)),
+            ("totalFrames", Json::Num(self.frames.len() as f64)),
+        ])
+    }
+
+    /// Locals of a frame, and the globals
+    fn scopes(&self, args: &Json) -> Json {
+        let frame = args.get("frameId").and_then(Json::as_num).unwrap_or(0.0) as usize;
+        let scope = |name: &str, reference: usize| Json::object(vec![
+            ("name", Json::str(name)),
+            ("variablesReference", Json::Num(reference as f64)),
+            ("expensive", Json::Bool(false)),
+        ]);
+
+        let mut scopes = vec![];
+        if frame > 0 && frame <= self.frames.len() {
+            scopes.push(scope("Locals", GLOBALS + frame));
+        }
+        scopes.push(scope("Globals", GLOBALS));
+        Json::object(vec![("scopes", Json::Array(scopes))])
+    }
+
+    /// Variables of a scope, in the order they are declared. Locals not
+    /// declared yet are left out
+    fn variables(&self, args: &Json, paused: &Paused) -> Json {
+        let reference = args.get("variablesReference").and_then(Json::as_num).unwrap_or(0.0) as usize;
+
+        let vars = match reference {
+            GLOBALS => paused.resolution.globals.iter().filter_map(|&(name, _)| {
+                let (t, _) = paused.global_symtab.get_type(name)?;
+                Some(variable(name, &t, paused.global_symtab.get_val(name)))
+            }).collect(),
+            r if r > GLOBALS && r - GLOBALS <= self.frames.len() => {
+                let frame = r - GLOBALS - 1;
+                let loc = self.frames[frame].1;
+                let func = paused.resolution.funcs.iter().find(|&&((l, r), _)| l <= loc && loc < r);
+                let mut names: Vec<&str> = vec![];
+                if let Some(&(_, ref scopes)) = func {
+                    for local in scopes.locals.iter() {
+                        if !names.contains(&local.name) {
+                            names.push(local.name);
+                        }
+                    }
+                }
+                names.into_iter().filter_map(|name| {
+                    let (t, val) = paused.local_symtab.get_in_frame(frame, name)?;
+                    Some(variable(name, &t, val))
+                }).collect()
+            },
+            _ => vec![],
+        };
+
+        Json::object(vec![("variables", Json::Array(vars))])
+    }
+
+    fn func_at(&self, loc: usize) -> String {
+        match self.funcs.iter().find(|&&((l, r), _)| l <= loc && loc < r) {
+            Some(&(_, ref name)) => name.clone(),
+            None => "?".to_owned(),
+        }
+    }
+
+    /// File of the program at a path, which may be written differently
+    fn file_of(&self, path: &str) -> Option<FileId> {
+        self.sources.files().iter().position(|f| Path::new(&f.name) == Path::new(path))
+    }
+
+    fn is_breakpoint(&self, file: FileId, line: usize) -> bool {
+        let path = match self.sources.file(file) {
+            Some(f) => Path::new(&f.name),
+            None => return false,
+        };
+        self.breakpoints.iter().any(|&(ref p, ref lines)| Path::new(p) == path && lines.contains(&line))
+    }
+
+    fn respond(&mut self, req: &Json, res: Result<Json, String>) -> io::Result<()> {
+        let mut pairs = vec![
+            ("type", Json::str("response")),
+            ("request_seq", req.get("seq").cloned().unwrap_or(Json::Null)),
+            ("command", req.get("command").cloned().unwrap_or(Json::Null)),
+        ];
+        match res {
+            Ok(Json::Null) => pairs.push(("success", Json::Bool(true))),
+            Ok(body) => {
+                pairs.push(("success", Json::Bool(true)));
+                pairs.push(("body", body));
+            },
+            Err(msg) => {
+                pairs.push(("success", Json::Bool(false)));
+                pairs.push(("message", Json::Str(msg)));
+            },
+        }
+        self.send(pairs)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(vec![("type", Json::str("event")), ("event", Json::str(event)), ("body", body)])
+    }
+
+    fn output(&mut self, text: &str, category: &str) -> io::Result<()> {
+        self.event("output", Json::object(vec![("category", Json::str(category)), ("output", Json::str(text))]))
+    }
+
+    fn send(&mut self, pairs: Vec<(&str, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        let mut msg = vec![("seq", Json::Num(self.seq as f64))];
+        msg.extend(pairs);
+        write_message(&mut self.output, &Json::object(msg))
+    }
+}
+
+impl Debug for Adapter {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "Adapter {{ step: {:?}, frames: {:?} }}", self.step, self.frames)
+    }
+}
+
+/// Variable as shown by the client, with values not yet written as the
+/// terminal debugger shows them
+fn variable(name: &str, t: &CType, val: Option<SymVal>) -> Json {
+    Json::object(vec![
+        ("name", Json::str(name)),
+        ("value", Json::Str(match val {
+            Some(v) => format!("{:?}", v),
+            None => "N\\A".to_owned(),
+        })),
+        ("type", Json::str(pretty::format_var(t, "").trim_end())),
+        ("variablesReference", Json::Num(0.0)),
+    ])
+}
@@ -1,17 +1,313 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use std::char;
+use stacker;
 
 use ast::*;
 use env::{FuncTab, SymTab, SymVal};
 use checker;
+use coverage::Coverage;
+use dap::Adapter;
 use error::CError;
+use lint::{Lint, Level};
+use profile::Profile;
 use repl::Repl;
+use resolver;
+use source::SourceMap;
+use trace::Tracer;
+
+/// How signed integer overflow is handled
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Overflow {
+    /// Wrap around in two's complement, like C compilers do in practice
+    Wrap,
+    /// Stop with a located run-time error
+    Trap,
+}
+
+/// How a program is executed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Backend {
+    /// Walk the AST, which the debugger needs
+    Tree,
+    /// Compile to bytecode and run that in the VM
+    Vm,
+}
+
+/// Calls the tree walker nests at most whatever the limits say, as the
+/// stack it recurses on for each would otherwise grow until memory runs out
+pub const TREE_DEPTH: usize = 10000;
+
+/// Stack the tree walker keeps free before recursing, moving onto a new
+/// segment of `STACK_SEGMENT` bytes when less is left
+const RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+/// Bounds on what a run may do, for running programs that are not trusted
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Limits {
+    /// Statements executed
+    pub steps: Option<u64>,
+    /// Calls active at once, not counting main
+    pub depth: Option<usize>,
+    /// Array cells allocated by declarations over the whole run
+    pub cells: Option<usize>,
+}
+
+/// Limit a run was stopped by
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Limit {
+    Steps,
+    Depth,
+    Cells,
+}
+
+impl Limit {
+    /// Command line flag setting the limit
+    pub fn flag(&self) -> &'static str {
+        match *self {
+            Limit::Steps => "max-steps",
+            Limit::Depth => "max-depth",
+            Limit::Cells => "max-cells",
+        }
+    }
+}
+
+/// What a run has used so far of its limits
+#[derive(Debug, Clone)]
+struct Usage {
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    cells: Cell<usize>,
+}
+
+/// Settings for a program run
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub backend: Backend,
+    pub overflow: Overflow,
+    /// Report reads of variables and array elements that were never written
+    pub sanitize_uninit: bool,
+    /// Lint levels that differ from the defaults, where later ones win
+    pub lints: Vec<(Lint, Level)>,
+    /// Fail the run on any warning
+    pub warnings_as_errors: bool,
+    pub limits: Limits,
+    /// Count statements run and time calls
+    pub profile: bool,
+    /// Record what ran and which way conditions went
+    pub coverage: bool,
+    usage: Usage,
+    profiler: RefCell<Profile>,
+    covered: RefCell<Coverage>,
+    tracer: Option<Rc<RefCell<Tracer>>>,
+    adapter: Option<Rc<RefCell<Adapter>>>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            backend: Backend::Tree,
+            overflow: Overflow::Wrap,
+            sanitize_uninit: false,
+            lints: vec![],
+            warnings_as_errors: false,
+            limits: Limits { steps: None, depth: None, cells: None },
+            profile: false,
+            coverage: false,
+            usage: Usage { steps: Cell::new(0), depth: Cell::new(0), cells: Cell::new(0) },
+            profiler: RefCell::new(Profile::new()),
+            covered: RefCell::new(Coverage::new()),
+            tracer: None,
+            adapter: None,
+        }
+    }
+
+    /// Start counting against the limits, profiling and coverage from zero
+    pub fn reset_usage(&self) {
+        self.usage.steps.set(0);
+        self.usage.depth.set(0);
+        self.usage.cells.set(0);
+        *self.profiler.borrow_mut() = Profile::new();
+        *self.covered.borrow_mut() = Coverage::new();
+    }
+
+    /// Profile of the last run, when profiling
+    pub fn profile(&self) -> Profile {
+        let mut profile = self.profiler.borrow().clone();
+        profile.finish();
+        profile
+    }
+
+    /// Coverage of the last run, when recording it
+    pub fn coverage(&self) -> Coverage {
+        self.covered.borrow().clone()
+    }
+
+    /// Trace the statements and assignments of the following runs
+    pub fn trace(&mut self, tracer: Tracer) {
+        self.tracer = Some(Rc::new(RefCell::new(tracer)));
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    /// Debug the following runs through an adapter for an editor, which
+    /// also gets what they print
+    pub fn debug_adapter(&mut self, adapter: Rc<RefCell<Adapter>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub fn adapter(&self) -> Option<Rc<RefCell<Adapter>>> {
+        self.adapter.clone()
+    }
+
+    /// Print what the program prints, to stdout or the debug adapter
+    pub fn print(&self, text: &str) {
+        match self.adapter {
+            Some(ref adapter) => adapter.borrow_mut().print(text),
+            None => print!("{}", text),
+        }
+    }
+
+    /// Trace a value assigned to a variable, or to one of its elements
+    pub fn assign(&self, id: &str, index: Option<usize>, val: &SymVal, loc: usize) {
+        if let Some(ref tracer) = self.tracer {
+            tracer.borrow_mut().assign(id, index, val, loc, self.usage.depth.get());
+        }
+    }
+
+    /// Record which way the condition of the statement at the location went
+    pub fn branch(&self, loc: usize, taken: bool) {
+        if self.coverage {
+            self.covered.borrow_mut().branch(loc, taken);
+        }
+    }
+
+    /// Count a statement about to run at the location
+    pub fn step(&self, loc: CLoc) -> Result<(), CError> {
+        if self.profile {
+            self.profiler.borrow_mut().stmt(loc.0);
+        }
+        if self.coverage {
+            self.covered.borrow_mut().stmt(loc.0);
+        }
+        if let Some(ref tracer) = self.tracer {
+            tracer.borrow_mut().stmt(loc, self.usage.depth.get());
+        }
+        self.count(loc)
+    }
+
+    /// Count a statement against the limits without reporting it, as it is
+    /// part of one that was, like the names and initializers of a
+    /// declaration after its first
+    pub fn count(&self, loc: CLoc) -> Result<(), CError> {
+        let steps = self.usage.steps.get() + 1;
+        self.usage.steps.set(steps);
+        match self.limits.steps {
+            Some(max) if steps > max =>
+                Err(CError::LimitError(Limit::Steps, format!("Step limit of {} exceeded", max), loc)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a call of the function made at the location, until
+    /// `leave_call`
+    pub fn enter_call(&self, id: &str, loc: CLoc) -> Result<(), CError> {
+        let depth = self.usage.depth.get() + 1;
+        match self.limits.depth {
+            Some(max) if depth > max =>
+                Err(CError::LimitError(Limit::Depth, format!("Call depth limit of {} exceeded", max), loc)),
+            _ => {
+                self.usage.depth.set(depth);
+                self.enter_func(id);
+                Ok(())
+            },
+        }
+    }
+
+    /// `enter_call` for the tree walker, which takes native stack for each
+    /// call and so also stops at `TREE_DEPTH` calls
+    fn enter_tree_call(&self, id: &str, loc: CLoc) -> Result<(), CError> {
+        let limited = self.limits.depth.map_or(false, |max| max <= TREE_DEPTH);
+        if self.usage.depth.get() >= TREE_DEPTH && !limited {
+            let msg = format!("Call depth of {} exceeded, the most the tree backend supports", TREE_DEPTH);
+            return Err(CError::LimitError(Limit::Depth, msg, loc));
+        }
+        self.enter_call(id, loc)
+    }
+
+    pub fn leave_call(&self) {
+        self.usage.depth.set(self.usage.depth.get() - 1);
+        self.leave_func();
+    }
+
+    /// Start timing a function, which for calls `enter_call` does
+    pub fn enter_func(&self, id: &str) {
+        if self.profile {
+            self.profiler.borrow_mut().enter(id);
+        }
+        if self.coverage {
+            self.covered.borrow_mut().call(id);
+        }
+    }
+
+    pub fn leave_func(&self) {
+        if self.profile {
+            self.profiler.borrow_mut().leave();
+        }
+    }
+
+    /// Count the cells of an array declared at the location
+    pub fn alloc(&self, cells: usize, loc: CLoc) -> Result<(), CError> {
+        let total = self.usage.cells.get().saturating_add(cells);
+        self.usage.cells.set(total);
+        match self.limits.cells {
+            Some(max) if total > max =>
+                Err(CError::LimitError(Limit::Cells, format!("Array cell limit of {} exceeded", max), loc)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn lint_level(&self, lint: Lint) -> Level {
+        match self.lints.iter().rev().find(|&&(l, _)| l == lint) {
+            Some(&(_, level)) => level,
+            None => lint.default_level(),
+        }
+    }
+}
+
+/// Control flow signal from a statement that did not run to completion
+#[derive(Debug, PartialEq, Clone)]
+pub enum Flow {
+    Return(Option<SymVal>),
+    Break,
+}
 
 pub fn run_prog<'input>(
-    ast: &'input CProg<'input>,
+    ast: &'input CProg,
     program: &'input str,
     args: &'input Vec<String>,
     debug: bool,
     verbose: bool,
+    ctx: &Context,
+) -> Result<Option<SymVal>, CError>
+{
+    let mut sources = SourceMap::new();
+    sources.add("", program);
+    run_sources(ast, &sources, args, debug, verbose, ctx)
+}
+
+/// Run a program loaded from the files of the map, which the debugger
+/// shows the lines of
+pub fn run_sources<'input>(
+    ast: &'input CProg,
+    sources: &SourceMap,
+    args: &'input Vec<String>,
+    debug: bool,
+    verbose: bool,
+    ctx: &Context,
 ) -> Result<Option<SymVal>, CError>
 {
     // load global function and symbol table
@@ -20,6 +316,23 @@ pub fn run_prog<'input>(
         Err(e) => return Err(e),
     };
 
+    ctx.reset_usage();
+
+    // allocate global arrays
+    let mut global_symtab = global_symtab;
+    for elem in ast.iter() {
+        if let CProgElem::Decl(loc, ref t, ref id, Some(ref e)) = *elem {
+            let l2 = try!(loc_of_expr(e));
+            let size = match promote(try!(run_expr(e, &vtab, &global_symtab, &SymTab::new(), &None, ctx))) {
+                SymVal::Int(i) if i < 0 => return Err(CError::RuntimeError(format!("Array '{}' declared with negative size {}", id, i), l2)),
+                SymVal::Int(i) => i as usize,
+                _ => return Err(CError::RuntimeError("Array size must be int".to_owned(), l2)),
+            };
+            try!(ctx.alloc(size, loc));
+            global_symtab.insert(id, CType::Ref(Box::new(t.clone())), Some(size), None, Some(loc));
+        }
+    }
+
     // get main function
     let main = vtab.get_func("main").unwrap();
 
@@ -47,14 +360,16 @@ pub fn run_prog<'input>(
                         Some(SymVal::Array(argv)),
                         None);
 
-    // repl
-    let repl = match debug {
-        true => Some(Repl::new(program, verbose)),
+    // repl, which hands the stops over to the adapter when an editor debugs
+    let repl = match debug || ctx.adapter.is_some() {
+        true => Some(Repl::new(sources, verbose, resolver::resolve_prog(ast, &global_symtab), ctx.adapter())),
         false => None
     };
 
     // run
-    let (ret, res_sym_tab, res_glob_tab, mut res_repl) = try!(run_func(main, &vtab, global_symtab.clone(), local_symtab.clone(), repl.clone()));
+    ctx.enter_func("main");
+    let (ret, res_sym_tab, res_glob_tab, mut res_repl) = try!(run_func(main, &vtab, global_symtab.clone(), local_symtab.clone(), repl.clone(), ctx));
+    ctx.leave_func();
 
     // show repl
     if let Some(ref mut x) = res_repl {
@@ -65,95 +380,74 @@ pub fn run_prog<'input>(
 }
 
 pub fn run_func<'input>(
-    func: &'input CFunc<'input>,
+    func: &'input CFunc,
     vtab: &'input FuncTab<'input>,
     global_symtab: SymTab<'input>,
     local_symtab: SymTab<'input>,
-    repl: Option<Repl>,
-) -> Result<(Option<SymVal>, SymTab<'input>, SymTab<'input>, Option<Repl>), CError>
+    repl: Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(Option<SymVal>, SymTab<'input>, SymTab<'input>, Option<Repl<'input>>), CError>
 {
-    // run block
-    let (ret, local_symtab, global_symtab, repl) = try!(run_stmt(&func.body, vtab, global_symtab, local_symtab, repl));
+    // the outermost block shares the scope of the parameters, which the
+    // debugger still shows once main has returned
+    let (ret, local_symtab, global_symtab, repl) = match func.body {
+        CStmt::Block(_, ref stmts) => {
+            let mut repl = repl;
+            try!(enter_stmt(&func.body, &global_symtab, &local_symtab, &mut repl, ctx));
+            try!(run_block(stmts, vtab, global_symtab, local_symtab, repl, ctx))
+        },
+        ref body => try!(run_stmt(body, vtab, global_symtab, local_symtab, repl, ctx)),
+    };
 
     // unwrap return val
-    Ok((ret.unwrap_or(None), local_symtab, global_symtab, repl))
+    let ret = match ret {
+        Some(Flow::Return(v)) => v,
+        _ => None,
+    };
+    Ok((ret, local_symtab, global_symtab, repl))
 }
 
 pub fn run_stmt<'input>(
-    stmt: &'input CStmt<'input>,
+    stmt: &'input CStmt,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: SymTab<'input>,
+    local_symtab: SymTab<'input>,
+    repl: Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(Option<Flow>, SymTab<'input>, SymTab<'input>, Option<Repl<'input>>), CError>
+{
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || walk_stmt(stmt, vtab, global_symtab, local_symtab, repl, ctx))
+}
+
+fn walk_stmt<'input>(
+    stmt: &'input CStmt,
     vtab: &'input FuncTab<'input>,
     global_symtab: SymTab<'input>,
     local_symtab: SymTab<'input>,
-    repl: Option<Repl>
-) -> Result<(Option<Option<SymVal>>, SymTab<'input>, SymTab<'input>, Option<Repl>), CError>
+    repl: Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(Option<Flow>, SymTab<'input>, SymTab<'input>, Option<Repl<'input>>), CError>
 {
     let mut tmp_repl = repl.clone();
-    if let Some(ref mut x) = tmp_repl {
-        x.show(stmt, &global_symtab, &local_symtab)?;
-    }
+    try!(enter_stmt(stmt, &global_symtab, &local_symtab, &mut tmp_repl, ctx));
 
-    let mut tmp_global_symtab = global_symtab.clone();
-    let mut tmp_symtab = local_symtab.clone();
+    let mut tmp_global_symtab = global_symtab;
+    let mut tmp_symtab = local_symtab;
 
     let res = match *stmt {
-        CStmt::Decl((l, _), ref t, id, ref eo) => {
-            // get index
-            let so = match *eo {
-                Some(ref e) => {
-                    let l2 = try!(loc_of_expr(e));
-                    let sym = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl));
-                    match sym {
-                        SymVal::Int(i) => Some(i as usize),
-                        _ => return Err(CError::RuntimeError("Array index must be int".to_owned(), l2))
-                    }
-                },
-                None => None
-            };
-            tmp_symtab.insert(id, t.clone(), so, None, Some(l));
+        CStmt::Decl(..) => {
+            try!(declare(stmt, vtab, &tmp_global_symtab, &mut tmp_symtab, &tmp_repl, ctx));
             None
         },
-        CStmt::Assign((l, _), id, ref eo, ref e) => {
-            // get index
-            let so = match *eo {
-                Some(ref e) => {
-                    let l2 = try!(loc_of_expr(e));
-                    let sym = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl));
-                    match sym {
-                        SymVal::Int(i) => Some(i as usize),
-                        _ => return Err(CError::RuntimeError("Array index must be int".to_owned(), l2))
-                    }
-                },
-                None => None
-            };
-            let val = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl));
-            let l2 = try!(loc_of_expr(e));
-            match tmp_symtab.get_type(id) {
-                // set in global
-                Some((t, _)) => {
-                    let casted = try!(auto_cast(&val, l2, &t));
-                    match tmp_symtab.set_val(id, so, casted, Some(l)) {
-                        Ok(()) => None,
-                        Err(s) => return Err(CError::RuntimeError(s, l)),
-                    }
-                },
-                // if not, assume global
-                None => match tmp_global_symtab.get_type(id) {
-                    Some((t, _)) => {
-                        let casted = try!(auto_cast(&val, l2, &t));
-                        match tmp_global_symtab.set_val(id, so, casted, Some(l)) {
-                            Ok(()) => None,
-                            Err(s) => return Err(CError::RuntimeError(s, l)),
-                        }
-                    },
-                    None => return Err(CError::RuntimeError(format!("Variable '{:?}' not declared", id), l))
-                }
-            }
+        CStmt::Assign(..) => {
+            try!(assign(stmt, vtab, &mut tmp_global_symtab, &mut tmp_symtab, &tmp_repl, ctx));
+            None
         },
-        CStmt::Call((l, _), id, ref args) => {
+        CStmt::Call(loc, ref id, ref args) => {
             // get func
             let f = match vtab.get_func(id) {
                 Some(f) => f,
-                None => return Err(CError::RuntimeError(format!("Function '{}' not initialized", id), l)),
+                None => return Err(CError::RuntimeError(format!("Function '{}' not initialized", id), loc)),
             };
 
             // calc and add args to symtab
@@ -163,47 +457,54 @@ pub fn run_stmt<'input>(
                 let (ref t, ref pid) = *p;
                 let e = match args.iter().nth(i) {
                     Some(x) => x,
-                    None => return Err(CError::RuntimeError(format!("Function '{}' missing param '{:?}'", id, p), l)),
+                    None => return Err(CError::RuntimeError(format!("Function '{}' missing param '{:?}'", id, p), loc)),
                 };
-                let val = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl));
-                tab.insert(pid, t.clone(), None, Some(val), Some(l));
+                let val = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx));
+                tab.insert(pid, t.clone(), None, Some(val), Some(loc));
             }
 
-            let (_, _, mut tab2, repl) = try!(run_func(&f, vtab, global_symtab, tab, repl));
+            try!(ctx.enter_tree_call(id, loc));
+            let (_, _, mut tab2, repl) = try!(run_func(&f, vtab, tmp_global_symtab.clone(), tab, repl, ctx));
+            ctx.leave_call();
             tab2.pop_frame()?;
             tmp_symtab = tab2;
             tmp_repl = repl;
             None
         },
         CStmt::Return(_, ref s) => match s {
-            &Some(ref e) => Some(Some(try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl)))),
-            _ => Some(None),
+            &Some(ref e) => Some(Flow::Return(Some(try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx))))),
+            _ => Some(Flow::Return(None)),
         },
         CStmt::Block(_, ref stmts) => {
-            let mut res = None;
-            for s in stmts.iter() {
-                let (res2, gtab, tab, repl) = try!(run_stmt(s, vtab, tmp_global_symtab, tmp_symtab, tmp_repl));
-                tmp_global_symtab = gtab;
-                tmp_symtab = tab;
-                tmp_repl = repl;
-                match res2 {
-                    Some(_) => {
-                        res = res2.clone();
-                        break
-                    },
-                    _ => res2
-                };
-            }
+            tmp_symtab.enter_scope();
+            let (res, gtab, mut tab, repl) = try!(run_block(stmts, vtab, tmp_global_symtab, tmp_symtab, tmp_repl, ctx));
+            tab.leave_scope();
+            tmp_global_symtab = gtab;
+            tmp_symtab = tab;
+            tmp_repl = repl;
             res
         },
-        CStmt::If((l, _), ref cond, ref s, ref o) => {
-            let b = match try!(run_expr(cond, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl)) {
-                SymVal::Int(i) => i != 0,
-                SymVal::Bool(b) => b,
-                x => return Err(CError::RuntimeError(format!("Expected bool, got {:?}", x), l)),
-            };
+        CStmt::Decls(_, ref stmts) => {
+            for (i, s) in stmts.iter().enumerate() {
+                // shown and reported once, as the statement it was written as
+                match (i, loc_of_stmt(s)) {
+                    (0, _) => try!(enter_stmt(s, &tmp_global_symtab, &tmp_symtab, &mut tmp_repl, ctx)),
+                    (_, Some(loc)) => try!(ctx.count(loc)),
+                    _ => (),
+                }
+                match **s {
+                    CStmt::Decl(..) => try!(declare(s, vtab, &tmp_global_symtab, &mut tmp_symtab, &tmp_repl, ctx)),
+                    CStmt::Assign(..) => try!(assign(s, vtab, &mut tmp_global_symtab, &mut tmp_symtab, &tmp_repl, ctx)),
+                    _ => return Err(CError::UnknownError(format!("unexpected stmt '{:?}' in declaration", s))),
+                }
+            }
+            None
+        },
+        CStmt::If(loc, ref cond, ref s, ref o) => {
+            let b = try!(to_bool(&try!(run_expr(cond, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx)), loc));
+            ctx.branch(loc.0, b);
             if b {
-                let (res, gtab, tab, repl) = try!(run_stmt(s, vtab, tmp_global_symtab, tmp_symtab, tmp_repl));
+                let (res, gtab, tab, repl) = try!(run_scoped(s, vtab, tmp_global_symtab, tmp_symtab, tmp_repl, ctx));
                 tmp_global_symtab = gtab;
                 tmp_symtab = tab;
                 tmp_repl = repl;
@@ -211,7 +512,7 @@ pub fn run_stmt<'input>(
             } else {
                 match *o {
                     Some(ref es) => {
-                        let (res, gtab, tab, repl) = try!(run_stmt(es, vtab, tmp_global_symtab, tmp_symtab, tmp_repl));
+                        let (res, gtab, tab, repl) = try!(run_scoped(es, vtab, tmp_global_symtab, tmp_symtab, tmp_repl, ctx));
                         tmp_global_symtab = gtab;
                         tmp_symtab = tab;
                         tmp_repl = repl;
@@ -221,66 +522,110 @@ pub fn run_stmt<'input>(
                 }
             }
         },
-        CStmt::While((l, _), ref cond, ref s) => {
-            let b = match try!(run_expr(cond, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl)) {
-                SymVal::Int(i) => i != 0,
-                SymVal::Bool(b) => b,
-                x => return Err(CError::RuntimeError(format!("Expected bool, got {:?}", x), l)),
-            };
-            if b {
-                let (res, gtab, tab, repl) = try!(run_stmt(s, vtab, global_symtab, local_symtab, repl));
-                match res {
-                    Some(_) => res,
-                    _ => {
-                        let (res2, gtab2, tab2, repl2) = try!(run_stmt(stmt, vtab, gtab, tab, repl));
-                        tmp_global_symtab = gtab2;
-                        tmp_symtab = tab2;
-                        tmp_repl = repl2;
-                        res2
-                    }
+        // iterations loop here rather than recursing, which would overflow
+        // the stack on long loops before any limit is reached
+        CStmt::While(loc, ref cond, ref s) => {
+            let mut res = None;
+            loop {
+                let b = try!(to_bool(&try!(run_expr(cond, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx)), loc));
+                ctx.branch(loc.0, b);
+                if !b {
+                    break;
                 }
-            } else {
-                None
+
+                let (res2, gtab, tab, repl) = try!(run_scoped(s, vtab, tmp_global_symtab, tmp_symtab, tmp_repl, ctx));
+                tmp_global_symtab = gtab;
+                tmp_symtab = tab;
+                tmp_repl = repl;
+                match res2 {
+                    Some(Flow::Break) => break,
+                    Some(_) => {
+                        res = res2;
+                        break
+                    },
+                    None => (),
+                };
+
+                // the loop statement is reached again for the next check
+                try!(enter_stmt(stmt, &tmp_global_symtab, &tmp_symtab, &mut tmp_repl, ctx));
             }
+            res
         },
-        CStmt::Print((l, _), ref fmto, ref e) => {
-            let val = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl));
-            let val_ws = format!("{:?}", val).replace("\u{0}", "") // remove null char
-                .replace("\\n", "\n").replace("\\t", "\t");        // unescape ws
+        CStmt::DoWhile((dl, _), ref s, ref cond) => {
+            let mut res = None;
+            loop {
+                let (res2, gtab, tab, repl) = try!(run_scoped(s, vtab, tmp_global_symtab, tmp_symtab, tmp_repl, ctx));
+                tmp_global_symtab = gtab;
+                tmp_symtab = tab;
+                tmp_repl = repl;
+                match res2 {
+                    Some(Flow::Break) => break,
+                    Some(_) => {
+                        res = res2;
+                        break
+                    },
+                    None => (),
+                };
 
-            let mut acc = String::new();
-            match *fmto {
-                Some(ref fmt) => {
-                    let mut fmt = fmt.clone();
-                    let fmts: String = fmt.clone().collect();
+                let loc = try!(loc_of_expr(cond));
+                let b = try!(to_bool(&try!(run_expr(cond, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx)), loc));
+                ctx.branch(dl, b);
+                if !b {
+                    break;
+                }
 
-                    loop {
-                        match fmt.next() {
-                            Some('%') => {
-                                match fmt.next() {
-                                    Some(_) => acc.push_str(val_ws.as_str()),
-                                    _ => return Err(CError::RuntimeError(format!("Bad string formatter '{}'", fmts), l))
-                                }
-                            },
-                            Some('\\') => {
-                                match fmt.next() {
-                                    Some('n') => acc.push('\n'),
-                                    Some('t') => acc.push('\t'),
-                                    Some('0') => acc.push('\0'),
-                                    _ => return Err(CError::RuntimeError(format!("Bad string formatter '{}'", fmts), l))
-                                }
-                            },
-                            Some(x) => acc.push(x),
-                            None => break // end of string
+                try!(enter_stmt(stmt, &tmp_global_symtab, &tmp_symtab, &mut tmp_repl, ctx));
+            }
+            res
+        },
+        CStmt::Switch(_, ref e, ref stmts) => {
+            let loc = try!(loc_of_expr(e));
+            let val = try!(case_value(&try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx)), loc));
+
+            // find matching label, or default
+            let mut start = None;
+            let mut default = None;
+            for (i, s) in stmts.iter().enumerate() {
+                match **s {
+                    CStmt::Case(_, Some(ref ce)) => {
+                        let l2 = try!(loc_of_expr(ce));
+                        let cv = try!(case_value(&try!(run_expr(ce, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx)), l2));
+                        if cv == val && start.is_none() {
+                            start = Some(i);
                         }
-                    }
-                },
-                None => {
-                    acc.push_str(val_ws.as_str());
+                    },
+                    CStmt::Case(_, None) => default = Some(i),
+                    _ => (),
                 }
             }
 
-            print!("{}", acc);
+            // run from label, falling through the following ones
+            let mut res = None;
+            tmp_symtab.enter_scope();
+            if let Some(i) = start.or(default) {
+                for s in stmts[i..].iter() {
+                    let (res2, gtab, tab, repl) = try!(run_stmt(s, vtab, tmp_global_symtab, tmp_symtab, tmp_repl, ctx));
+                    tmp_global_symtab = gtab;
+                    tmp_symtab = tab;
+                    tmp_repl = repl;
+                    match res2 {
+                        Some(Flow::Break) => break,
+                        Some(_) => {
+                            res = res2;
+                            break
+                        },
+                        None => (),
+                    };
+                }
+            }
+            tmp_symtab.leave_scope();
+            res
+        },
+        CStmt::Case(..) => None,
+        CStmt::Break(_) => Some(Flow::Break),
+        CStmt::Print(loc, ref fmto, ref e) => {
+            let val = try!(run_expr(e, vtab, &tmp_global_symtab, &tmp_symtab, &tmp_repl, ctx));
+            ctx.print(&try!(format_print(fmto, &val, loc)));
 
             None
         },
@@ -290,12 +635,183 @@ pub fn run_stmt<'input>(
     Ok((res, tmp_global_symtab, tmp_symtab, tmp_repl))
 }
 
+/// Run the statements of a block in turn, until one ends the flow
+fn run_block<'input>(
+    stmts: &'input [Box<CStmt>],
+    vtab: &'input FuncTab<'input>,
+    global_symtab: SymTab<'input>,
+    local_symtab: SymTab<'input>,
+    repl: Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(Option<Flow>, SymTab<'input>, SymTab<'input>, Option<Repl<'input>>), CError>
+{
+    let mut global_symtab = global_symtab;
+    let mut local_symtab = local_symtab;
+    let mut repl = repl;
+    for s in stmts.iter() {
+        let (res, gtab, tab, repl2) = try!(run_stmt(s, vtab, global_symtab, local_symtab, repl, ctx));
+        global_symtab = gtab;
+        local_symtab = tab;
+        repl = repl2;
+        if res.is_some() {
+            return Ok((res, global_symtab, local_symtab, repl));
+        }
+    }
+    Ok((None, global_symtab, local_symtab, repl))
+}
+
+/// Run a statement under `if`, `else`, `while` or `do`, which is a scope of
+/// its own even when it is not a block
+fn run_scoped<'input>(
+    stmt: &'input CStmt,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: SymTab<'input>,
+    local_symtab: SymTab<'input>,
+    repl: Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(Option<Flow>, SymTab<'input>, SymTab<'input>, Option<Repl<'input>>), CError>
+{
+    if let CStmt::Block(..) = *stmt {
+        return run_stmt(stmt, vtab, global_symtab, local_symtab, repl, ctx);
+    }
+    let mut local_symtab = local_symtab;
+    local_symtab.enter_scope();
+    let (res, global_symtab, mut local_symtab, repl) = try!(run_stmt(stmt, vtab, global_symtab, local_symtab, repl, ctx));
+    local_symtab.leave_scope();
+    Ok((res, global_symtab, local_symtab, repl))
+}
+
+/// Show a statement about to run to the debugger, and count it against the
+/// limits
+fn enter_stmt<'input>(
+    stmt: &'input CStmt,
+    global_symtab: &SymTab<'input>,
+    local_symtab: &SymTab<'input>,
+    repl: &mut Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(), CError>
+{
+    if let Some(ref mut x) = *repl {
+        x.show(stmt, global_symtab, local_symtab)?;
+    }
+
+    if let Some(loc) = loc_of_stmt(stmt) {
+        try!(ctx.step(loc));
+    }
+    Ok(())
+}
+
+/// Declare a local variable, or array of the size given
+fn declare<'input>(
+    stmt: &'input CStmt,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: &SymTab<'input>,
+    local_symtab: &mut SymTab<'input>,
+    repl: &Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(), CError>
+{
+    let (loc, t, id, eo) = match *stmt {
+        CStmt::Decl(loc, ref t, ref id, ref eo) => (loc, t, id, eo),
+        _ => unreachable!(),
+    };
+
+    // get size
+    let so = match *eo {
+        Some(ref e) => {
+            let l2 = try!(loc_of_expr(e));
+            let sym = try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx));
+            match promote(sym) {
+                SymVal::Int(i) if i < 0 => return Err(CError::RuntimeError(format!("Array '{}' declared with negative size {}", id, i), l2)),
+                SymVal::Int(i) => Some(i as usize),
+                _ => return Err(CError::RuntimeError("Array size must be int".to_owned(), l2))
+            }
+        },
+        None => None
+    };
+    if let Some(size) = so {
+        try!(ctx.alloc(size, loc));
+    }
+    local_symtab.insert(id, t.clone(), so, None, Some(loc));
+    Ok(())
+}
+
+/// Assign a variable, or an element of an array, in the local table if it
+/// is declared there and otherwise in the global one
+fn assign<'input>(
+    stmt: &'input CStmt,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: &mut SymTab<'input>,
+    local_symtab: &mut SymTab<'input>,
+    repl: &Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<(), CError>
+{
+    let (loc, id, eo, e) = match *stmt {
+        CStmt::Assign(loc, ref id, ref eo, ref e) => (loc, id, eo, e),
+        _ => unreachable!(),
+    };
+
+    // get index
+    let so = match *eo {
+        Some(ref e) => {
+            let l2 = try!(loc_of_expr(e));
+            let sym = try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx));
+            match promote(sym) {
+                SymVal::Int(i) => {
+                    let len = array_len(id, global_symtab, local_symtab);
+                    Some(try!(check_bounds(id, i, len, l2, global_symtab, local_symtab)))
+                },
+                _ => return Err(CError::RuntimeError("Array index must be int".to_owned(), l2))
+            }
+        },
+        None => None
+    };
+    let val = try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx));
+    let l2 = try!(loc_of_expr(e));
+
+    // if not local, assume global
+    let tab = match local_symtab.get_type(id) {
+        Some(_) => local_symtab,
+        None => global_symtab,
+    };
+    let t = match tab.get_type(id) {
+        Some((t, _)) => t,
+        None => return Err(CError::RuntimeError(format!("Variable '{:?}' not declared", id), loc)),
+    };
+    let casted = try!(cast(&val, l2, &elem_type(&t, &so)));
+    let traced = match ctx.tracing() {
+        true => Some(casted.clone()),
+        false => None,
+    };
+    match tab.set_val(id, so, casted, Some(loc)) {
+        Ok(()) => {
+            if let Some(v) = traced { ctx.assign(id, so, &v, loc.0); }
+            Ok(())
+        },
+        Err(s) => Err(CError::RuntimeError(s, loc)),
+    }
+}
+
 pub fn run_expr<'input>(
-    expr: &'input CExpr<'input>,
+    expr: &'input CExpr,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: &'input SymTab<'input>,
+    local_symtab: &'input SymTab<'input>,
+    repl: &'input Option<Repl<'input>>,
+    ctx: &Context,
+) -> Result<SymVal, CError>
+{
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || walk_expr(expr, vtab, global_symtab, local_symtab, repl, ctx))
+}
+
+fn walk_expr<'input>(
+    expr: &'input CExpr,
     vtab: &'input FuncTab<'input>,
     global_symtab: &'input SymTab<'input>,
     local_symtab: &'input SymTab<'input>,
-    repl: &'input Option<Repl>
+    repl: &'input Option<Repl<'input>>,
+    ctx: &Context,
 ) -> Result<SymVal, CError>
 {
     let res = match *expr {
@@ -303,7 +819,7 @@ pub fn run_expr<'input>(
         CExpr::Float((_, _), f) => SymVal::Float(f),
         CExpr::Str((_, _), ref s) => {
             let mut arr = Vec::with_capacity(s.as_str().len() + 1);
-            for c in s.clone() {
+            for c in s.chars() {
                 arr.push(Box::new(SymVal::Char(c)));
             }
             // add null char
@@ -311,162 +827,55 @@ pub fn run_expr<'input>(
             SymVal::Array(arr)
         },
         CExpr::Char((_, _), c) => SymVal::Char(c),
-        CExpr::Ident((l, _), id) => match local_symtab.get_val(id) {
-            Some(v) => v,
-            _ => match global_symtab.get_val(id) {
+        CExpr::Ident(loc, ref id) => {
+            let tab = match local_symtab.get_type(id) {
+                Some(_) => local_symtab,
+                None => global_symtab,
+            };
+            match tab.get_val(id) {
                 Some(v) => v,
-                _ => return Err(CError::RuntimeError(format!("Variable '{}' not initialized", id), l)),
+                None if ctx.sanitize_uninit && tab.get_type(id).is_some() =>
+                    return Err(uninit_read(format!("variable '{}'", id), id, loc, global_symtab, local_symtab)),
+                None => return Err(CError::RuntimeError(format!("Variable '{}' not initialized", id), loc)),
             }
         },
 
-        CExpr::UnOp((l, _), op, ref e) => {
-            let v = try!(run_expr(e, vtab, global_symtab, local_symtab, repl));
-            match op {
-                COp::Not => match v {
-                    SymVal::Int(b) => SymVal::Bool(b != 0),
-                    SymVal::Bool(b) => SymVal::Bool(!b),
-                    v => return Err(CError::RuntimeError(format!("Cannot negate {:?}", v), l)),
-                },
-                COp::Neg => match v {
-                    SymVal::Int(n) => SymVal::Int(-n),
-                    SymVal::Float(n) => SymVal::Float(-n),
-                    v => return Err(CError::RuntimeError(format!("Cannot negate {:?}", v), l)),
+        CExpr::UnOp(loc, op, ref e) => {
+            let v = try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx));
+            try!(un_op(op, v, loc, ctx))
+        },
+        // logical, where the right operand is only evaluated when needed
+        CExpr::BinOp(loc, COp::And, ref e1, ref e2) => {
+            let v1 = try!(run_expr(e1, vtab, global_symtab, local_symtab, repl, ctx));
+            match try!(to_bool(&v1, loc)) {
+                false => SymVal::Bool(false),
+                true => {
+                    let v2 = try!(run_expr(e2, vtab, global_symtab, local_symtab, repl, ctx));
+                    SymVal::Bool(try!(to_bool(&v2, loc)))
                 },
-                _ => return Err(CError::RuntimeError(format!("Unsupported unary operator {:?}", op), l)),
             }
         },
-        CExpr::BinOp((l, _), op, ref e1, ref e2) => {
-            let v1 = try!(run_expr(e1, vtab, global_symtab, local_symtab, repl));
-            let (is_num1, is_int1, i1, is_float1, f1, is_bool1, b1) =
-                match v1 {
-                    SymVal::Int(x)   => (true,  true,  x, false, 0f32, false, false),
-                    SymVal::Float(x) => (true,  false, 0, true,  x,    false, false),
-                    SymVal::Bool(x)  => (false, false, 0, false, 0f32, true,  x),
-                    _ => return Err(CError::RuntimeError(format!("Unexpected '{:?}' in binary op", v1), l)),
-                };
-            let v2 = try!(run_expr(e2, vtab, global_symtab, local_symtab, repl));
-            let (is_num2, is_int2, i2, is_float2, f2, is_bool2, b2) =
-                match v2 {
-                    SymVal::Int(x)   => (true,  true,  x, false, 0f32, false, false),
-                    SymVal::Float(x) => (true,  false, 0, true,  x,    false, false),
-                    SymVal::Bool(x)  => (false, false, 0, false, 0f32, true,  x),
-                    _ => return Err(CError::RuntimeError(format!("Unexpected '{:?}' in binary op", v2), l)),
-                };
-
-            match op {
-                COp::Add => match (is_num1, is_num2) {
-                    (true, true) => match (is_int1, is_int2) {
-                        (true, true) => SymVal::Int(i1 + i2),
-                        (false, true) => SymVal::Float(f1 + i2 as f32),
-                        (true, false) => SymVal::Float(i1 as f32 + f2),
-                        (false, false) => SymVal::Float(f1 + f2),
-                    },
-                    _ => return Err(CError::RuntimeError(format!("`+` op expected numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Sub => match (is_num1, is_num2) {
-                    (true, true) => match (is_int1, is_int2) {
-                        (true, true) => SymVal::Int(i1 - i2),
-                        (false, true) => SymVal::Float(f1 - i2 as f32),
-                        (true, false) => SymVal::Float(i1 as f32 - f2),
-                        (false, false) => SymVal::Float(f1 - f2),
-                    },
-                    _ => return Err(CError::RuntimeError(format!("`-` op expected numbers, got '{:?}' and '{:?}'", v1, v2), l)),
+        CExpr::BinOp(loc, COp::Or, ref e1, ref e2) => {
+            let v1 = try!(run_expr(e1, vtab, global_symtab, local_symtab, repl, ctx));
+            match try!(to_bool(&v1, loc)) {
+                true => SymVal::Bool(true),
+                false => {
+                    let v2 = try!(run_expr(e2, vtab, global_symtab, local_symtab, repl, ctx));
+                    SymVal::Bool(try!(to_bool(&v2, loc)))
                 },
-                COp::Mul => match (is_num1, is_num2) {
-                    (true, true) => match (is_int1, is_int2) {
-                        (true, true) => SymVal::Int(i1 * i2),
-                        (false, true) => SymVal::Float(f1 * i2 as f32),
-                        (true, false) => SymVal::Float(i1 as f32 * f2),
-                        (false, false) => SymVal::Float(f1 * f2),
-                    },
-                    _ => return Err(CError::RuntimeError(format!("`*` op expected numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Div => match (is_num1, is_num2) {
-                    (true, true) => match (is_int1, is_int2) {
-                        (true, true) => SymVal::Int(i1 / i2),
-                        (false, true) => SymVal::Float(f1 / i2 as f32),
-                        (true, false) => SymVal::Float(i1 as f32 / f2),
-                        (false, false) => SymVal::Float(f1 / f2),
-                    },
-                    _ => return Err(CError::RuntimeError(format!("`/` op expected numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                // relational
-                COp::Eq => match (is_int1, is_int2, is_float1, is_float2) {
-                    (true, true, false, false) => SymVal::Bool(i1 == i2),
-                    (false, true, true, false) => SymVal::Bool(f1 == i2 as f32),
-                    (true, false, false, true) => SymVal::Bool(i1 as f32 == f2),
-                    (false, false, true, true) => SymVal::Bool(f1 == f2),
-                    _ => return Err(CError::RuntimeError(format!("`==` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Neq => match (is_int1, is_int2, is_float1, is_float2) {
-                    (true, true, false, false) => SymVal::Bool(i1 != i2),
-                    (false, true, true, false) => SymVal::Bool(f1 != i2 as f32),
-                    (true, false, false, true) => SymVal::Bool(i1 as f32 != f2),
-                    (false, false, true, true) => SymVal::Bool(f1 != f2),
-                    _ => return Err(CError::RuntimeError(format!("`!=` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Lt => match (is_int1, is_int2, is_float1, is_float2) {
-                    (true, true, false, false) => SymVal::Bool(i1 < i2),
-                    (false, true, true, false) => SymVal::Bool(f1 < i2 as f32),
-                    (true, false, false, true) => SymVal::Bool((i1 as f32) < f2),
-                    (false, false, true, true) => SymVal::Bool(f1 < f2),
-                    _ => return Err(CError::RuntimeError(format!("`<` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Lte => match (is_int1, is_int2, is_float1, is_float2) {
-                    (true, true, false, false) => SymVal::Bool(i1 <= i2),
-                    (false, true, true, false) => SymVal::Bool(f1 <= i2 as f32),
-                    (true, false, false, true) => SymVal::Bool(i1 as f32 <= f2),
-                    (false, false, true, true) => SymVal::Bool(f1 <= f2),
-                    _ => return Err(CError::RuntimeError(format!("`<=` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Gt => match (is_int1, is_int2, is_float1, is_float2) {
-                    (true, true, false, false) => SymVal::Bool(i1 > i2),
-                    (false, true, true, false) => SymVal::Bool(f1 > i2 as f32),
-                    (true, false, false, true) => SymVal::Bool(i1 as f32 > f2),
-                    (false, false, true, true) => SymVal::Bool(f1 > f2),
-                    _ => return Err(CError::RuntimeError(format!("`>` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Gte => match (is_int1, is_int2, is_float1, is_float2) {
-                    (true, true, false, false) => SymVal::Bool(i1 >= i2),
-                    (false, true, true, false) => SymVal::Bool(f1 >= i2 as f32),
-                    (true, false, false, true) => SymVal::Bool(i1 as f32 >= f2),
-                    (false, false, true, true) => SymVal::Bool(f1 >= f2),
-                    _ => return Err(CError::RuntimeError(format!("`>=` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                // logical
-                COp::And => match (is_int1, is_int2, is_float1, is_float2, is_bool1, is_bool2) {
-                    (true, true, false, false, ..) => SymVal::Bool((i1 != 0) && (i2 != 0)),
-                    (false, true, true, false, ..) => SymVal::Bool((f1 != 0.0) && (i2 != 0)),
-                    (true, false, false, true, ..) => SymVal::Bool((i1 != 0) && (f2 != 0.0)),
-                    (false, false, true, true, ..) => SymVal::Bool((f1 != 0.0) && (f2 != 0.0)),
-                    // auto cast
-                    (.., false, true) => SymVal::Bool(((i1 as f32 + f1) != 0.0) && b2),
-                    (.., true, false) => SymVal::Bool(b1 && ((i2 as f32 + f2) != 0.0)),
-                    // normal
-                    (.., true, true) => SymVal::Bool(b1 && b2),
-                    _ => return Err(CError::RuntimeError(format!("`&&` op expected pair of bools, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                COp::Or => match (is_int1, is_int2, is_float1, is_float2, is_bool1, is_bool2) {
-                    (true, true, false, false, ..) => SymVal::Bool((i1 != 0) || (i2 != 0)),
-                    (false, true, true, false, ..) => SymVal::Bool((f1 != 0.0) || (i2 != 0)),
-                    (true, false, false, true, ..) => SymVal::Bool((i1 != 0) || (f2 != 0.0)),
-                    (false, false, true, true, ..) => SymVal::Bool((f1 != 0.0) || (f2 != 0.0)),
-                    // auto cast
-                    (.., false, true) => SymVal::Bool(((i1 as f32 + f1) != 0.0) || b2),
-                    (.., true, false) => SymVal::Bool(b1 || ((i2 as f32 + f2) != 0.0)),
-                    // normal
-                    (.., true, true) => SymVal::Bool(b1 && b2),
-                    _ => return Err(CError::RuntimeError(format!("`||` op expected pair of bools, got '{:?}' and '{:?}'", v1, v2), l)),
-                },
-                _ => return Err(CError::RuntimeError(format!("Unsupported operator `{:?}`", op), l)),
             }
         },
+        CExpr::BinOp(loc, op, ref e1, ref e2) => {
+            let v1 = try!(operand(try!(run_expr(e1, vtab, global_symtab, local_symtab, repl, ctx)), loc));
+            let v2 = try!(operand(try!(run_expr(e2, vtab, global_symtab, local_symtab, repl, ctx)), loc));
+            try!(bin_op(op, v1, v2, loc, ctx))
+        },
 
-        CExpr::Call((l, _), id, ref args) => {
+        CExpr::Call(loc, ref id, ref args) => {
             // get func
             let f = match vtab.get_func(id) {
                 Some(f) => f,
-                None => return Err(CError::RuntimeError(format!("Function '{}' not initialized", id), l)),
+                None => return Err(CError::RuntimeError(format!("Function '{}' not initialized", id), loc)),
             };
 
             // calc and add args to symtab
@@ -476,44 +885,75 @@ pub fn run_expr<'input>(
                 let (ref t, ref pid) = *p;
                 let e = match args.iter().nth(i) {
                     Some(x) => x,
-                    None => return Err(CError::RuntimeError(format!("Function '{}' missing param '{:?}'", id, p), l)),
+                    None => return Err(CError::RuntimeError(format!("Function '{}' missing param '{:?}'", id, p), loc)),
                 };
-                let val = try!(run_expr(e, vtab, global_symtab, local_symtab, repl));
-                tab.insert(pid, t.clone(), None, Some(val), Some(l));
+                let val = try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx));
+                tab.insert(pid, t.clone(), None, Some(val), Some(loc));
             }
 
-            match try!(run_func(&f, vtab, global_symtab.clone(), tab, repl.clone())) {
+            try!(ctx.enter_tree_call(id, loc));
+            let res = try!(run_func(&f, vtab, global_symtab.clone(), tab, repl.clone(), ctx));
+            ctx.leave_call();
+            match res {
                 (Some(v), ..) => v,
-                _ => return Err(CError::RuntimeError(format!("Expression returned void"), l)),
+                _ => return Err(CError::RuntimeError(format!("Expression returned void"), loc)),
             }
             // tab.pop_frame()?;
         },
 
-        CExpr::Index((l, _), id, ref e) => {
-            let sym = match local_symtab.get_val(id) {
+        CExpr::Index(loc, ref id, ref e) => {
+            // get index
+            let i = match promote(try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx))) {
+                SymVal::Int(n) => n,
+                x => return Err(CError::RuntimeError(format!("Expected array index, got {:?}", x), loc)),
+            };
+
+            let tab = match local_symtab.get_type(id) {
+                Some(_) => local_symtab,
+                None => global_symtab,
+            };
+            let sym = match tab.get_val(id) {
                 Some(v) => v,
-                _ => match global_symtab.get_val(id) {
-                    Some(v) => v,
-                    _ => return Err(CError::RuntimeError(format!("Variable '{}' not initialized", id), l)),
-                }
+                None if ctx.sanitize_uninit && tab.get_type(id).is_some() => {
+                    let len = array_len(id, global_symtab, local_symtab);
+                    try!(check_bounds(id, i, len, loc, global_symtab, local_symtab));
+                    return Err(uninit_read(format!("element '{}[{}]'", id, i), id, loc, global_symtab, local_symtab));
+                },
+                None => return Err(CError::RuntimeError(format!("Variable '{}' not initialized", id), loc)),
             };
             let a = match sym {
                 SymVal::Array(a) => a,
-                x => return Err(CError::RuntimeError(format!("Expected array, got {:?}", x), l)),
-            };
-
-            // get index
-            let i = match try!(run_expr(e, vtab, global_symtab, local_symtab, repl)) {
-                SymVal::Int(n) => n,
-                x => return Err(CError::RuntimeError(format!("Expected array index, got {:?}", x), l)),
+                x => return Err(CError::RuntimeError(format!("Expected array, got {:?}", x), loc)),
             };
 
             // check bounds
-            if i >= (a.len() as i32) {
-                return Err(CError::RuntimeError(format!("Index {} out of bounds (range: {})", i, a.len()), l))
-            };
+            let i = try!(check_bounds(id, i, Some(a.len()), loc, global_symtab, local_symtab));
 
-            (*a[i as usize]).clone()
+            if ctx.sanitize_uninit && !tab.is_written(id, Some(i)) {
+                return Err(uninit_read(format!("element '{}[{}]'", id, i), id, loc, global_symtab, local_symtab));
+            }
+
+            (*a[i]).clone()
+        },
+
+        CExpr::Cast(loc, ref t, ref e) => {
+            let v = try!(run_expr(e, vtab, global_symtab, local_symtab, repl, ctx));
+            try!(cast(&v, loc, t))
+        },
+
+        CExpr::Ternary(loc, ref cond, ref e1, ref e2) => {
+            let c = try!(run_expr(cond, vtab, global_symtab, local_symtab, repl, ctx));
+            // only evaluate the taken branch
+            match try!(to_bool(&c, loc)) {
+                true => try!(run_expr(e1, vtab, global_symtab, local_symtab, repl, ctx)),
+                false => try!(run_expr(e2, vtab, global_symtab, local_symtab, repl, ctx)),
+            }
+        },
+
+        CExpr::Comma(_, ref e1, ref e2) => {
+            // left value is discarded
+            try!(run_expr(e1, vtab, global_symtab, local_symtab, repl, ctx));
+            try!(run_expr(e2, vtab, global_symtab, local_symtab, repl, ctx))
         },
 
         _ => return Err(CError::UnknownError(format!("unexpected expr '{:?}' in ast", expr)))
@@ -522,51 +962,385 @@ pub fn run_expr<'input>(
     Ok(res)
 }
 
-fn loc_of_expr<'input>(expr: &'input CExpr) -> Result<usize, CError> {
+/// Format a value for printf, with an optional format string
+pub fn format_print(fmt: &Option<CString>, val: &SymVal, loc: CLoc) -> Result<String, CError> {
+    let val_ws = format!("{:?}", val).replace("\u{0}", "") // remove null char
+        .replace("\\n", "\n").replace("\\t", "\t");        // unescape ws
+
+    let mut acc = String::new();
+    match *fmt {
+        Some(ref fmt) => {
+            let mut fmt = fmt.chars();
+            let fmts: String = fmt.clone().collect();
+
+            loop {
+                match fmt.next() {
+                    Some('%') => {
+                        match fmt.next() {
+                            Some('d') | Some('i') => match try!(cast(val, loc, &CType::Int)) {
+                                SymVal::Int(i) => acc.push_str(&i.to_string()),
+                                _ => unreachable!(),
+                            },
+                            Some('c') => match try!(cast(val, loc, &CType::Char)) {
+                                SymVal::Char(c) => acc.push(c),
+                                _ => unreachable!(),
+                            },
+                            Some(_) => acc.push_str(val_ws.as_str()),
+                            _ => return Err(CError::RuntimeError(format!("Bad string formatter '{}'", fmts), loc))
+                        }
+                    },
+                    Some('\\') => {
+                        match fmt.next() {
+                            Some('n') => acc.push('\n'),
+                            Some('t') => acc.push('\t'),
+                            Some('0') => acc.push('\0'),
+                            _ => return Err(CError::RuntimeError(format!("Bad string formatter '{}'", fmts), loc))
+                        }
+                    },
+                    Some(x) => acc.push(x),
+                    None => break // end of string
+                }
+            }
+        },
+        None => {
+            acc.push_str(val_ws.as_str());
+        }
+    }
+
+    Ok(acc)
+}
+
+/// Integer promotion of an arithmetic operand, which has to be a number
+pub fn operand(val: SymVal, loc: CLoc) -> Result<SymVal, CError> {
+    match promote(val) {
+        v @ SymVal::Int(_) | v @ SymVal::Float(_) => Ok(v),
+        v => Err(CError::RuntimeError(format!("Unexpected '{:?}' in binary op", v), loc)),
+    }
+}
+
+/// Arithmetic and relational operators on promoted operands
+pub fn bin_op(op: COp, v1: SymVal, v2: SymVal, loc: CLoc, ctx: &Context) -> Result<SymVal, CError> {
+    let (is_num1, is_int1, i1, is_float1, f1) =
+        match v1 {
+            SymVal::Int(x)   => (true,  true,  x, false, 0f32),
+            SymVal::Float(x) => (true,  false, 0, true,  x),
+            _ => return Err(CError::RuntimeError(format!("Unexpected '{:?}' in binary op", v1), loc)),
+        };
+    let (is_num2, is_int2, i2, is_float2, f2) =
+        match v2 {
+            SymVal::Int(x)   => (true,  true,  x, false, 0f32),
+            SymVal::Float(x) => (true,  false, 0, true,  x),
+            _ => return Err(CError::RuntimeError(format!("Unexpected '{:?}' in binary op", v2), loc)),
+        };
+
+    let res = match op {
+        COp::Add => match (is_num1, is_num2) {
+            (true, true) => match (is_int1, is_int2) {
+                (true, true) => int_arith(op, i1, i2, loc, ctx)?,
+                (false, true) => SymVal::Float(f1 + i2 as f32),
+                (true, false) => SymVal::Float(i1 as f32 + f2),
+                (false, false) => SymVal::Float(f1 + f2),
+            },
+            _ => return Err(CError::RuntimeError(format!("`+` op expected numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Sub => match (is_num1, is_num2) {
+            (true, true) => match (is_int1, is_int2) {
+                (true, true) => int_arith(op, i1, i2, loc, ctx)?,
+                (false, true) => SymVal::Float(f1 - i2 as f32),
+                (true, false) => SymVal::Float(i1 as f32 - f2),
+                (false, false) => SymVal::Float(f1 - f2),
+            },
+            _ => return Err(CError::RuntimeError(format!("`-` op expected numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Mul => match (is_num1, is_num2) {
+            (true, true) => match (is_int1, is_int2) {
+                (true, true) => int_arith(op, i1, i2, loc, ctx)?,
+                (false, true) => SymVal::Float(f1 * i2 as f32),
+                (true, false) => SymVal::Float(i1 as f32 * f2),
+                (false, false) => SymVal::Float(f1 * f2),
+            },
+            _ => return Err(CError::RuntimeError(format!("`*` op expected numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Div => match (is_num1, is_num2) {
+            (true, true) => match (is_int1, is_int2) {
+                (true, true) => int_arith(op, i1, i2, loc, ctx)?,
+                (false, true) => SymVal::Float(f1 / i2 as f32),
+                (true, false) => SymVal::Float(i1 as f32 / f2),
+                (false, false) => SymVal::Float(f1 / f2),
+            },
+            _ => return Err(CError::RuntimeError(format!("`/` op expected numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        // relational
+        COp::Eq => match (is_int1, is_int2, is_float1, is_float2) {
+            (true, true, false, false) => SymVal::Bool(i1 == i2),
+            (false, true, true, false) => SymVal::Bool(f1 == i2 as f32),
+            (true, false, false, true) => SymVal::Bool(i1 as f32 == f2),
+            (false, false, true, true) => SymVal::Bool(f1 == f2),
+            _ => return Err(CError::RuntimeError(format!("`==` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Neq => match (is_int1, is_int2, is_float1, is_float2) {
+            (true, true, false, false) => SymVal::Bool(i1 != i2),
+            (false, true, true, false) => SymVal::Bool(f1 != i2 as f32),
+            (true, false, false, true) => SymVal::Bool(i1 as f32 != f2),
+            (false, false, true, true) => SymVal::Bool(f1 != f2),
+            _ => return Err(CError::RuntimeError(format!("`!=` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Lt => match (is_int1, is_int2, is_float1, is_float2) {
+            (true, true, false, false) => SymVal::Bool(i1 < i2),
+            (false, true, true, false) => SymVal::Bool(f1 < i2 as f32),
+            (true, false, false, true) => SymVal::Bool((i1 as f32) < f2),
+            (false, false, true, true) => SymVal::Bool(f1 < f2),
+            _ => return Err(CError::RuntimeError(format!("`<` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Lte => match (is_int1, is_int2, is_float1, is_float2) {
+            (true, true, false, false) => SymVal::Bool(i1 <= i2),
+            (false, true, true, false) => SymVal::Bool(f1 <= i2 as f32),
+            (true, false, false, true) => SymVal::Bool(i1 as f32 <= f2),
+            (false, false, true, true) => SymVal::Bool(f1 <= f2),
+            _ => return Err(CError::RuntimeError(format!("`<=` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Gt => match (is_int1, is_int2, is_float1, is_float2) {
+            (true, true, false, false) => SymVal::Bool(i1 > i2),
+            (false, true, true, false) => SymVal::Bool(f1 > i2 as f32),
+            (true, false, false, true) => SymVal::Bool(i1 as f32 > f2),
+            (false, false, true, true) => SymVal::Bool(f1 > f2),
+            _ => return Err(CError::RuntimeError(format!("`>` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        COp::Gte => match (is_int1, is_int2, is_float1, is_float2) {
+            (true, true, false, false) => SymVal::Bool(i1 >= i2),
+            (false, true, true, false) => SymVal::Bool(f1 >= i2 as f32),
+            (true, false, false, true) => SymVal::Bool(i1 as f32 >= f2),
+            (false, false, true, true) => SymVal::Bool(f1 >= f2),
+            _ => return Err(CError::RuntimeError(format!("`>=` op expected pair of numbers, got '{:?}' and '{:?}'", v1, v2), loc)),
+        },
+        _ => return Err(CError::RuntimeError(format!("Unsupported operator `{:?}`", op), loc)),
+    };
+
+    Ok(res)
+}
+
+/// Unary operators, after integer promotion
+pub fn un_op(op: COp, v: SymVal, loc: CLoc, ctx: &Context) -> Result<SymVal, CError> {
+    let v = promote(v);
+    let res = match op {
+        COp::Not => match v {
+            SymVal::Int(b) => SymVal::Bool(b == 0),
+            SymVal::Float(f) => SymVal::Bool(f == 0.0),
+            SymVal::Bool(b) => SymVal::Bool(!b),
+            v => return Err(CError::RuntimeError(format!("Cannot negate {:?}", v), loc)),
+        },
+        COp::Neg => match v {
+            SymVal::Int(n) => match (n.checked_neg(), ctx.overflow) {
+                (Some(i), _) => SymVal::Int(i),
+                (None, Overflow::Wrap) => SymVal::Int(n.wrapping_neg()),
+                (None, Overflow::Trap) =>
+                    return Err(CError::RuntimeError(format!("Signed overflow in `-({})`", n), loc)),
+            },
+            SymVal::Float(n) => SymVal::Float(-n),
+            v => return Err(CError::RuntimeError(format!("Cannot negate {:?}", v), loc)),
+        },
+        _ => return Err(CError::RuntimeError(format!("Unsupported unary operator {:?}", op), loc)),
+    };
+
+    Ok(res)
+}
+
+/// Location of a statement, except blocks which only group others
+pub fn loc_of_stmt<'input>(stmt: &'input CStmt) -> Option<CLoc> {
+    match *stmt {
+        CStmt::Decl(loc, ..) |
+        CStmt::Assign(loc, ..) |
+        CStmt::While(loc, ..) |
+        CStmt::DoWhile(loc, ..) |
+        CStmt::Switch(loc, ..) |
+        CStmt::Case(loc, ..) |
+        CStmt::Break(loc) |
+        CStmt::Call(loc, ..) |
+        CStmt::Return(loc, ..) |
+        CStmt::If(loc, ..) |
+        CStmt::Print(loc, ..) => Some(loc),
+        CStmt::Block(..) |
+        CStmt::Decls(..) |
+        CStmt::Error => None,
+    }
+}
+
+pub fn loc_of_expr<'input>(expr: &'input CExpr) -> Result<CLoc, CError> {
     match *expr {
-        CExpr::Int((l, _), ..) => Ok(l),
-        CExpr::Float((l, _), ..)  => Ok(l),
-        CExpr::Str((l, _), ..) => Ok(l),
-        CExpr::Char((l, _), ..) => Ok(l),
-        CExpr::Ident((l, _), ..) => Ok(l),
-        CExpr::UnOp((l, _), ..) => Ok(l),
-        CExpr::BinOp((l, _), ..) => Ok(l),
-        CExpr::Call((l, _), ..) => Ok(l),
-        CExpr::Index((l, _), ..) => Ok(l),
+        CExpr::Int(loc, ..) => Ok(loc),
+        CExpr::Float(loc, ..)  => Ok(loc),
+        CExpr::Str(loc, ..) => Ok(loc),
+        CExpr::Char(loc, ..) => Ok(loc),
+        CExpr::Ident(loc, ..) => Ok(loc),
+        CExpr::UnOp(loc, ..) => Ok(loc),
+        CExpr::BinOp(loc, ..) => Ok(loc),
+        CExpr::Call(loc, ..) => Ok(loc),
+        CExpr::Index(loc, ..) => Ok(loc),
+        CExpr::Cast(loc, ..) => Ok(loc),
+        CExpr::Ternary(loc, ..) => Ok(loc),
+        CExpr::Comma(loc, ..) => Ok(loc),
         _ => Err(CError::UnknownError(format!("unexpected expr '{:?}'", expr)))
     }
 }
 
-fn auto_cast<'input>(val: &'input SymVal, loc: usize, t: &'input CType) -> Result<SymVal, CError> {
+pub fn to_bool<'input>(val: &'input SymVal, loc: CLoc) -> Result<bool, CError> {
+    match *val {
+        SymVal::Int(i) => Ok(i != 0),
+        SymVal::Float(f) => Ok(f != 0.0),
+        SymVal::Char(c) => Ok(c != '\0'),
+        SymVal::Bool(b) => Ok(b),
+        ref x => Err(CError::RuntimeError(format!("Expected bool, got {:?}", x), loc)),
+    }
+}
+
+pub fn case_value<'input>(val: &'input SymVal, loc: CLoc) -> Result<i32, CError> {
+    match *val {
+        SymVal::Int(i) => Ok(i),
+        SymVal::Char(c) => Ok(c as i32),
+        SymVal::Bool(b) => Ok(if b { 1 } else { 0 }),
+        ref x => Err(CError::RuntimeError(format!("Expected integer in switch, got {:?}", x), loc)),
+    }
+}
+
+/// Integer arithmetic, with division by zero and overflow checked
+fn int_arith(op: COp, i1: i32, i2: i32, loc: CLoc, ctx: &Context) -> Result<SymVal, CError> {
+    let res = match op {
+        COp::Add => i1.checked_add(i2),
+        COp::Sub => i1.checked_sub(i2),
+        COp::Mul => i1.checked_mul(i2),
+        COp::Div => {
+            if i2 == 0 {
+                return Err(CError::RuntimeError(format!("Division by zero in `{} / {}`", i1, i2), loc));
+            }
+            i1.checked_div(i2)
+        },
+        _ => return Err(CError::RuntimeError(format!("Unsupported operator `{:?}`", op), loc)),
+    };
+
+    match (res, ctx.overflow) {
+        (Some(i), _) => Ok(SymVal::Int(i)),
+        (None, Overflow::Wrap) => Ok(SymVal::Int(match op {
+            COp::Add => i1.wrapping_add(i2),
+            COp::Sub => i1.wrapping_sub(i2),
+            COp::Mul => i1.wrapping_mul(i2),
+            _ => i1.wrapping_div(i2),
+        })),
+        (None, Overflow::Trap) =>
+            Err(CError::RuntimeError(format!("Signed overflow in `{} {:?} {}`", i1, op, i2), loc)),
+    }
+}
+
+/// Length of array, either allocated or declared
+fn array_len<'input>(id: &'input str, global_symtab: &SymTab<'input>, local_symtab: &SymTab<'input>) -> Option<usize> {
+    let tab = match local_symtab.get_type(id) {
+        Some(_) => local_symtab,
+        None => global_symtab,
+    };
+    match tab.get_val(id) {
+        Some(SymVal::Array(ref a)) => Some(a.len()),
+        _ => match tab.get_type(id) {
+            Some((_, s)) => s,
+            None => None,
+        },
+    }
+}
+
+/// Check array index against length, pointing at the declaration if out of bounds
+fn check_bounds<'input>(
+    id: &'input str,
+    i: i32,
+    len: Option<usize>,
+    loc: CLoc,
+    global_symtab: &SymTab<'input>,
+    local_symtab: &SymTab<'input>,
+) -> Result<usize, CError>
+{
+    let err = match len {
+        Some(len) if i >= 0 && (i as usize) < len => return Ok(i as usize),
+        Some(len) => CError::RuntimeError(format!("Index {} out of bounds for array '{}' of length {}", i, id, len), loc),
+        None => CError::RuntimeError(format!("Cannot index '{}', which has no size", id), loc),
+    };
+
+    Err(with_decl_note(err, id, global_symtab, local_symtab))
+}
+
+/// Error for a read of something never written, under the uninit sanitizer
+fn uninit_read<'input>(
+    what: String,
+    id: &'input str,
+    loc: CLoc,
+    global_symtab: &SymTab<'input>,
+    local_symtab: &SymTab<'input>,
+) -> CError
+{
+    let err = CError::RuntimeError(format!("Read of uninitialized {}", what), loc);
+    with_decl_note(err, id, global_symtab, local_symtab)
+}
+
+/// Attach the declaration site of a variable to an error, when known
+fn with_decl_note<'input>(
+    err: CError,
+    id: &'input str,
+    global_symtab: &SymTab<'input>,
+    local_symtab: &SymTab<'input>,
+) -> CError
+{
+    let decl = match local_symtab.get_type(id) {
+        Some(_) => local_symtab.get_decl(id),
+        None => global_symtab.get_decl(id),
+    };
+    match decl {
+        Some(loc) => err.with_note(format!("'{}' declared here", id), loc),
+        None => err,
+    }
+}
+
+/// Type of the assigned value, which is the element type when indexing
+fn elem_type(t: &CType, index: &Option<usize>) -> CType {
+    match (t, index) {
+        (&CType::Ref(ref t), &Some(_)) => (**t).clone(),
+        _ => t.clone(),
+    }
+}
+
+/// Integer promotion: chars and truth values take part in arithmetic as ints
+pub fn promote(val: SymVal) -> SymVal {
+    match val {
+        SymVal::Char(c) => SymVal::Int(c as i32),
+        SymVal::Bool(b) => SymVal::Int(if b { 1 } else { 0 }),
+        x => x,
+    }
+}
+
+/// Convert value to type, following C's conversion rules. Chars are 8 bit
+/// and convert to and from ints by code point.
+pub fn cast<'input>(val: &'input SymVal, loc: CLoc, t: &'input CType) -> Result<SymVal, CError> {
     match *t {
         CType::Int => match *val {
             SymVal::Int(_) => Ok(val.clone()),
             SymVal::Float(f) => Ok(SymVal::Int(f as i32)),
-            SymVal::Char(c) => match c.to_digit(16) {
-                Some(i) => Ok(SymVal::Int(i as i32)),
-                None => Err(CError::RuntimeError("Failed to cast char type to int".to_owned(), loc))
-            },
+            SymVal::Char(c) => Ok(SymVal::Int(c as i32)),
             SymVal::Bool(b) => Ok(SymVal::Int(if b { 1 } else { 0 })),
-            SymVal::Array(_) => Err(CError::RuntimeError("Cannot auto cast array type to int".to_owned(), loc)),
+            SymVal::Array(_) => Err(CError::RuntimeError("Cannot cast array type to int".to_owned(), loc)),
         },
         CType::Float => match *val {
             SymVal::Int(i) => Ok(SymVal::Float(i as f32)),
             SymVal::Float(_) => Ok(val.clone()),
-            SymVal::Char(_) => Err(CError::RuntimeError("Cannot auto cast char type to float".to_owned(), loc)),
-            SymVal::Bool(_) => Err(CError::RuntimeError("Cannot auto cast bool type to float".to_owned(), loc)),
-            SymVal::Array(_) => Err(CError::RuntimeError("Cannot auto cast array type to float".to_owned(), loc))
+            SymVal::Char(c) => Ok(SymVal::Float(c as u32 as f32)),
+            SymVal::Bool(b) => Ok(SymVal::Float(if b { 1.0 } else { 0.0 })),
+            SymVal::Array(_) => Err(CError::RuntimeError("Cannot cast array type to float".to_owned(), loc))
         },
         CType::Char => match *val {
-            SymVal::Int(i) => match char::from_digit(i as u32, 16) {
-                Some(c) => Ok(SymVal::Char(c)),
-                None => Err(CError::RuntimeError("Failed to cast int type to char".to_owned(), loc))
-            },
-            SymVal::Float(_) => Err(CError::RuntimeError("Cannot auto cast float type to char".to_owned(), loc)),
+            // wrap around like an unsigned 8 bit char
+            SymVal::Int(i) => Ok(SymVal::Char(i as u8 as char)),
+            SymVal::Float(f) => Ok(SymVal::Char(f as i32 as u8 as char)),
             SymVal::Char(_) => Ok(val.clone()),
-            SymVal::Bool(_) => Err(CError::RuntimeError("Cannot auto cast bool type to char".to_owned(), loc)),
-            SymVal::Array(_) => Err(CError::RuntimeError("Cannot auto cast array type to char".to_owned(), loc))
+            SymVal::Bool(b) => Ok(SymVal::Char(if b { '\u{1}' } else { '\0' })),
+            SymVal::Array(_) => Err(CError::RuntimeError("Cannot cast array type to char".to_owned(), loc))
+        },
+        CType::Ref(_) => match *val {
+            SymVal::Array(_) => Ok(val.clone()),
+            ref x => Err(CError::RuntimeError(format!("Cannot cast {:?} to pointer type '{:?}'", x, t), loc)),
         },
-        CType::Ref(_) => Ok(val.clone()) // TODO: throw error
-        // Array(_) => Err(CError::RuntimeError("Cannot cast array type".to_owned(), loc))
     }
 }
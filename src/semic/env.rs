@@ -1,5 +1,6 @@
 use std::fmt::{Debug, Formatter, Error};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
 
 use ast::*;
 use error::CError;
@@ -11,21 +12,21 @@ pub struct FuncTab<'a> {
     tab: HashMap<&'a str, FuncEntry<'a>>
 }
 
-pub type FuncEntry<'a> = (&'a CProto<'a>, Option<&'a CFunc<'a>>);
+pub type FuncEntry<'a> = (&'a CProto, Option<&'a CFunc>);
 
 impl<'a> FuncTab<'a> {
     pub fn new() -> FuncTab<'a> {
         FuncTab { tab: HashMap::new() }
     }
 
-    pub fn get_proto(&self, key: &'a str) -> Option<&'a CProto<'a>> {
+    pub fn get_proto(&self, key: &'a str) -> Option<&'a CProto> {
         match self.tab.get(key) {
             Some(&(proto, _)) => Some(proto),
             _ => None,
         }
     }
 
-    pub fn get_func(&self, key: &'a str) -> Option<&'a CFunc<'a>> {
+    pub fn get_func(&self, key: &'a str) -> Option<&'a CFunc> {
         match self.tab.get(key) {
             Some(&(_, f)) => f,
             _ => None
@@ -34,8 +35,8 @@ impl<'a> FuncTab<'a> {
 
     pub fn insert(
         &mut self,
-        key: &'a str, proto: &'a CProto<'a>,
-        func: Option<&'a CFunc<'a>>
+        key: &'a str, proto: &'a CProto,
+        func: Option<&'a CFunc>
     ) -> Option<FuncEntry<'a>>
     {
         self.tab.insert(key, (proto, func))
@@ -46,10 +47,42 @@ impl<'a> FuncTab<'a> {
 
 #[derive(Debug, Clone)]
 pub struct SymTab<'a> {
-    stack: Vec<HashMap<&'a str, SymEntry>>
+    /// Innermost frame, linked to those of the calls below it, which the
+    /// copy a call makes of the table shares rather than clones
+    top: Rc<Frame<'a>>
 }
 
-pub type SymEntry = (CType, Option<usize>, Vec<(Option<SymVal>, Option<usize>)>);
+#[derive(Debug, Clone)]
+struct Frame<'a> {
+    vars: HashMap<&'a str, SymEntry>,
+    parent: Option<Rc<Frame<'a>>>,
+    depth: usize,
+    /// Names declared in each block open in the call, with the entries
+    /// they shadow, which are put back when the block ends
+    scopes: Vec<Vec<(&'a str, Option<SymEntry>)>>,
+}
+
+/// Writes kept for each variable, for the debugger's `trace` command
+pub const TRACE_LEN: usize = 16;
+
+/// Variable in a symbol table
+#[derive(Debug, Clone)]
+pub struct SymEntry {
+    t: CType,
+    size: Option<usize>,
+    val: Option<SymVal>,
+    decl: Option<CLoc>,
+    /// Value it was declared with and the writes since, the element
+    /// written for arrays, of which only the last `TRACE_LEN` are kept
+    trace: VecDeque<SymWrite>,
+    /// Writes dropped from the front of the trace
+    dropped: usize,
+    /// Which array elements have been written (empty when all have)
+    written: Vec<bool>,
+}
+
+/// Index written for arrays, value and location of a write
+pub type SymWrite = (Option<usize>, Option<SymVal>, Option<CLoc>);
 
 #[derive(PartialEq, Clone)]
 pub enum SymVal {
@@ -62,105 +95,119 @@ pub enum SymVal {
 
 impl<'a> SymTab<'a> {
     pub fn new() -> SymTab<'a> {
-        SymTab { stack: vec![HashMap::new()] }
+        SymTab { top: Rc::new(Frame { vars: HashMap::new(), parent: None, depth: 1, scopes: vec![] }) }
+    }
+
+    /// Frame of the current call, copied first if a caller still shares it
+    fn frame_mut(&mut self) -> &mut HashMap<&'a str, SymEntry> {
+        &mut Rc::make_mut(&mut self.top).vars
     }
 
     pub fn get_type(&self, key: &'a str) -> Option<(CType, Option<usize>)> {
-        let tab = self.stack.last().unwrap();
-        match tab.get(key) {
-            Some(&(ref t, s, _)) => Some((t.clone(), s)),
+        match self.top.vars.get(key) {
+            Some(entry) => Some((entry.t.clone(), entry.size)),
             _ => None,
         }
     }
 
     pub fn get_val(&self, key: &'a str) -> Option<SymVal> {
-        let tab = self.stack.last().unwrap();
-        match tab.get(key) {
-            Some(&(_, _, ref v)) => match v.last() {
-                Some(&(ref v, _)) => v.clone(),
-                _ => None
-            },
+        match self.top.vars.get(key) {
+            Some(entry) => entry.val.clone(),
             _ => None
         }
     }
 
     pub fn get_val_parent(&self, key: &'a str) -> Option<SymVal> {
-        for i in (1..self.stack.len()).rev() {
-            let tab = self.stack.get(i - 1).unwrap();
-            match tab.get(key) {
-                Some(&(_, _, ref v)) => match v.last() {
-                    Some(&(ref v, _)) => return v.clone(),
-                    _ => ()
-                },
-                _ => ()
+        let mut frame = &self.top.parent;
+        while let Some(ref f) = *frame {
+            if let Some(&SymEntry { val: Some(ref v), .. }) = f.vars.get(key) {
+                return Some(v.clone());
             }
+            frame = &f.parent;
         }
         None
     }
 
-    pub fn get_trace(&self, key: &'a str) -> Option<Vec<(Option<SymVal>, Option<usize>)>> {
-        let tab = self.stack.last().unwrap();
-        match tab.get(key) {
-            Some(&(_, _, ref v)) => Some(v.clone()),
+    /// Location a variable was declared at
+    pub fn get_decl(&self, key: &'a str) -> Option<CLoc> {
+        self.top.vars.get(key).and_then(|entry| entry.decl)
+    }
+
+    /// Writes to a variable that are kept, oldest first, with the number
+    /// of earlier ones dropped
+    pub fn get_trace(&self, key: &'a str) -> Option<(usize, Vec<SymWrite>)> {
+        match self.top.vars.get(key) {
+            Some(entry) => Some((entry.dropped, entry.trace.iter().cloned().collect())),
             _ => None
         }
     }
 
+    /// Whether a variable, or an element of an array, has ever been written
+    pub fn is_written(&self, key: &'a str, i: Option<usize>) -> bool {
+        match self.top.vars.get(key) {
+            Some(&SymEntry { val: Some(_), ref written, .. }) => match i {
+                None => true,
+                Some(i) => written.is_empty() || written.get(i) == Some(&true),
+            },
+            _ => false
+        }
+    }
+
     pub fn set_val(
         &mut self,
         key: &'a str,
         i: Option<usize>,
         val: SymVal,
-        loc: Option<usize>
+        loc: Option<CLoc>
     ) -> Result <(), String>
     {
-        let mut tab = self.stack.last_mut().unwrap();
-        let clone = tab.clone();
-        let &(ref t, s, ref prev) = match clone.get(key) {
+        let entry = match self.frame_mut().get_mut(key) {
             Some(v) => v,
             _ => return Err(format!("Variable '{}' not declared", key)),
         };
 
         match i {
-            // set array
+            // set array, in place
             Some(i) => {
-                let mut vec = prev.clone();
-                let (last_val, _) = prev.last().unwrap().clone();
-                let new = match last_val {
-                    // set in existing array
-                    Some(SymVal::Array(ref a)) => {
-                        let mut a = a.clone();
-                        a.remove(i); // remove old val
-                        a.insert(i, Box::new(val)); // set new val
-                        SymVal::Array(a)
+                match entry.val {
+                    Some(SymVal::Array(ref mut a)) => {
+                        if i >= a.len() {
+                            return Err(format!("Index {} out of bounds for array '{}' of length {}", i, key, a.len()));
+                        }
+                        *a[i] = val.clone();
+                        if let Some(w) = entry.written.get_mut(i) {
+                            *w = true;
+                        }
                     },
-                    Some(x) => return Err(format!("Expected array, got {:?}", x)),
+                    Some(ref x) => return Err(format!("Expected array, got {:?}", x)),
                     // create init array
                     None => {
-                        let mut a = Vec::new();
-                        let size = s.unwrap();
-                        for j in 0..size {
-                            if j == i {
-                                a.push(Box::new(val.clone()));
-                            } else {
-                                a.push(Box::new(SymVal::Int(0)));
-                            }
-                        }
-                        SymVal::Array(a)
+                        let size = match entry.size {
+                            Some(size) if i < size => size,
+                            Some(size) => return Err(format!("Index {} out of bounds for array '{}' of length {}", i, key, size)),
+                            None => return Err(format!("Cannot index '{}', which has no size", key)),
+                        };
+                        let mut a: Vec<Box<SymVal>> = (0..size).map(|_| Box::new(SymVal::Int(0))).collect();
+                        *a[i] = val.clone();
+                        entry.val = Some(SymVal::Array(a));
+                        entry.written = (0..size).map(|j| j == i).collect();
                     },
                 };
-
-                vec.push((Some(new), loc));
-                tab.insert(key, (t.clone(), s, vec));
             },
             // set var
             None => {
-                let mut vec = prev.clone();
-                vec.push((Some(val), loc));
-                tab.insert(key, (t.clone(), s, vec));
+                entry.val = Some(val.clone());
+                entry.written.clear();
             }
         }
 
+        // only the element written is kept for arrays
+        if entry.trace.len() == TRACE_LEN {
+            entry.trace.pop_front();
+            entry.dropped += 1;
+        }
+        entry.trace.push_back((i, Some(val), loc));
+
         Ok(())
     }
 
@@ -170,21 +217,79 @@ impl<'a> SymTab<'a> {
         t: CType,
         s: Option<usize>,
         val: Option<SymVal>,
-        loc: Option<usize>
+        loc: Option<CLoc>
     ) -> Option<SymEntry>
     {
-        let mut tab = self.stack.last_mut().unwrap();
-        let vec = vec![(val, loc)];
-        tab.insert(key, (t, s, vec))
+        let entry = SymEntry {
+            t: t,
+            size: s,
+            val: val.clone(),
+            decl: loc,
+            trace: vec![(None, val, loc)].into_iter().collect(),
+            dropped: 0,
+            written: vec![],
+        };
+        let frame = Rc::make_mut(&mut self.top);
+        let old = frame.vars.insert(key, entry);
+        if let Some(declared) = frame.scopes.last_mut() {
+            declared.push((key, old.clone()));
+        }
+        old
+    }
+
+    /// Open a block, whose declarations last until `leave_scope`
+    pub fn enter_scope(&mut self) {
+        Rc::make_mut(&mut self.top).scopes.push(vec![]);
+    }
+
+    /// Close the innermost block, dropping what it declared and restoring
+    /// the variables that hid
+    pub fn leave_scope(&mut self) {
+        let frame = Rc::make_mut(&mut self.top);
+        if let Some(declared) = frame.scopes.pop() {
+            for (key, old) in declared.into_iter().rev() {
+                match old {
+                    Some(entry) => frame.vars.insert(key, entry),
+                    None => frame.vars.remove(key),
+                };
+            }
+        }
+    }
+
+    /// Names of the variables in the current frame
+    pub fn names(&self) -> Vec<&'a str> {
+        self.top.vars.keys().cloned().collect()
+    }
+
+    /// Frames in the table, one for main and one for each call below it
+    pub fn depth(&self) -> usize {
+        self.top.depth
+    }
+
+    /// Type and value of a variable in a frame, counting from main's
+    pub fn get_in_frame(&self, frame: usize, key: &'a str) -> Option<(CType, Option<SymVal>)> {
+        let mut f = Some(&self.top).filter(|_| frame < self.top.depth);
+        for _ in frame + 1..self.top.depth {
+            f = f.and_then(|f| f.parent.as_ref());
+        }
+        match f.and_then(|f| f.vars.get(key)) {
+            Some(entry) => Some((entry.t.clone(), entry.val.clone())),
+            None => None,
+        }
     }
 
     pub fn push_frame(&mut self) {
-        self.stack.push(HashMap::new())
+        let depth = self.top.depth + 1;
+        let parent = Some(self.top.clone());
+        self.top = Rc::new(Frame { vars: HashMap::new(), parent: parent, depth: depth, scopes: vec![] })
     }
 
     pub fn pop_frame(&mut self) -> Result<(), CError> {
-        match self.stack.pop() {
-            Some(_) => Ok(()),
+        match self.top.parent.clone() {
+            Some(parent) => {
+                self.top = parent;
+                Ok(())
+            },
             None => Err(CError::UnknownError("Cannot pop frame of empty symbol table".to_owned()))
         }
     }
@@ -2,84 +2,397 @@ extern crate lalrpop_util;
 
 use lalrpop_util::ParseError;
 
+use ast::CLoc;
+use engine::Limit;
+use json::Json;
+use lint::Lint;
+use source::{FileId, SourceMap};
+
 #[derive(Debug)]
 pub enum CError {
-    ParseError(String, usize),
-    RuntimeError(String, usize),
-    CheckerError(Vec<(String, Option<usize>)>),
+    ParseError(String, CLoc),
+    RuntimeError(String, CLoc),
+    CheckerError(Vec<(String, Option<CLoc>)>),
     UnknownError(String),
+    WithNote(Box<CError>, String, CLoc),
+    WithHint(Box<CError>, String),
+    Warning(Lint, String, CLoc),
+    /// Run stopped by one of its limits at the statement that hit it
+    LimitError(Limit, String, CLoc),
+    Multiple(Vec<CError>),
 }
 
 impl<'input> CError {
-    pub fn from_lalrpop(err: lalrpop_util::ParseError<usize, (usize, &'input str), ()>) -> CError {
+    /// Attach a note pointing at related code, e.g. a declaration
+    pub fn with_note(self, msg: String, loc: CLoc) -> CError {
+        CError::WithNote(Box::new(self), msg, loc)
+    }
+
+    /// Single error, or all of them when there are several
+    pub fn from_errors(mut errs: Vec<CError>) -> CError {
+        if errs.len() == 1 {
+            errs.pop().unwrap()
+        } else {
+            CError::Multiple(errs)
+        }
+    }
+
+    /// Lint a warning was raised by, if it is one
+    pub fn lint(&self) -> Option<Lint> {
+        match *self {
+            CError::Warning(lint, ..) => Some(lint),
+            CError::WithNote(ref err, ..) |
+            CError::WithHint(ref err, _) => err.lint(),
+            _ => None,
+        }
+    }
+
+    /// Attach a hint on how to fix the error
+    pub fn with_hint(self, msg: &str) -> CError {
+        CError::WithHint(Box::new(self), msg.to_owned())
+    }
+
+    /// Translate a parser error into a readable message, given the source
+    pub fn from_lalrpop(
+        err: ParseError<usize, (usize, &'input str), ()>,
+        input: &'input str
+    ) -> CError
+    {
         match err {
-            ParseError::InvalidToken { location } =>
-                CError::ParseError(format!("Invalid token"), location),
-            ParseError::UnrecognizedToken { token: Some((loc, (_, tok), _)), expected: exp } =>
-                CError::ParseError(format!("Unrecognized token {:?}. Expected either {:?}", tok, exp), loc),
-            ParseError::ExtraToken { token: (loc, tok, _) } =>
-                CError::ParseError(format!("Extra token {:?}", tok), loc),
-            _ => CError::UnknownError(format!("unknown parse error: {:?}", err)),
+            ParseError::InvalidToken { location } => {
+                let c = input[location..].chars().next().unwrap_or(' ');
+                CError::ParseError(format!("Invalid character '{}'", c), (location, location + c.len_utf8()))
+            },
+            ParseError::UnrecognizedToken { token: Some((loc, (_, tok), end)), expected } => {
+                let has = |t: &str| expected.iter().any(|e| e == t);
+                let prev = input[..loc].trim_end().len();
+
+                if has("\";\"") && (tok == "}" || input[prev..loc].contains('\n')) {
+                    let err = CError::ParseError(format!("Missing ';'"), (prev, prev));
+                    if tok == "}" {
+                        err.with_hint("the last statement before '}' needs a ';' too")
+                    } else {
+                        err
+                    }
+                } else {
+                    // inside parentheses, a ')' is more likely missing than a ';'
+                    let line = &input[input[..loc].rfind('\n').map_or(0, |i| i + 1)..loc];
+                    let in_parens = line.matches('(').count() > line.matches(')').count();
+                    let err = CError::ParseError(
+                        format!("Unexpected '{}', expected {}", tok, describe(&expected, in_parens)), (loc, end));
+                    if tok == "=" && has("\"==\"") {
+                        err.with_hint("use '==' to compare values, '=' is only for assignment")
+                    } else {
+                        err
+                    }
+                }
+            },
+            ParseError::UnrecognizedToken { token: None, expected } => {
+                // point just past the last character of the file
+                let loc = input.trim_end().len();
+                let err = CError::ParseError(
+                    format!("Unexpected end of file, expected {}", describe(&expected, false)), (loc, loc));
+                if expected.iter().any(|e| e == "\"}\"") {
+                    err.with_hint("a '{' is never closed")
+                } else {
+                    err
+                }
+            },
+            ParseError::ExtraToken { token: (loc, (_, tok), end) } =>
+                CError::ParseError(format!("Unexpected '{}'", tok), (loc, end)),
+            ParseError::User { .. } =>
+                CError::UnknownError(format!("unknown parse error: {:?}", err)),
+        }
+    }
+}
+
+/// Phrase for a set of expected tokens, e.g. "an expression"
+fn describe(expected: &[String], in_parens: bool) -> String {
+    let has = |t: &str| expected.iter().any(|e| e == t);
+    let ops = ["+", "-", "*", "/", "==", "!=", "<", "<=", ">", ">=", "&&", "||", "?"];
+
+    if has("\"return\"") {
+        return "a statement".to_owned();
+    }
+    if has("TInt") && has("TIdent") {
+        return "an expression".to_owned();
+    }
+    if has("\"int\"") && has("\"char\"") {
+        return "a type".to_owned();
+    }
+
+    let mut words: Vec<String> = vec![];
+    if ops.iter().any(|op| has(&format!("{:?}", op))) {
+        words.push("an operator".to_owned());
+        if in_parens && has("\")\"") {
+            words.push("')'".to_owned());
+        } else if has("\";\"") {
+            words.push("';'".to_owned());
+        }
+    } else {
+        for e in expected.iter() {
+            words.push(match e.as_str() {
+                "TIdent" => "a name".to_owned(),
+                "TInt" | "TFloat" => "a number".to_owned(),
+                "TString" => "a string".to_owned(),
+                "TChar" => "a character".to_owned(),
+                x => format!("'{}'", x.trim_matches('"')),
+            });
+        }
+        words.dedup();
+    }
+
+    match words.pop() {
+        None => "nothing".to_owned(),
+        Some(last) if words.is_empty() => last,
+        Some(last) => format!("{} or {}", words.join(", "), last),
+    }
+}
+
+// diagnostics
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Syntax,
+    Type,
+    Runtime,
+    Lint,
+    Limit,
+    Other,
+}
+
+/// Position in the source. Lines and columns are 1-based, and the end
+/// is exclusive
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub message: String,
+    /// File of the span, which can be another than the diagnostic's
+    pub file: String,
+    pub span: Option<Span>,
+}
+
+/// Error report as data, for printing or for tools to consume
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: Kind,
+    pub message: String,
+    pub file: String,
+    pub span: Option<Span>,
+    pub notes: Vec<Note>,
+    /// Name of the lint for warnings, e.g. "unused"
+    pub code: Option<String>,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Kind::Syntax => "syntax",
+            Kind::Type => "type",
+            Kind::Runtime => "runtime",
+            Kind::Lint => "lint",
+            Kind::Limit => "limit",
+            Kind::Other => "other",
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Serialize as a single-line JSON object
+    pub fn to_json(&self) -> Json {
+        let mut pairs = vec![
+            ("severity", Json::str(self.severity.as_str())),
+            ("kind", Json::str(self.kind.as_str())),
+            ("message", Json::str(&self.message)),
+            ("file", Json::str(&self.file)),
+        ];
+        push_span(&mut pairs, &self.span);
+        pairs.push(("code", self.code.as_ref().map_or(Json::Null, |c| Json::str(c))));
+
+        let notes = self.notes.iter().map(|n| {
+            let mut pairs = vec![("message", Json::str(&n.message)), ("file", Json::str(&n.file))];
+            push_span(&mut pairs, &n.span);
+            Json::object(pairs)
+        }).collect();
+        pairs.push(("notes", Json::Array(notes)));
+
+        Json::object(pairs)
+    }
+
+    fn head(&self) -> String {
+        match (self.kind, self.severity, &self.code) {
+            (Kind::Syntax, ..) => "Syntax error".to_owned(),
+            (Kind::Type, ..) => "Type error".to_owned(),
+            (Kind::Runtime, ..) => "Run-time error".to_owned(),
+            (Kind::Limit, ..) => "Limit exceeded".to_owned(),
+            (Kind::Lint, Severity::Warning, &Some(ref c)) => format!("Warning [{}]", c),
+            (Kind::Lint, Severity::Error, &Some(ref c)) => format!("Error [{}]", c),
+            _ => "Error".to_owned(),
         }
     }
 }
 
+fn push_span(pairs: &mut Vec<(&str, Json)>, span: &Option<Span>) {
+    let s = span.as_ref();
+    pairs.push(("line", Json::opt_num(s.map(|s| s.line))));
+    pairs.push(("column", Json::opt_num(s.map(|s| s.column))));
+    pairs.push(("end_line", Json::opt_num(s.map(|s| s.end_line))));
+    pairs.push(("end_column", Json::opt_num(s.map(|s| s.end_column))));
+}
+
 // printer
 
 pub struct ErrorPrinter {
-    filename: String,
-    lines: Vec<String>,
+    sources: SourceMap,
 }
 
 impl<'a> ErrorPrinter {
     pub fn new(filename: &'a str, prog: &'a str) -> ErrorPrinter {
-        ErrorPrinter {
-            filename: String::from(filename),
-            lines: prog.split("\n").map(|x| String::from(x)).collect(),
-        }
+        let mut sources = SourceMap::new();
+        sources.add(filename, prog);
+        ErrorPrinter::from_map(sources)
+    }
+
+    /// Printer for a program of several files, where errors without a
+    /// location are put in the first
+    pub fn from_map(sources: SourceMap) -> ErrorPrinter {
+        ErrorPrinter { sources: sources }
     }
 
     pub fn print_err(&self, err: CError) {
-        let (head, es) = match err {
-            CError::ParseError(msg, loc) => ("Syntax error", vec![(msg, Some(loc))]),
-            CError::RuntimeError(msg, loc) => ("Run-time error", vec![(msg, Some(loc))]),
-            CError::CheckerError(es) => ("Type error", es),
-            CError::UnknownError(msg) => ("Error", vec![(msg, None)]),
+        for d in self.diagnostics(err) {
+            self.print_diag(&d);
+        }
+    }
+
+    /// Convert an error into one diagnostic per reported problem
+    pub fn diagnostics(&self, err: CError) -> Vec<Diagnostic> {
+        let (kind, es) = match err {
+            CError::WithNote(err, msg, loc) => {
+                let mut ds = self.diagnostics(*err);
+                if let Some(d) = ds.last_mut() {
+                    let (file, span) = self.span(Some(loc));
+                    d.notes.push(Note { message: msg, file: file, span: span });
+                }
+                return ds;
+            },
+            CError::WithHint(err, msg) => {
+                let mut ds = self.diagnostics(*err);
+                if let Some(d) = ds.last_mut() {
+                    let file = d.file.clone();
+                    d.notes.push(Note { message: msg, file: file, span: None });
+                }
+                return ds;
+            },
+            CError::Multiple(errs) =>
+                return errs.into_iter().flat_map(|e| self.diagnostics(e)).collect(),
+            CError::ParseError(msg, loc) => (Kind::Syntax, vec![(msg, Some(loc))]),
+            CError::RuntimeError(msg, loc) => (Kind::Runtime, vec![(msg, Some(loc))]),
+            CError::CheckerError(es) => (Kind::Type, es),
+            CError::UnknownError(msg) => (Kind::Other, vec![(msg, None)]),
+            CError::Warning(lint, msg, loc) => {
+                let (file, span) = self.span(Some(loc));
+                return vec![Diagnostic {
+                    severity: Severity::Warning,
+                    kind: Kind::Lint,
+                    message: msg,
+                    file: file,
+                    span: span,
+                    notes: vec![],
+                    code: Some(lint.name().to_owned()),
+                }];
+            },
+            CError::LimitError(limit, msg, loc) => {
+                let (file, span) = self.span(Some(loc));
+                return vec![Diagnostic {
+                    severity: Severity::Error,
+                    kind: Kind::Limit,
+                    message: msg,
+                    file: file,
+                    span: span,
+                    notes: vec![],
+                    code: Some(limit.flag().to_owned()),
+                }];
+            },
         };
 
-        for (msg, loc) in es {
-            match loc {
-                Some(loc) => {
-                    let (i, off) = self.get_line_with_off(loc).unwrap();
-                    println!("{}: line {}:{} ({})", head, i + 1, off, self.filename);
-                    self.print_at_loc(loc)
-                },
-                None => println!("{}: ({})", head, self.filename),
-
-            };
-            println!(" └ > {}", msg);
-        }
+        es.into_iter().map(|(msg, loc)| {
+            let (file, span) = self.span(loc);
+            Diagnostic {
+                severity: Severity::Error,
+                kind: kind,
+                message: msg,
+                file: file,
+                span: span,
+                notes: vec![],
+                code: None,
+            }
+        }).collect()
     }
 
-    fn print_at_loc(&self, loc: usize) {
-        let (i, off) = self.get_line_with_off(loc).unwrap();
-        let line = self.lines.get(i).unwrap();
-        println!(" │ {}", line);
-        println!(" │ {}^", String::from_utf8(vec![b' '; off]).unwrap());
+    /// Name of the file a location is in, and its span there
+    fn span(&self, loc: Option<CLoc>) -> (String, Option<Span>) {
+        let (id, span) = match loc.and_then(|loc| self.sources.span(loc)) {
+            Some((id, span)) => (id, Some(span)),
+            None => (0, None),
+        };
+        (self.name(id).to_owned(), span)
     }
 
-    fn get_line_with_off(&self, loc: usize) -> Option<(usize, usize)> {
-        let mut count = 0;
+    fn name(&self, id: FileId) -> &str {
+        self.sources.file(id).map_or("", |f| f.name.as_str())
+    }
 
-        for (i, line) in self.lines.iter().enumerate() {
-            if loc < (count + line.len() + 1) {
-                return Some((i, loc - count))
-            }
+    /// Print diagnostic in human-readable form
+    pub fn print_diag(&self, diag: &Diagnostic) {
+        print!("{}", self.format_diag(diag));
+    }
 
-            // +1 for newline char
-            count += line.len() + 1;
+    /// Diagnostic in human-readable form, as printed
+    pub fn format_diag(&self, diag: &Diagnostic) -> String {
+        let mut out = self.format_part(&diag.head(), &diag.message, &diag.file, &diag.span);
+        for note in diag.notes.iter() {
+            match note.span {
+                Some(_) => out.push_str(&self.format_part("Note", &note.message, &note.file, &note.span)),
+                None => out.push_str(&format!(" └ > Hint: {}\n", note.message)),
+            }
         }
+        out
+    }
 
-        None
+    fn format_part(&self, head: &str, msg: &str, file: &str, span: &Option<Span>) -> String {
+        let source = self.sources.find(file).and_then(|id| self.sources.file(id));
+        let mut out = match (span, source) {
+            (&Some(ref span), Some(source)) =>
+                format!("{}: line {}:{} ({})\n{}", head, span.line, span.column - 1, file, source.underline(span)),
+            (&Some(ref span), None) => format!("{}: line {}:{} ({})\n", head, span.line, span.column - 1, file),
+            (&None, _) => format!("{}: ({})\n", head, file),
+        };
+        out.push_str(&format!(" └ > {}\n", msg));
+        out
     }
 }
@@ -0,0 +1,250 @@
+//! Loading of programs split across files with `#include`.
+//!
+//! Every file is preprocessed and parsed on its own and added to a
+//! `SourceMap`, and the locations in its syntax tree are moved back from
+//! the preprocessed text to the range the map gives the file. What a file
+//! declares takes the place of the `#include` naming it, and each file is
+//! included once, however often it is named, so headers need no guards.
+//! Macros are shared by all files, in the order they are read.
+
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use ast::*;
+use error::CError;
+use preprocess::{self, Macros};
+use source::SourceMap;
+
+/// Preprocess and parse a program and the files it includes, reading them
+/// with `read`. Paths are relative to the file including them. The map is
+/// returned also when loading fails, for reporting the errors
+pub fn load<F>(name: &str, src: &str, read: F) -> (SourceMap, Result<CProg, CError>)
+    where F: Fn(&str) -> io::Result<String>
+{
+    let mut loader = Loader { sources: SourceMap::new(), read: read, errors: vec![], seen: HashSet::new() };
+    let prog = loader.file(name, src, &mut Macros::new());
+
+    let res = match loader.errors.len() {
+        0 => Ok(prog),
+        _ => Err(CError::from_errors(loader.errors)),
+    };
+    (loader.sources, res)
+}
+
+struct Loader<F> {
+    sources: SourceMap,
+    read: F,
+    errors: Vec<CError>,
+    /// Files read so far, by `identity`
+    seen: HashSet<PathBuf>,
+}
+
+impl<F> Loader<F> where F: Fn(&str) -> io::Result<String> {
+    fn file(&mut self, name: &str, src: &str, macros: &mut Macros) -> CProg {
+        self.seen.insert(identity(name));
+        let id = self.sources.add(name, src);
+        let base = self.sources.base(id);
+
+        // included files are read as their `#include` is reached, for the
+        // macros they define, and take its place once the file is parsed
+        let mut included = VecDeque::new();
+        let expanded = preprocess::preprocess(src, macros, |path, loc, macros| {
+            let prog = self.include(name, path, shift(loc, base), macros);
+            included.push_back(prog);
+        });
+        let expanded = match expanded {
+            Ok(expanded) => expanded,
+            Err(err) => {
+                self.errors.push(relocate_err(err, &|loc| shift(loc, base)));
+                return vec![];
+            },
+        };
+        let relocate = |loc| shift(expanded.loc(loc), base);
+
+        let ast = match ::parse_prog(&expanded.text) {
+            Ok(ast) => ast,
+            Err(err) => {
+                self.errors.push(relocate_err(err, &relocate));
+                return vec![];
+            },
+        };
+
+        let mut prog = vec![];
+        for mut elem in ast {
+            if let CProgElem::Include(..) = elem {
+                prog.extend(included.pop_front().unwrap_or_default());
+                continue;
+            }
+            relocate_elem(&mut elem, &relocate);
+            prog.push(elem);
+        }
+        prog
+    }
+
+    /// Program of an included file, or nothing if it has been included
+    /// before or cannot be read
+    fn include(&mut self, name: &str, path: &str, loc: CLoc, macros: &mut Macros) -> CProg {
+        let path = relative_to(name, path);
+        if self.seen.contains(&identity(&path)) {
+            return vec![];
+        }
+        match (self.read)(&path) {
+            Ok(src) => self.file(&path, &src, macros),
+            Err(err) => {
+                self.errors.push(CError::ParseError(format!("Cannot include '{}' ({})", path, err), loc));
+                vec![]
+            },
+        }
+    }
+}
+
+/// Path of an included file, as seen from the directory of the file
+/// including it
+fn relative_to(name: &str, path: &str) -> String {
+    let path = match Path::new(name).parent() {
+        Some(dir) if !Path::new(path).is_absolute() => dir.join(path),
+        _ => PathBuf::from(path),
+    };
+    normalize(&path).to_string_lossy().into_owned()
+}
+
+/// What tells files apart whatever path names them: the canonical path, or
+/// for files not on disk, as when reading from memory, the normalized one
+fn identity(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| normalize(Path::new(path)))
+}
+
+/// Path with `.` and `..` resolved without looking at the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => (),
+            Component::ParentDir => match res.components().next_back() {
+                Some(Component::Normal(_)) => { res.pop(); },
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => res.push(".."),
+            },
+            c => res.push(c.as_os_str()),
+        }
+    }
+    res
+}
+
+// moving locations
+
+fn shift(loc: CLoc, base: usize) -> CLoc {
+    (loc.0 + base, loc.1 + base)
+}
+
+fn relocate_err<F: Fn(CLoc) -> CLoc>(err: CError, f: &F) -> CError {
+    match err {
+        CError::ParseError(msg, loc) => CError::ParseError(msg, f(loc)),
+        CError::WithHint(err, msg) => CError::WithHint(Box::new(relocate_err(*err, f)), msg),
+        CError::Multiple(errs) => CError::Multiple(errs.into_iter().map(|e| relocate_err(e, f)).collect()),
+        err => err,
+    }
+}
+
+fn relocate_elem<F: Fn(CLoc) -> CLoc>(elem: &mut CProgElem, f: &F) {
+    match *elem {
+        CProgElem::Decl(ref mut loc, _, _, ref mut so) => {
+            *loc = f(*loc);
+            if let Some(ref mut e) = *so { relocate_expr(e, f); }
+        },
+        CProgElem::Extern(ref mut loc, ..) |
+        CProgElem::Proto(ref mut loc, _) |
+        CProgElem::Include(ref mut loc, _) => *loc = f(*loc),
+        CProgElem::Func(ref mut loc, ref mut func) => {
+            *loc = f(*loc);
+            relocate_stmt(&mut func.body, f);
+        },
+        CProgElem::Error => (),
+    }
+}
+
+fn relocate_stmt<F: Fn(CLoc) -> CLoc>(stmt: &mut CStmt, f: &F) {
+    match *stmt {
+        CStmt::Decl(ref mut loc, _, _, ref mut eo) => {
+            *loc = f(*loc);
+            if let Some(ref mut e) = *eo { relocate_expr(e, f); }
+        },
+        CStmt::Assign(ref mut loc, _, ref mut eo, ref mut e) => {
+            *loc = f(*loc);
+            if let Some(ref mut i) = *eo { relocate_expr(i, f); }
+            relocate_expr(e, f);
+        },
+        CStmt::Call(ref mut loc, _, ref mut args) => {
+            *loc = f(*loc);
+            for e in args.iter_mut() { relocate_expr(e, f); }
+        },
+        CStmt::Return(ref mut loc, ref mut eo) |
+        CStmt::Case(ref mut loc, ref mut eo) => {
+            *loc = f(*loc);
+            if let Some(ref mut e) = *eo { relocate_expr(e, f); }
+        },
+        CStmt::Block(ref mut loc, ref mut stmts) |
+        CStmt::Decls(ref mut loc, ref mut stmts) => {
+            *loc = f(*loc);
+            for s in stmts.iter_mut() { relocate_stmt(s, f); }
+        },
+        CStmt::If(ref mut loc, ref mut cond, ref mut s, ref mut so) => {
+            *loc = f(*loc);
+            relocate_expr(cond, f);
+            relocate_stmt(s, f);
+            if let Some(ref mut s) = *so { relocate_stmt(s, f); }
+        },
+        CStmt::While(ref mut loc, ref mut cond, ref mut s) |
+        CStmt::DoWhile(ref mut loc, ref mut s, ref mut cond) => {
+            *loc = f(*loc);
+            relocate_expr(cond, f);
+            relocate_stmt(s, f);
+        },
+        CStmt::Switch(ref mut loc, ref mut e, ref mut stmts) => {
+            *loc = f(*loc);
+            relocate_expr(e, f);
+            for s in stmts.iter_mut() { relocate_stmt(s, f); }
+        },
+        CStmt::Break(ref mut loc) => *loc = f(*loc),
+        CStmt::Print(ref mut loc, _, ref mut e) => {
+            *loc = f(*loc);
+            relocate_expr(e, f);
+        },
+        CStmt::Error => (),
+    }
+}
+
+fn relocate_expr<F: Fn(CLoc) -> CLoc>(expr: &mut CExpr, f: &F) {
+    match *expr {
+        CExpr::Int(ref mut loc, _) |
+        CExpr::Float(ref mut loc, _) |
+        CExpr::Str(ref mut loc, _) |
+        CExpr::Char(ref mut loc, _) |
+        CExpr::Ident(ref mut loc, _) => *loc = f(*loc),
+        CExpr::UnOp(ref mut loc, _, ref mut e) |
+        CExpr::Index(ref mut loc, _, ref mut e) |
+        CExpr::Cast(ref mut loc, _, ref mut e) => {
+            *loc = f(*loc);
+            relocate_expr(e, f);
+        },
+        CExpr::BinOp(ref mut loc, _, ref mut e1, ref mut e2) |
+        CExpr::Comma(ref mut loc, ref mut e1, ref mut e2) => {
+            *loc = f(*loc);
+            relocate_expr(e1, f);
+            relocate_expr(e2, f);
+        },
+        CExpr::Call(ref mut loc, _, ref mut args) => {
+            *loc = f(*loc);
+            for e in args.iter_mut() { relocate_expr(e, f); }
+        },
+        CExpr::Ternary(ref mut loc, ref mut c, ref mut e1, ref mut e2) => {
+            *loc = f(*loc);
+            relocate_expr(c, f);
+            relocate_expr(e1, f);
+            relocate_expr(e2, f);
+        },
+        CExpr::Error => (),
+    }
+}
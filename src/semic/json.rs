@@ -0,0 +1,260 @@
+use std::fmt::{Display, Formatter, Error};
+
+/// Minimal JSON value, enough for emitting machine-readable output and
+/// reading the messages of editors
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Build an object from key-value pairs, keeping their order
+    pub fn object(pairs: Vec<(&str, Json)>) -> Json {
+        Json::Object(pairs.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    pub fn str(s: &str) -> Json {
+        Json::Str(s.to_owned())
+    }
+
+    pub fn opt_num(n: Option<usize>) -> Json {
+        match n {
+            Some(n) => Json::Num(n as f64),
+            None => Json::Null,
+        }
+    }
+
+    /// Parse a value, which has to be all of the text
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semic::json::Json;
+    /// let json = Json::parse(r#"{"id": 1, "params": {"uri": "a\u00e9"}}"#).unwrap();
+    /// assert_eq!(Some(1.0), json.get("id").and_then(Json::as_num));
+    /// assert_eq!(Some("a\u{e9}"), json.get("params").and_then(|p| p.get("uri")).and_then(Json::as_str));
+    /// ```
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { text: text, pos: 0 };
+        let json = parser.value()?;
+        parser.space();
+        match parser.pos == text.len() {
+            true => Ok(json),
+            false => Err(format!("Unexpected text at {}", parser.pos)),
+        }
+    }
+
+    /// Value of a key, if this is an object with it
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref ps) => ps.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::Str(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_num(&self) -> Option<f64> {
+        match *self {
+            Json::Num(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches(|c| c == ' ' || c == '\t' || c == '\n' || c == '\r').len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), String> {
+        match self.text[self.pos..].starts_with(s) {
+            true => {
+                self.pos += s.len();
+                Ok(())
+            },
+            false => Err(format!("Expected '{}' at {}", s, self.pos)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.space();
+        match self.peek() {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::Str),
+            Some('[') => {
+                self.pos += 1;
+                let mut vs = vec![];
+                self.space();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(vs));
+                }
+                loop {
+                    vs.push(self.value()?);
+                    self.space();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect("]").map(|_| Json::Array(vs))
+            },
+            Some('{') => {
+                self.pos += 1;
+                let mut ps = vec![];
+                self.space();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(ps));
+                }
+                loop {
+                    self.space();
+                    let k = self.string()?;
+                    self.space();
+                    self.expect(":")?;
+                    ps.push((k, self.value()?));
+                    self.space();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect("}").map(|_| Json::Object(ps))
+            },
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let rest = &self.text[self.pos..];
+                let len = rest.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c))).unwrap_or(rest.len());
+                match rest[..len].parse() {
+                    Ok(n) => {
+                        self.pos += len;
+                        Ok(Json::Num(n))
+                    },
+                    Err(_) => Err(format!("Bad number at {}", self.pos)),
+                }
+            },
+            _ => Err(format!("Expected a value at {}", self.pos)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(format!("Unterminated string at {}", self.pos)),
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let e = match self.peek() {
+                        Some(e) => e,
+                        None => return Err(format!("Unterminated string at {}", self.pos)),
+                    };
+                    self.pos += e.len_utf8();
+                    match e {
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => {
+                            let mut code = self.hex()?;
+                            // characters outside the basic plane come as surrogate pairs
+                            if code >= 0xd800 && code < 0xdc00 && self.text[self.pos..].starts_with("\\u") {
+                                self.pos += 2;
+                                let low = self.hex()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            s.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                        },
+                        e => s.push(e),
+                    }
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let code = self.text.get(self.pos..(self.pos + 4)).and_then(|h| u32::from_str_radix(h, 16).ok());
+        match code {
+            Some(code) => {
+                self.pos += 4;
+                Ok(code)
+            },
+            None => Err(format!("Bad escape at {}", self.pos)),
+        }
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        use self::Json::*;
+        match *self {
+            Null => write!(fmt, "null"),
+            Bool(b) => write!(fmt, "{}", b),
+            Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(fmt, "{}", n as i64),
+            Num(n) if n.is_finite() => write!(fmt, "{}", n),
+            Num(_) => write!(fmt, "null"),
+            Str(ref s) => write_str(fmt, s),
+            Array(ref vs) => {
+                write!(fmt, "[")?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i > 0 { write!(fmt, ",")?; }
+                    write!(fmt, "{}", v)?;
+                }
+                write!(fmt, "]")
+            },
+            Object(ref ps) => {
+                write!(fmt, "{{")?;
+                for (i, &(ref k, ref v)) in ps.iter().enumerate() {
+                    if i > 0 { write!(fmt, ",")?; }
+                    write_str(fmt, k)?;
+                    write!(fmt, ":{}", v)?;
+                }
+                write!(fmt, "}}")
+            },
+        }
+    }
+}
+
+fn write_str(fmt: &mut Formatter, s: &str) -> Result<(), Error> {
+    write!(fmt, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(fmt, "\\\"")?,
+            '\\' => write!(fmt, "\\\\")?,
+            '\n' => write!(fmt, "\\n")?,
+            '\r' => write!(fmt, "\\r")?,
+            '\t' => write!(fmt, "\\t")?,
+            c if (c as u32) < 0x20 => write!(fmt, "\\u{:04x}", c as u32)?,
+            c => write!(fmt, "{}", c)?,
+        }
+    }
+    write!(fmt, "\"")
+}
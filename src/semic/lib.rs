@@ -1,4 +1,6 @@
 extern crate lalrpop_util;
+extern crate rustyline;
+extern crate stacker;
 
 pub mod ast;
 pub mod parser;
@@ -7,12 +9,34 @@ pub mod env;
 pub mod engine;
 pub mod error;
 pub mod repl;
-pub mod util;
+pub mod json;
+pub mod lint;
+pub mod bytecode;
+pub mod resolver;
+pub mod compiler;
+pub mod vm;
+pub mod profile;
+pub mod coverage;
+pub mod trace;
+pub mod pretty;
+pub mod build;
+pub mod source;
+pub mod preprocess;
+pub mod include;
+pub mod lsp;
+pub mod dap;
+
+use std::fs::File;
+use std::io::Read;
 
 use ast::{CProg, CProgElem, CFunc, CStmt, CExpr};
+use lalrpop_util::{ErrorRecovery, ParseError};
+
 use error::CError;
+use source::SourceMap;
 
-/// Run program
+/// Run program, returning its result or the diagnostics explaining why it
+/// failed
 ///
 /// # Examples
 ///
@@ -20,44 +44,174 @@ use error::CError;
 /// use semic::env::SymVal;
 /// let filename = "foo.semic".to_owned();
 /// let program = r#"int main () { return 0; }"#.to_owned();
-/// let result = semic::run(filename, program, vec![], false, false);
+/// let context = semic::engine::Context::new();
+/// let result = semic::run(filename, program, vec![], false, false, &context);
 /// assert!(result.is_ok());
 /// assert_eq!(Some(SymVal::Int(0)), result.unwrap());
 /// ```
+///
+/// ```
+/// use semic::error::Kind;
+/// let filename = "foo.semic".to_owned();
+/// let program = "int main () {\n  return 1 / 0;\n}".to_owned();
+/// let context = semic::engine::Context::new();
+/// let diags = semic::run(filename, program, vec![], false, false, &context).unwrap_err();
+/// assert_eq!(Kind::Runtime, diags[0].kind);
+/// assert_eq!(2, diags[0].span.as_ref().unwrap().line);
+/// ```
 pub fn run(
     filename: String,
     program: String,
     args: Vec<String>,
     debug: bool,
-    verbose: bool
-) -> Result<Option<env::SymVal>, ()>
+    verbose: bool,
+    context: &engine::Context,
+) -> Result<Option<env::SymVal>, Vec<error::Diagnostic>>
 {
-    let error_printer = error::ErrorPrinter::new(&filename, &program);
+    let (sources, loaded) = load(&filename, &program);
+    let error_printer = error::ErrorPrinter::from_map(sources.clone());
 
-    let ast = match parse_prog(&program) {
+    let ast = match loaded {
         Ok(ast) => {
             if verbose { println!("ast: {:#?}", &ast); }
             ast
         },
-        Err(err) => {
-            error_printer.print_err(err);
-            return Err(());
-        }
+        Err(err) => return Err(error_printer.diagnostics(err)),
     };
 
-    match engine::run_prog(&ast, &program, &args, debug, verbose) {
+    // fail on denied lints, leaving warnings to `lint`
+    let lints = lint_diagnostics(&ast, &error_printer, context);
+    if lints.iter().any(|d| d.severity == error::Severity::Error) {
+        return Err(lints);
+    }
+
+    // the debuggers step through the AST, so they always use the tree-walker
+    let res = match context.backend {
+        engine::Backend::Vm if !debug && context.adapter().is_none() => vm::run_prog(&ast, &args, context),
+        _ => engine::run_sources(&ast, &sources, &args, debug, verbose, context),
+    };
+
+    match res {
         Ok(ret) => {
             if verbose { println!("returned: {:?}", ret); }
             Ok(ret)
         }
-        Err(err) => {
-            error_printer.print_err(err);
-            Err(())
+        Err(err) => Err(error_printer.diagnostics(err)),
+    }
+}
+
+/// Lint program, giving warnings, or errors for warnings the context turns
+/// into errors. Programs that do not parse give none, as `run` reports them
+///
+/// # Examples
+///
+/// ```
+/// let program = "int main () {\n  int x;\n  return 0;\n}";
+/// let context = semic::engine::Context::new();
+/// let diags = semic::lint("foo.semic", program, &context);
+/// assert_eq!(1, diags.len());
+/// assert_eq!("Unused variable 'x'", diags[0].message);
+/// ```
+pub fn lint(filename: &str, program: &str, context: &engine::Context) -> Vec<error::Diagnostic> {
+    let (sources, loaded) = load(filename, program);
+    let error_printer = error::ErrorPrinter::from_map(sources);
+
+    match loaded {
+        Ok(ast) => lint_diagnostics(&ast, &error_printer, context),
+        Err(_) => vec![],
+    }
+}
+
+/// Preprocess and parse program and the files it includes, reading them
+/// from disk relative to the file including them. The sources are returned
+/// also when it fails, to report the errors against
+///
+/// # Examples
+///
+/// ```
+/// let (sources, ast) = semic::load("foo.semic", "int main () { return 0; }");
+/// assert!(ast.is_ok());
+/// assert_eq!("foo.semic", sources.file(0).unwrap().name);
+/// ```
+///
+/// ```
+/// let (_, ast) = semic::load("foo.semic", "#include \"missing.semic\"\nint main () { return 0; }");
+/// assert!(ast.is_err());
+/// ```
+pub fn load(filename: &str, program: &str) -> (SourceMap, Result<CProg, CError>) {
+    include::load(filename, program, |path| {
+        let mut src = String::new();
+        File::open(path).and_then(|mut f| f.read_to_string(&mut src)).map(|_| src)
+    })
+}
+
+/// Format program canonically, keeping single blank lines. Fails when it
+/// does not parse, uses directives other than `#include`, whose macros
+/// would be lost, or when the result would not parse back to the same
+/// program
+///
+/// # Examples
+///
+/// ```
+/// let program = "int main(){int i=0;for(;i<3;i++)printf(\"%d\", i);return i;}";
+/// let formatted = semic::format(program).unwrap();
+/// assert_eq!("int main(void) {\n    int i = 0;\n    for (; i < 3; i++) {\n        printf(\"%d\", i);\n    }\n    return i;\n}\n", formatted);
+/// ```
+pub fn format(program: &str) -> Result<String, CError> {
+    // the formatter prints what the parser sees, which has no macros left
+    let mut offset = 0;
+    for line in program.split('\n') {
+        let directive = line.trim_start();
+        if directive.starts_with('#') && !directive.starts_with("#include") {
+            let l = offset + line.len() - directive.len();
+            return Err(CError::ParseError(
+                format!("Cannot format programs using the preprocessor"), (l, offset + line.trim_end().len())));
         }
+        offset += line.len() + 1;
+    }
+
+    let ast = parse_prog(program)?;
+    let formatted = pretty::format_prog(&ast, Some(program));
+
+    let same = match parse_prog(&formatted) {
+        Ok(ast2) => format!("{:?}", ast) == format!("{:?}", ast2),
+        Err(_) => false,
+    };
+    match same {
+        true => Ok(formatted),
+        false => Err(CError::UnknownError("formatting would change the program".to_owned())),
     }
 }
 
-/// Parse program
+fn lint_diagnostics<'input>(
+    ast: &'input CProg,
+    error_printer: &error::ErrorPrinter,
+    context: &engine::Context,
+) -> Vec<error::Diagnostic>
+{
+    let mut diags = vec![];
+
+    for warning in lint::lint_prog(ast) {
+        let level = match warning.lint() {
+            Some(lint) => context.lint_level(lint),
+            None => lint::Level::Warn,
+        };
+        if level == lint::Level::Allow {
+            continue;
+        }
+
+        for mut d in error_printer.diagnostics(warning) {
+            if context.warnings_as_errors {
+                d.severity = error::Severity::Error;
+            }
+            diags.push(d);
+        }
+    }
+
+    diags
+}
+
+/// Parse program, which has to be preprocessed already, as by `load`
 ///
 /// # Examples
 ///
@@ -68,34 +222,114 @@ pub fn run(
 /// ```
 /// assert!(semic::parse_prog(r#"main () {}"#).is_err());
 /// ```
-pub fn parse_prog<'input, 'err,>(input: &'input str) -> Result<CProg<'input>, CError> {
-    match parser::parse_Prog(&mut vec![], input) {
-        Ok(x) => Ok(x),
-        Err(err) => Err(CError::from_lalrpop(err)),
+pub fn parse_prog(input: &str) -> Result<CProg, CError> {
+    let mut errors = vec![];
+    let res = match parser::parse_Prog(&mut errors, input) {
+        Ok(prog) => match unparsed(&prog, input) {
+            Some(token) => Err(ParseError::ExtraToken { token: token }),
+            None => Ok(prog),
+        },
+        Err(err) => Err(err),
+    };
+    recovered(input, res, errors)
+}
+
+/// First token after the end of the last element of the program, if any.
+/// The parser drops its error when recovery runs into the end of the input,
+/// leaving the rest of the input silently unparsed
+fn unparsed<'input>(prog: &CProg, input: &'input str) -> Option<(usize, (usize, &'input str), usize)> {
+    let end = match prog.last() {
+        Some(&CProgElem::Error) => return None,
+        Some(&CProgElem::Decl((_, r), ..)) |
+        Some(&CProgElem::Extern((_, r), ..)) |
+        Some(&CProgElem::Proto((_, r), ..)) |
+        Some(&CProgElem::Func((_, r), ..)) |
+        Some(&CProgElem::Include((_, r), _)) => r,
+        None => 0,
+    };
+
+    let rest = input[end..].trim_start();
+    if rest.is_empty() {
+        return None;
     }
+
+    let l = input.len() - rest.len();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let len = match rest.chars().next() {
+        Some(c) if is_word(c) => rest.chars().take_while(|&c| is_word(c)).map(|c| c.len_utf8()).sum(),
+        Some(c) => c.len_utf8(),
+        None => 0,
+    };
+    Some((l, (0, &rest[..len]), l + len))
 }
 
-pub fn parse_func<'input, 'err,>(input: &'input str,) -> Result<CFunc<'input>, CError> {
-    match parser::parse_Func(&mut vec![], input) {
+pub fn parse_func(input: &str) -> Result<CFunc, CError> {
+    let mut errors = vec![];
+    let res = parser::parse_Func(&mut errors, input);
+    match recovered(input, res, errors) {
         Ok(ref x) => match x.first().unwrap() {
             &CProgElem::Func(_, ref f) => Ok(f.clone()),
             x => Err(CError::UnknownError(format!("unexpected prog elem '{:?}'", x))),
         },
-        Err(err) => Err(CError::from_lalrpop(err)),
+        Err(err) => Err(err),
     }
 }
 
-pub fn parse_stmt<'input, 'err,>(input: &'input str,) -> Result<CStmt<'input>, CError> {
-    match parser::parse_Stmt(&mut vec![], input) {
-        Ok(x) => Ok(x),
-        Err(err) => Err(CError::from_lalrpop(err)),
-    }
+pub fn parse_stmt(input: &str) -> Result<CStmt, CError> {
+    let mut errors = vec![];
+    let res = parser::parse_Stmt(&mut errors, input);
+    recovered(input, res, errors)
 }
 
-pub fn parse_expr<'input, 'err,>(input: &'input str,) -> Result<CExpr<'input>, CError> {
-    match parser::parse_Expr(&mut vec![], input) {
-        Ok(x) => Ok(x),
-        Err(err) => Err(CError::from_lalrpop(err)),
+pub fn parse_expr(input: &str) -> Result<CExpr, CError> {
+    let mut errors = vec![];
+    let res = parser::parse_Expr(&mut errors, input);
+    recovered(input, res, errors)
+}
+
+/// Combine errors the parser recovered from with its final result, so every
+/// syntax error is reported at once. Only the first error on a line is kept,
+/// as the rest tend to follow from it
+fn recovered<'input, T>(
+    input: &'input str,
+    res: Result<T, ParseError<usize, (usize, &'input str), ()>>,
+    errors: Vec<ErrorRecovery<usize, (usize, &'input str), ()>>,
+) -> Result<T, CError>
+{
+    let mut errs = errors.into_iter().map(|e| e.error).collect::<Vec<_>>();
+    let res = match res {
+        Ok(x) => Some(x),
+        Err(err) => {
+            errs.push(err);
+            None
+        },
+    };
+
+    // errors are pushed as rules reduce, which is not source order
+    fn loc_of(err: &CError) -> Option<usize> {
+        match *err {
+            CError::ParseError(_, (l, _)) => Some(l),
+            CError::WithHint(ref err, _) => loc_of(err),
+            _ => None,
+        }
+    }
+    let mut errs: Vec<CError> = errs.into_iter().map(|err| CError::from_lalrpop(err, input)).collect();
+    errs.sort_by_key(|err| loc_of(err).unwrap_or(input.len()));
+
+    let mut es: Vec<CError> = vec![];
+    let mut prev = None;
+    for err in errs {
+        let loc = loc_of(&err);
+        match (prev, loc) {
+            (Some(p), Some(l)) if p <= l && !input[p..l].contains('\n') => continue,
+            _ => prev = loc,
+        }
+        es.push(err);
+    }
+
+    match res {
+        Some(x) if es.is_empty() => Ok(x),
+        _ => Err(CError::from_errors(es)),
     }
 }
 
@@ -112,7 +346,7 @@ pub fn parse_expr<'input, 'err,>(input: &'input str,) -> Result<CExpr<'input>, C
 /// let ast = semic::parse_prog(r#"int x, x;"#).unwrap();
 /// assert!(semic::check_prog(&ast).is_err()); // main missing
 /// ```
-pub fn check_prog<'input, 'err>(
+pub fn check_prog<'input>(
     ast: &'input CProg
 ) -> Result<(env::FuncTab<'input>, env::SymTab<'input>), CError>
 {
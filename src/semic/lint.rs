@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::*;
+use error::CError;
+
+/// Warnings about valid but suspicious code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lint {
+    Unused,
+    Unreachable,
+    MissingReturn,
+    ShadowGlobal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+}
+
+impl Lint {
+    pub fn all() -> Vec<Lint> {
+        vec![Lint::Unused, Lint::Unreachable, Lint::MissingReturn, Lint::ShadowGlobal]
+    }
+
+    /// Name used on the command line, e.g. '-A unused'
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Lint::Unused => "unused",
+            Lint::Unreachable => "unreachable",
+            Lint::MissingReturn => "missing-return",
+            Lint::ShadowGlobal => "shadow-global",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::all().into_iter().find(|l| l.name() == name)
+    }
+
+    /// Shadowing globals is how scoping is meant to work, so it is only
+    /// reported when asked for
+    pub fn default_level(&self) -> Level {
+        match *self {
+            Lint::ShadowGlobal => Level::Allow,
+            _ => Level::Warn,
+        }
+    }
+}
+
+// lint functions
+
+pub fn lint_prog<'input>(ast: &'input CProg) -> Vec<CError> {
+    let mut warnings = vec![];
+
+    let globals: HashMap<&str, CLoc> = ast.iter().filter_map(|elem| match *elem {
+        CProgElem::Decl(loc, _, ref name, _) => Some((name.as_str(), loc)),
+        _ => None,
+    }).collect();
+
+    for elem in ast.iter() {
+        if let CProgElem::Func(loc, ref func) = *elem {
+            lint_func(func, loc, &globals, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+fn lint_func<'input>(
+    func: &'input CFunc,
+    loc: CLoc,
+    globals: &HashMap<&str, CLoc>,
+    warnings: &mut Vec<CError>,
+)
+{
+    let CFunc { ref proto, ref body } = *func;
+
+    let mut decls = vec![];
+    let mut reads = HashSet::new();
+    collect_stmt(body, &mut decls, &mut reads);
+
+    // unused, where main has to take its params even if it does not use them
+    if proto.name != "main" {
+        for &(_, ref name) in proto.params.iter() {
+            if !reads.contains(name.as_str()) {
+                let msg = format!("Unused parameter '{}' of '{}'", name, proto.name);
+                warnings.push(CError::Warning(Lint::Unused, msg, loc));
+            }
+        }
+    }
+    for &(name, l) in decls.iter() {
+        if !reads.contains(name) {
+            let msg = format!("Unused variable '{}'", name);
+            warnings.push(CError::Warning(Lint::Unused, msg, l));
+        }
+    }
+
+    // shadowed globals
+    let locals = proto.params.iter().map(|&(_, ref name)| ("Parameter", name.as_str(), loc))
+        .chain(decls.iter().map(|&(name, l)| ("Variable", name, l)));
+    for (what, name, l) in locals {
+        if let Some(&g) = globals.get(name) {
+            let msg = format!("{} '{}' shadows a global variable", what, name);
+            warnings.push(CError::Warning(Lint::ShadowGlobal, msg, l)
+                          .with_note(format!("global '{}' declared here", name), g));
+        }
+    }
+
+    lint_stmt(body, warnings);
+
+    // missing return, reported at the closing brace. Like in C, main may
+    // end without one
+    if let (&Some(_), &CStmt::Block((_, r), _)) = (&proto.ret, body) {
+        if proto.name != "main" && !returns(body) {
+            let msg = format!("Function '{}' can reach its end without returning a value", proto.name);
+            warnings.push(CError::Warning(Lint::MissingReturn, msg, (r - 1, r)));
+        }
+    }
+}
+
+/// Collect local declarations and every variable read
+fn collect_stmt<'input>(
+    stmt: &'input CStmt,
+    decls: &mut Vec<(&'input str, CLoc)>,
+    reads: &mut HashSet<&'input str>,
+)
+{
+    match *stmt {
+        CStmt::Decl(loc, _, ref name, ref so) => {
+            decls.push((name, loc));
+            if let Some(ref e) = *so { collect_expr(e, reads); }
+        },
+        CStmt::Assign(_, _, ref io, ref e) => {
+            if let Some(ref i) = *io { collect_expr(i, reads); }
+            collect_expr(e, reads);
+        },
+        CStmt::Call(_, _, ref args) => {
+            for e in args.iter() { collect_expr(e, reads); }
+        },
+        CStmt::Return(_, Some(ref e)) |
+        CStmt::Case(_, Some(ref e)) |
+        CStmt::Print(_, _, ref e) => collect_expr(e, reads),
+        CStmt::Block(_, ref stmts) |
+        CStmt::Decls(_, ref stmts) => {
+            for s in stmts.iter() { collect_stmt(s, decls, reads); }
+        },
+        CStmt::If(_, ref cond, ref s1, ref s2) => {
+            collect_expr(cond, reads);
+            collect_stmt(s1, decls, reads);
+            if let Some(ref s2) = *s2 { collect_stmt(s2, decls, reads); }
+        },
+        CStmt::While(_, ref cond, ref s) |
+        CStmt::DoWhile(_, ref s, ref cond) => {
+            collect_expr(cond, reads);
+            collect_stmt(s, decls, reads);
+        },
+        CStmt::Switch(_, ref e, ref stmts) => {
+            collect_expr(e, reads);
+            for s in stmts.iter() { collect_stmt(s, decls, reads); }
+        },
+        CStmt::Return(_, None) |
+        CStmt::Case(_, None) |
+        CStmt::Break(_) |
+        CStmt::Error => (),
+    }
+}
+
+fn collect_expr<'input>(expr: &'input CExpr, reads: &mut HashSet<&'input str>) {
+    match *expr {
+        CExpr::Ident(_, ref name) => { reads.insert(name); },
+        CExpr::Index(_, ref name, ref e) => {
+            reads.insert(name);
+            collect_expr(e, reads);
+        },
+        CExpr::Call(_, _, ref args) => {
+            for e in args.iter() { collect_expr(e, reads); }
+        },
+        CExpr::UnOp(_, _, ref e) |
+        CExpr::Cast(_, _, ref e) => collect_expr(e, reads),
+        CExpr::BinOp(_, _, ref e1, ref e2) |
+        CExpr::Comma(_, ref e1, ref e2) => {
+            collect_expr(e1, reads);
+            collect_expr(e2, reads);
+        },
+        CExpr::Ternary(_, ref c, ref e1, ref e2) => {
+            collect_expr(c, reads);
+            collect_expr(e1, reads);
+            collect_expr(e2, reads);
+        },
+        CExpr::Int(..) |
+        CExpr::Float(..) |
+        CExpr::Str(..) |
+        CExpr::Char(..) |
+        CExpr::Error => (),
+    }
+}
+
+/// Report unreachable code in nested statement lists
+fn lint_stmt<'input>(stmt: &'input CStmt, warnings: &mut Vec<CError>) {
+    match *stmt {
+        CStmt::Block(_, ref stmts) => lint_stmts(stmts, false, warnings),
+        CStmt::Switch(_, _, ref stmts) => lint_stmts(stmts, true, warnings),
+        CStmt::If(_, _, ref s1, ref s2) => {
+            lint_stmt(s1, warnings);
+            if let Some(ref s2) = *s2 { lint_stmt(s2, warnings); }
+        },
+        CStmt::While(_, _, ref s) |
+        CStmt::DoWhile(_, ref s, _) => lint_stmt(s, warnings),
+        _ => (),
+    }
+}
+
+fn lint_stmts<'input>(stmts: &'input [Box<CStmt>], in_switch: bool, warnings: &mut Vec<CError>) {
+    // statement that ended the flow, and whether that has been reported
+    let mut ended: Option<(&str, CLoc)> = None;
+    let mut reported = false;
+
+    for stmt in stmts.iter() {
+        // a case label can be jumped to
+        if let CStmt::Case(..) = **stmt {
+            if in_switch {
+                ended = None;
+                reported = false;
+                continue;
+            }
+        }
+
+        match (ended, stmt_loc(stmt)) {
+            // the increment of a 'for' loop is placed after its body,
+            // so only code written after the end counts
+            (Some((what, end)), Some(loc)) if loc.0 > end.0 => {
+                if !reported {
+                    let msg = format!("Unreachable code after '{}'", what);
+                    warnings.push(CError::Warning(Lint::Unreachable, msg, loc));
+                    reported = true;
+                }
+            },
+            (Some(_), _) => (),
+            (None, _) => {
+                lint_stmt(stmt, warnings);
+                ended = ends(stmt);
+            },
+        };
+    }
+}
+
+/// How a statement always leaves the enclosing list, if it does
+fn ends<'input>(stmt: &'input CStmt) -> Option<(&'static str, CLoc)> {
+    match *stmt {
+        CStmt::Return(loc, _) => Some(("return", loc)),
+        CStmt::Break(loc) => Some(("break", loc)),
+        CStmt::Block(_, ref stmts) => stmts.iter().filter_map(|s| ends(s)).next(),
+        CStmt::If(_, _, ref s1, Some(ref s2)) => ends(s1).and(ends(s2)),
+        _ => None,
+    }
+}
+
+/// Whether a statement returns on every path
+fn returns<'input>(stmt: &'input CStmt) -> bool {
+    match *stmt {
+        CStmt::Return(..) => true,
+        CStmt::Block(_, ref stmts) => stmts.iter().any(|s| returns(s)),
+        CStmt::If(_, _, ref s1, Some(ref s2)) => returns(s1) && returns(s2),
+        CStmt::While(_, ref cond, ref s) => always_true(cond) && !breaks(s),
+        CStmt::DoWhile(_, ref s, ref cond) => !breaks(s) && (returns(s) || always_true(cond)),
+        CStmt::Switch(_, _, ref stmts) => {
+            let has_default = stmts.iter().any(|s| match **s {
+                CStmt::Case(_, None) => true,
+                _ => false,
+            });
+            // every case falls through to the code after the last label
+            let last = stmts.iter().rposition(|s| match **s {
+                CStmt::Case(..) => true,
+                _ => false,
+            }).unwrap_or(0);
+            has_default
+                && !stmts.iter().any(|s| breaks(s))
+                && stmts[last..].iter().any(|s| returns(s))
+        },
+        _ => false,
+    }
+}
+
+/// Whether a statement may break out of the enclosing loop or switch
+fn breaks<'input>(stmt: &'input CStmt) -> bool {
+    match *stmt {
+        CStmt::Break(_) => true,
+        CStmt::Block(_, ref stmts) => stmts.iter().any(|s| breaks(s)),
+        CStmt::If(_, _, ref s1, ref s2) => breaks(s1) || s2.as_ref().map_or(false, |s| breaks(s)),
+        _ => false,
+    }
+}
+
+fn always_true<'input>(expr: &'input CExpr) -> bool {
+    match *expr {
+        CExpr::Int(_, n) => n != 0,
+        _ => false,
+    }
+}
+
+fn stmt_loc<'input>(stmt: &'input CStmt) -> Option<CLoc> {
+    match *stmt {
+        CStmt::Decl(loc, ..) |
+        CStmt::Assign(loc, ..) |
+        CStmt::Call(loc, ..) |
+        CStmt::Return(loc, ..) |
+        CStmt::Block(loc, ..) |
+        CStmt::Decls(loc, ..) |
+        CStmt::If(loc, ..) |
+        CStmt::While(loc, ..) |
+        CStmt::DoWhile(loc, ..) |
+        CStmt::Switch(loc, ..) |
+        CStmt::Case(loc, ..) |
+        CStmt::Break(loc) |
+        CStmt::Print(loc, ..) => Some(loc),
+        CStmt::Error => None,
+    }
+}
@@ -0,0 +1,540 @@
+//! Language server, speaking the Language Server Protocol over a pair of
+//! streams such as stdin and stdout. It keeps the text of the documents
+//! open in the editor, publishes their diagnostics as they change, and
+//! answers go-to-definition, hover and document symbol requests from the
+//! tables of the checker.
+//!
+//! Documents are loaded like programs run from the command line, so files
+//! they include are read from disk unless they are open too.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+
+use ast::*;
+use checker;
+use error::{CError, Diagnostic, ErrorPrinter, Severity, Span};
+use include;
+use json::Json;
+use pretty;
+use resolver::{self, Scope};
+use source::{SourceFile, SourceMap};
+
+/// Serve the messages read from `input` until it ends or the client asks
+/// the server to exit, returning whether it was shut down first
+pub fn serve<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<bool> {
+    let mut server = Server::new();
+    while let Some(msg) = read_message(&mut input)? {
+        let replies = match Json::parse(&msg) {
+            Ok(msg) => server.handle(&msg),
+            Err(err) => vec![error_reply(Json::Null, -32700, &err)],
+        };
+        for reply in replies.iter() {
+            write_message(&mut output, reply)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+/// Read the body of the next message, if any
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        // headers end at an empty line
+        let line = line.trim_end();
+        if line.is_empty() && len.is_some() {
+            break;
+        }
+        let mut parts = line.splitn(2, ':');
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; len.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message<W: Write>(output: &mut W, msg: &Json) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// State of the server between messages
+pub struct Server {
+    /// Text of the open documents by URI
+    docs: HashMap<String, String>,
+    /// URIs each document has published diagnostics for, to clear them
+    /// once they are gone
+    published: HashMap<String, Vec<String>>,
+    shut_down: bool,
+    exited: bool,
+}
+
+/// Program of a document, with the files it includes, if it parses
+struct Analysis {
+    uri: String,
+    sources: SourceMap,
+    ast: Option<CProg>,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server { docs: HashMap::new(), published: HashMap::new(), shut_down: false, exited: false }
+    }
+
+    /// Handle a message, giving the messages to send back
+    pub fn handle(&mut self, msg: &Json) -> Vec<Json> {
+        let method = msg.get("method").and_then(Json::as_str).unwrap_or("");
+        let null = Json::Null;
+        let params = msg.get("params").unwrap_or(&null);
+
+        let id = match msg.get("id") {
+            // replies to requests of ours, which there are none of
+            Some(_) if method.is_empty() => return vec![],
+            Some(id) => id.clone(),
+            None => return self.notify(method, params),
+        };
+
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Json::Null)
+            },
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/documentSymbol" => Ok(self.symbols(params)),
+            _ => Err(format!("Unknown method '{}'", method)),
+        };
+        match result {
+            Ok(result) => vec![Json::object(vec![
+                ("jsonrpc", Json::str("2.0")),
+                ("id", id),
+                ("result", result),
+            ])],
+            Err(msg) => vec![error_reply(id, -32601, &msg)],
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let doc = params.get("textDocument");
+        let uri = doc.and_then(|d| d.get("uri")).and_then(Json::as_str).unwrap_or("").to_owned();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = doc.and_then(|d| d.get("text")).and_then(Json::as_str).unwrap_or("");
+                self.docs.insert(uri.clone(), text.to_owned());
+                self.publish(&uri)
+            },
+            // the whole text is sent on each change
+            "textDocument/didChange" => {
+                let changes = match params.get("contentChanges") {
+                    Some(&Json::Array(ref changes)) => changes,
+                    _ => return vec![],
+                };
+                if let Some(text) = changes.last().and_then(|c| c.get("text")).and_then(Json::as_str) {
+                    self.docs.insert(uri.clone(), text.to_owned());
+                }
+                self.publish(&uri)
+            },
+            "textDocument/didClose" => {
+                self.docs.remove(&uri);
+                let uris = self.published.remove(&uri).unwrap_or_default();
+                uris.iter().map(|u| diagnostics(u, vec![])).collect()
+            },
+            "exit" => {
+                self.exited = true;
+                vec![]
+            },
+            _ => vec![],
+        }
+    }
+
+    fn load(&self, uri: &str) -> Option<(SourceMap, Result<CProg, CError>)> {
+        let text = self.docs.get(uri)?;
+        let docs = &self.docs;
+        Some(include::load(&uri_path(uri), text, |path| {
+            match docs.get(&path_uri(path)) {
+                Some(text) => Ok(text.clone()),
+                None => {
+                    let mut src = String::new();
+                    File::open(path).and_then(|mut f| f.read_to_string(&mut src)).map(|_| src)
+                },
+            }
+        }))
+    }
+
+    fn analyze(&self, uri: &str) -> Option<Analysis> {
+        let (sources, prog) = self.load(uri)?;
+        Some(Analysis { uri: uri.to_owned(), sources: sources, ast: prog.ok() })
+    }
+
+    /// Diagnostics of a document from parsing and checking it, for it and
+    /// for the files it includes
+    fn publish(&mut self, uri: &str) -> Vec<Json> {
+        let (sources, prog) = match self.load(uri) {
+            Some(loaded) => loaded,
+            None => return vec![],
+        };
+        let printer = ErrorPrinter::from_map(sources.clone());
+        let diags = match prog {
+            Ok(ast) => match checker::analyze_prog(&ast) {
+                Ok(_) => vec![],
+                Err(err) => printer.diagnostics(err),
+            },
+            Err(err) => printer.diagnostics(err),
+        };
+        let analysis = Analysis { uri: uri.to_owned(), sources: sources, ast: None };
+
+        // the document always gets its list, to clear what it had before
+        let mut by_uri: Vec<(String, Vec<Json>)> = vec![(uri.to_owned(), vec![])];
+        for d in diags.iter() {
+            let file_uri = analysis.file_uri(analysis.sources.find(&d.file).unwrap_or(0));
+            let json = analysis.diagnostic(d);
+            match by_uri.iter().position(|&(ref u, _)| *u == file_uri) {
+                Some(i) => by_uri[i].1.push(json),
+                None => by_uri.push((file_uri, vec![json])),
+            }
+        }
+
+        let uris: Vec<String> = by_uri.iter().map(|&(ref u, _)| u.clone()).collect();
+        let gone: Vec<String> = match self.published.insert(uri.to_owned(), uris.clone()) {
+            Some(old) => old.into_iter().filter(|u| !uris.contains(u)).collect(),
+            None => vec![],
+        };
+
+        let mut msgs: Vec<Json> = by_uri.into_iter().map(|(u, ds)| diagnostics(&u, ds)).collect();
+        msgs.extend(gone.iter().map(|u| diagnostics(u, vec![])));
+        msgs
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let analysis = match self.analyze(doc_uri(params)) {
+            Some(analysis) => analysis,
+            None => return Json::Null,
+        };
+        match analysis.lookup(params) {
+            Some((_, _, Some(decl))) => analysis.location(decl),
+            _ => Json::Null,
+        }
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let analysis = match self.analyze(doc_uri(params)) {
+            Some(analysis) => analysis,
+            None => return Json::Null,
+        };
+        match analysis.lookup(params) {
+            Some((loc, decl, _)) => Json::object(vec![
+                ("contents", Json::object(vec![
+                    ("kind", Json::str("markdown")),
+                    ("value", Json::Str(format!("```c\n{}\n```", decl))),
+                ])),
+                ("range", analysis.range(loc)),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    /// Functions and globals defined in a document
+    fn symbols(&self, params: &Json) -> Json {
+        let analysis = match self.analyze(doc_uri(params)) {
+            Some(analysis) => analysis,
+            None => return Json::Null,
+        };
+        let ast = match analysis.ast {
+            Some(ref ast) => ast,
+            None => return Json::Null,
+        };
+
+        let mut symbols = vec![];
+        for elem in ast.iter() {
+            let (loc, name, detail, kind) = match *elem {
+                CProgElem::Func(loc, ref func) => (loc, &func.proto.name, pretty::format_proto(&func.proto), 12),
+                CProgElem::Decl(loc, ref t, ref name, ref size) => {
+                    let t = match *size {
+                        Some(_) => CType::Ref(Box::new(t.clone())),
+                        None => t.clone(),
+                    };
+                    (loc, name, pretty::format_var(&t, name), 13)
+                },
+                _ => continue,
+            };
+            // not those of included files
+            if analysis.sources.locate(loc.0).map(|(id, _)| id) != Some(0) {
+                continue;
+            }
+            symbols.push(Json::object(vec![
+                ("name", Json::str(name)),
+                ("detail", Json::Str(detail)),
+                ("kind", Json::Num(kind as f64)),
+                ("range", analysis.range(loc)),
+                ("selectionRange", analysis.range(analysis.name_loc(loc, name))),
+            ]));
+        }
+        Json::Array(symbols)
+    }
+}
+
+impl Analysis {
+    fn file_uri(&self, id: usize) -> String {
+        match id {
+            0 => self.uri.clone(),
+            id => path_uri(&self.sources.file(id).unwrap().name),
+        }
+    }
+
+    /// Name at the position of a request, its declaration as written in C,
+    /// and where it is declared, if anywhere
+    fn lookup(&self, params: &Json) -> Option<(CLoc, String, Option<CLoc>)> {
+        let ast = self.ast.as_ref()?;
+        let file = self.sources.file(0)?;
+        let offset = offset(file, params.get("position")?)?;
+
+        // the word around the position
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let l = offset - file.src[..offset].chars().rev().take_while(|&c| is_word(c)).map(char::len_utf8).sum::<usize>();
+        let r = offset + file.src[offset..].chars().take_while(|&c| is_word(c)).map(char::len_utf8).sum::<usize>();
+        let name = &file.src[l..r];
+        if name.is_empty() || !name.starts_with(char::is_alphabetic) {
+            return None;
+        }
+
+        let (vtab, symtab, _) = checker::declare_prog(ast);
+        let resolution = resolver::resolve_prog(ast, &symtab);
+        match resolution.scope_at(name, l) {
+            Scope::Local(i) => {
+                let &(func, ref scopes) = resolution.funcs.iter().find(|&&((a, b), _)| a <= l && l < b)?;
+                let local = &scopes.locals[i];
+                // parameters are named first in the function
+                let decl = local.decl.unwrap_or(func);
+                Some(((l, r), pretty::format_var(&local.t, name), Some(self.name_loc(decl, name))))
+            },
+            Scope::Global(_) => {
+                let (t, _) = symtab.get_type(name)?;
+                let decl = symtab.get_decl(name);
+                Some(((l, r), pretty::format_var(&t, name), decl.map(|d| self.name_loc(d, name))))
+            },
+            Scope::Undeclared => {
+                let proto = vtab.get_proto(name)?;
+                // the definition if there is one, otherwise the prototype
+                let func = ast.iter().filter_map(|elem| match *elem {
+                    CProgElem::Func(loc, ref f) if f.proto.name == name => Some(loc),
+                    _ => None,
+                }).next();
+                let decl = func.or_else(|| ast.iter().filter_map(|elem| match *elem {
+                    CProgElem::Proto(loc, ref p) if p.name == name => Some(loc),
+                    _ => None,
+                }).next());
+                Some(((l, r), pretty::format_proto(proto), decl.map(|d| self.name_loc(d, name))))
+            },
+        }
+    }
+
+    /// Location of a name in a declaration, or of the whole declaration if
+    /// it cannot be found
+    fn name_loc(&self, decl: CLoc, name: &str) -> CLoc {
+        let (id, l) = match self.sources.locate(decl.0) {
+            Some(res) => res,
+            None => return decl,
+        };
+        let base = self.sources.base(id);
+        let src = &self.sources.file(id).unwrap().src;
+        let text = &src[l..(decl.1 - base).min(src.len())];
+
+        let is_word = |c: Option<char>| c.map_or(false, |c| c.is_alphanumeric() || c == '_');
+        for (i, _) in text.match_indices(name) {
+            let before = text[..i].chars().next_back();
+            let after = text[(i + name.len())..].chars().next();
+            if !is_word(before) && !is_word(after) {
+                return (decl.0 + i, decl.0 + i + name.len());
+            }
+        }
+        decl
+    }
+
+    fn range(&self, loc: CLoc) -> Json {
+        match self.sources.span(loc) {
+            Some((id, span)) => {
+                let file = self.sources.file(id).unwrap();
+                range(file, span.line, span.column, span.end_line, span.end_column)
+            },
+            None => range_at(0, 0),
+        }
+    }
+
+    fn location(&self, loc: CLoc) -> Json {
+        let id = self.sources.locate(loc.0).map_or(0, |(id, _)| id);
+        Json::object(vec![
+            ("uri", Json::Str(self.file_uri(id))),
+            ("range", self.range(loc)),
+        ])
+    }
+
+    fn diagnostic(&self, d: &Diagnostic) -> Json {
+        let range = |file: &str, span: &Option<Span>| match (self.sources.find(file).and_then(|id| self.sources.file(id)), span) {
+            (Some(f), &Some(ref s)) => range(f, s.line, s.column, s.end_line, s.end_column),
+            _ => range_at(0, 0),
+        };
+
+        let mut pairs = vec![
+            ("range", range(&d.file, &d.span)),
+            ("severity", Json::Num(match d.severity { Severity::Error => 1.0, Severity::Warning => 2.0 })),
+            ("source", Json::str("semic")),
+            ("message", Json::str(&d.message)),
+        ];
+        if let Some(ref code) = d.code {
+            pairs.push(("code", Json::str(code)));
+        }
+        if !d.notes.is_empty() {
+            let notes = d.notes.iter().map(|n| {
+                let id = self.sources.find(&n.file).unwrap_or(0);
+                Json::object(vec![
+                    ("location", Json::object(vec![
+                        ("uri", Json::Str(self.file_uri(id))),
+                        ("range", range(&n.file, &n.span)),
+                    ])),
+                    ("message", Json::str(&n.message)),
+                ])
+            }).collect();
+            pairs.push(("relatedInformation", Json::Array(notes)));
+        }
+        Json::object(pairs)
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        ("capabilities", Json::object(vec![
+            // the whole text on each change
+            ("textDocumentSync", Json::Num(1.0)),
+            ("definitionProvider", Json::Bool(true)),
+            ("hoverProvider", Json::Bool(true)),
+            ("documentSymbolProvider", Json::Bool(true)),
+        ])),
+        ("serverInfo", Json::object(vec![
+            ("name", Json::str("semic-lsp")),
+            ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+        ])),
+    ])
+}
+
+fn diagnostics(uri: &str, diags: Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/publishDiagnostics")),
+        ("params", Json::object(vec![
+            ("uri", Json::str(uri)),
+            ("diagnostics", Json::Array(diags)),
+        ])),
+    ])
+}
+
+fn error_reply(id: Json, code: i32, msg: &str) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::str("2.0")),
+        ("id", id),
+        ("error", Json::object(vec![
+            ("code", Json::Num(code as f64)),
+            ("message", Json::str(msg)),
+        ])),
+    ])
+}
+
+fn doc_uri(params: &Json) -> &str {
+    params.get("textDocument").and_then(|d| d.get("uri")).and_then(Json::as_str).unwrap_or("")
+}
+
+// positions, which count lines from 0 and characters in UTF-16 units
+
+fn position(file: &SourceFile, line: usize, column: usize) -> (usize, usize) {
+    let text = file.line_text(line).unwrap_or("");
+    let character = text.chars().take(column.saturating_sub(1)).map(char::len_utf16).sum();
+    (line.saturating_sub(1), character)
+}
+
+fn range(file: &SourceFile, line: usize, column: usize, end_line: usize, end_column: usize) -> Json {
+    let (l, c) = position(file, line, column);
+    let (end_l, end_c) = position(file, end_line, end_column);
+    let pos = |l: usize, c: usize| Json::object(vec![
+        ("line", Json::Num(l as f64)),
+        ("character", Json::Num(c as f64)),
+    ]);
+    Json::object(vec![("start", pos(l, c)), ("end", pos(end_l, end_c))])
+}
+
+fn range_at(line: usize, character: usize) -> Json {
+    let pos = Json::object(vec![
+        ("line", Json::Num(line as f64)),
+        ("character", Json::Num(character as f64)),
+    ]);
+    Json::object(vec![("start", pos.clone()), ("end", pos)])
+}
+
+/// Offset in a file of a position
+fn offset(file: &SourceFile, pos: &Json) -> Option<usize> {
+    let line = pos.get("line")?.as_num()? as usize + 1;
+    let character = pos.get("character")?.as_num()? as usize;
+    let text = file.line_text(line)?;
+
+    let mut units = 0;
+    let column = 1 + text.chars().take_while(|c| {
+        units += c.len_utf16();
+        units <= character
+    }).count();
+    file.loc(line, column)
+}
+
+// URIs of files
+
+fn uri_path(uri: &str) -> String {
+    if !uri.starts_with("file://") {
+        return uri.to_owned();
+    }
+
+    let bytes = uri["file://".len()..].as_bytes();
+    let mut path = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes[i] {
+            b'%' => ::std::str::from_utf8(&bytes[(i + 1)..(i + 3).min(bytes.len())]).ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(b) => {
+                path.push(b);
+                i += 3;
+            },
+            None => {
+                path.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&path).into_owned()
+}
+
+fn path_uri(path: &str) -> String {
+    let mut uri = "file://".to_owned();
+    for &b in path.as_bytes() {
+        match b {
+            b if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) => uri.push(b as char),
+            b => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
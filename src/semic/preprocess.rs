@@ -0,0 +1,453 @@
+//! Preprocessing of a file ahead of parsing: `#define` of constants and of
+//! macros taking arguments, `#undef`, and `#ifdef`, `#ifndef`, `#else` and
+//! `#endif`. `#include` lines are left for the parser, and are reported as
+//! they are reached, so the macros a header defines are known in the rest
+//! of the file including it.
+//!
+//! The text given to the parser differs from the source where directives
+//! are dropped and macros expanded, so it keeps where each of its pieces
+//! came from, for moving the locations of the parsed program back.
+//!
+//! # Examples
+//!
+//! ```
+//! use semic::preprocess::{preprocess, Macros};
+//!
+//! let src = "#define N 10\n#define MAX(a, b) (a > b ? a : b)\nint x = MAX(N, 3);\n";
+//! let out = preprocess(src, &mut Macros::new(), |_, _, _| ()).unwrap();
+//! assert_eq!("int x =  ( 10  > 3 ?  10  : 3) ;\n", out.text);
+//! // locations in an expansion give the whole use of the macro
+//! assert_eq!((55, 64), out.loc((8, 30)));
+//! ```
+
+use std::collections::HashMap;
+
+use ast::CLoc;
+use error::CError;
+
+/// Macros by name
+pub type Macros = HashMap<String, Macro>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+    /// Parameters of a macro taking arguments, `None` for a constant
+    pub params: Option<Vec<String>>,
+    pub body: String,
+}
+
+/// Text for the parser, and where it came from
+#[derive(Debug, Clone)]
+pub struct Expanded {
+    pub text: String,
+    pieces: Vec<Piece>,
+}
+
+/// Part of the text starting at an offset, copied from the source from a
+/// location on, or expanded from the use of a macro at a location
+#[derive(Debug, Clone)]
+enum Piece {
+    Copied(usize, usize),
+    Macro(usize, CLoc),
+}
+
+impl Piece {
+    fn start(&self) -> usize {
+        match *self {
+            Piece::Copied(out, _) | Piece::Macro(out, _) => out,
+        }
+    }
+}
+
+impl Expanded {
+    /// Location in the source of a location in the text. Locations within
+    /// an expansion give the whole use of the macro
+    pub fn loc(&self, loc: CLoc) -> CLoc {
+        let l = match self.piece(loc.0, true) {
+            Some(&Piece::Copied(out, orig)) => orig + (loc.0 - out),
+            Some(&Piece::Macro(_, (l, _))) => l,
+            None => loc.0,
+        };
+        // ends are exclusive, so one at the start of a piece belongs to the
+        // one before
+        let r = match self.piece(loc.1, false) {
+            Some(&Piece::Copied(out, orig)) => orig + (loc.1 - out),
+            Some(&Piece::Macro(_, (_, r))) => r,
+            None => loc.1,
+        };
+        (l, r.max(l))
+    }
+
+    fn piece(&self, offset: usize, start: bool) -> Option<&Piece> {
+        let n = self.pieces.iter().take_while(|p| match start {
+            true => p.start() <= offset,
+            false => p.start() < offset,
+        }).count();
+        match n {
+            0 => self.pieces.first(),
+            n => self.pieces.get(n - 1),
+        }
+    }
+}
+
+/// Preprocess a file with the macros defined so far, calling `include`
+/// with the path and location of each `#include` reached, and the macros
+/// for the included file to use
+pub fn preprocess<F>(src: &str, macros: &mut Macros, include: F) -> Result<Expanded, CError>
+    where F: FnMut(&str, CLoc, &mut Macros)
+{
+    let mut pp = Preprocessor {
+        src: src,
+        macros: macros,
+        include: include,
+        out: Expanded { text: String::new(), pieces: vec![] },
+        conds: vec![],
+        errors: vec![],
+    };
+
+    let mut text = 0;
+    let mut pos = 0;
+    while pos < src.len() {
+        let mut end = line_end(src, pos);
+        if !src[pos..end].trim_start().starts_with('#') {
+            pos = end;
+            continue;
+        }
+
+        // directives go on over lines ending in a backslash
+        while end < src.len() && src[pos..end].trim_end().ends_with('\\') {
+            end = line_end(src, end);
+        }
+        pp.text(text, pos);
+        pp.directive(pos, end);
+        text = end;
+        pos = end;
+    }
+    pp.text(text, src.len());
+
+    for cond in pp.conds.drain(..) {
+        pp.errors.push(CError::ParseError(format!("Unterminated '#{}'", cond.name), cond.loc));
+    }
+
+    match pp.errors.len() {
+        0 => Ok(pp.out),
+        _ => Err(CError::from_errors(pp.errors)),
+    }
+}
+
+/// Branch of an `#ifdef` or `#ifndef` being read
+struct Cond {
+    name: String,
+    loc: CLoc,
+    /// Whether the text around it is used
+    outer: bool,
+    taken: bool,
+}
+
+struct Preprocessor<'a, F> {
+    src: &'a str,
+    macros: &'a mut Macros,
+    include: F,
+    out: Expanded,
+    conds: Vec<Cond>,
+    errors: Vec<CError>,
+}
+
+impl<'a, F> Preprocessor<'a, F> where F: FnMut(&str, CLoc, &mut Macros) {
+    fn active(&self) -> bool {
+        self.conds.last().map_or(true, |c| c.outer && c.taken)
+    }
+
+    fn copy(&mut self, l: usize, r: usize) {
+        if l < r {
+            self.out.pieces.push(Piece::Copied(self.out.text.len(), l));
+            self.out.text.push_str(&self.src[l..r]);
+        }
+    }
+
+    /// Copy the text between directives, expanding the macros in it
+    fn text(&mut self, l: usize, r: usize) {
+        if !self.active() {
+            return;
+        }
+
+        let src = self.src;
+        let mut copied = l;
+        let mut i = l;
+        while i < r {
+            let (end, ident) = token(src, i, r);
+            if !ident || !self.macros.contains_key(&src[i..end]) {
+                i = end;
+                continue;
+            }
+
+            let name = &src[i..end];
+            let (args, end) = match self.macros[name].params {
+                None => (None, end),
+                Some(_) => match call_args(src, end, r) {
+                    Some((args, end)) => (Some(args), end),
+                    // without arguments, the name is left as it is
+                    None if !src[end..r].trim_start().starts_with('(') => {
+                        i = end;
+                        continue;
+                    },
+                    None => {
+                        self.errors.push(CError::ParseError(
+                            format!("Unterminated use of macro '{}'", name), (i, end)));
+                        return;
+                    },
+                },
+            };
+
+            match expand(self.macros, name, args, &mut vec![]) {
+                Ok(body) => {
+                    self.copy(copied, i);
+                    self.out.pieces.push(Piece::Macro(self.out.text.len(), (i, end)));
+                    // spaced apart from what is around it, so tokens do not run together
+                    self.out.text.push_str(&format!(" {} ", body));
+                },
+                Err(msg) => self.errors.push(CError::ParseError(msg, (i, end))),
+            }
+            copied = end;
+            i = end;
+        }
+        self.copy(copied, r);
+    }
+
+    fn directive(&mut self, l: usize, r: usize) {
+        let text = self.src[l..r].trim_end();
+        let loc = (l + (self.src[l..r].len() - self.src[l..r].trim_start().len()), l + text.len());
+
+        // continued lines are joined
+        let line = text.trim_start()[1..].replace("\\\n", " ").replace("\\\r\n", " ");
+        let line = line.trim_start();
+        let word_end = line.find(|c: char| !c.is_alphanumeric()).unwrap_or(line.len());
+        let (word, rest) = (&line[..word_end], line[word_end..].trim());
+
+        match word {
+            "ifdef" | "ifndef" => {
+                let outer = self.active();
+                let defined = self.macros.contains_key(rest);
+                self.conds.push(Cond {
+                    name: word.to_owned(),
+                    loc: loc,
+                    outer: outer,
+                    taken: defined == (word == "ifdef"),
+                });
+                if outer && ident(rest).map(|(_, rest)| rest) != Some("") {
+                    self.errors.push(CError::ParseError(format!("Expected a macro name after '#{}'", word), loc));
+                }
+            },
+            "else" => match self.conds.last_mut() {
+                Some(c) => c.taken = !c.taken,
+                None => self.errors.push(CError::ParseError(format!("'#else' without '#ifdef'"), loc)),
+            },
+            "endif" => if self.conds.pop().is_none() {
+                self.errors.push(CError::ParseError(format!("'#endif' without '#ifdef'"), loc));
+            },
+            _ if !self.active() => (),
+            "define" => match define(rest) {
+                Ok((name, m)) => match self.macros.get(&name) {
+                    Some(old) if *old != m =>
+                        self.errors.push(CError::ParseError(format!("Macro '{}' already defined", name), loc)),
+                    _ => { self.macros.insert(name, m); },
+                },
+                Err(msg) => self.errors.push(CError::ParseError(msg, loc)),
+            },
+            "undef" => match ident(rest) {
+                Some((name, "")) => { self.macros.remove(name); },
+                _ => self.errors.push(CError::ParseError(format!("Expected a macro name after '#undef'"), loc)),
+            },
+            "include" => {
+                // the parser reports includes not naming a file
+                if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+                    (self.include)(&rest[1..rest.len() - 1], loc, self.macros);
+                }
+                self.copy(l, r);
+            },
+            _ => self.errors.push(CError::ParseError(
+                format!("Unknown preprocessor directive '#{}'", word), loc)),
+        }
+    }
+}
+
+/// Definition of a macro, from what follows `#define`
+fn define(text: &str) -> Result<(String, Macro), String> {
+    let (name, rest) = match ident(text) {
+        Some(res) => res,
+        None => return Err(format!("Expected a macro name after '#define'")),
+    };
+
+    // parameters follow the name with no space between
+    if !rest.starts_with('(') {
+        return Ok((name.to_owned(), Macro { params: None, body: rest.trim().to_owned() }));
+    }
+    let close = match rest.find(')') {
+        Some(i) => i,
+        None => return Err(format!("Unterminated parameters of macro '{}'", name)),
+    };
+
+    let mut params = vec![];
+    let list = rest[1..close].trim();
+    if !list.is_empty() {
+        for p in list.split(',') {
+            match ident(p.trim()) {
+                Some((p, "")) if !params.contains(&p.to_owned()) => params.push(p.to_owned()),
+                _ => return Err(format!("Bad parameter '{}' of macro '{}'", p.trim(), name)),
+            }
+        }
+    }
+    Ok((name.to_owned(), Macro { params: Some(params), body: rest[close + 1..].trim().to_owned() }))
+}
+
+/// Body of a macro with the arguments put in for its parameters, and the
+/// macros in both expanded. Macros being expanded are `hidden`, so they
+/// are not expanded again within themselves
+fn expand(macros: &Macros, name: &str, args: Option<Vec<&str>>, hidden: &mut Vec<String>) -> Result<String, String> {
+    let m = &macros[name];
+    let body = match (&m.params, args) {
+        (&Some(ref params), Some(args)) => {
+            // `F()` has one empty argument, or none
+            let args = match args.len() == 1 && args[0].trim().is_empty() && params.is_empty() {
+                true => vec![],
+                false => args,
+            };
+            if args.len() != params.len() {
+                return Err(format!("Macro '{}' takes {} arguments, given {}", name, params.len(), args.len()));
+            }
+
+            let mut expanded = vec![];
+            for arg in args {
+                expanded.push(rescan(macros, arg.trim(), hidden)?);
+            }
+            substitute(&m.body, params, &expanded)
+        },
+        _ => m.body.clone(),
+    };
+
+    hidden.push(name.to_owned());
+    let res = rescan(macros, &body, hidden);
+    hidden.pop();
+    res
+}
+
+/// Text with the macros in it expanded
+fn rescan(macros: &Macros, text: &str, hidden: &mut Vec<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < text.len() {
+        let (end, is_ident) = token(text, i, text.len());
+        let name = &text[i..end];
+        if !is_ident || !macros.contains_key(name) || hidden.iter().any(|h| h == name) {
+            out.push_str(name);
+            i = end;
+            continue;
+        }
+
+        let (args, end) = match macros[name].params {
+            None => (None, end),
+            Some(_) => match call_args(text, end, text.len()) {
+                Some((args, end)) => (Some(args), end),
+                None => {
+                    out.push_str(name);
+                    i = end;
+                    continue;
+                },
+            },
+        };
+        out.push_str(&format!(" {} ", expand(macros, name, args, hidden)?));
+        i = end;
+    }
+    Ok(out)
+}
+
+/// Body with the parameters replaced by the arguments
+fn substitute(body: &str, params: &[String], args: &[String]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < body.len() {
+        let (end, is_ident) = token(body, i, body.len());
+        match params.iter().position(|p| is_ident && *p == body[i..end]) {
+            Some(n) => out.push_str(&args[n]),
+            None => out.push_str(&body[i..end]),
+        }
+        i = end;
+    }
+    out
+}
+
+/// Arguments of the use of a macro, if a parenthesis follows it before
+/// `r`, and the end of the use
+fn call_args(text: &str, l: usize, r: usize) -> Option<(Vec<&str>, usize)> {
+    let open = l + (text[l..r].len() - text[l..r].trim_start().len());
+    if !text[open..r].starts_with('(') {
+        return None;
+    }
+
+    let mut args = vec![];
+    let mut depth = 0;
+    let mut arg = open + 1;
+    let mut i = open;
+    while i < r {
+        let (end, _) = token(text, i, r);
+        match &text[i..end] {
+            "(" => depth += 1,
+            ")" if depth == 1 => {
+                args.push(&text[arg..i]);
+                return Some((args, end));
+            },
+            ")" => depth -= 1,
+            "," if depth == 1 => {
+                args.push(&text[arg..i]);
+                arg = end;
+            },
+            _ => (),
+        }
+        i = end;
+    }
+    None
+}
+
+/// End of the token at `i`, not going past `r`, and whether it is an
+/// identifier. Literals are single tokens, so nothing is expanded in them
+fn token(text: &str, i: usize, r: usize) -> (usize, bool) {
+    let rest = &text[i..r];
+    let c = rest.chars().next().unwrap();
+    let end = match c {
+        '"' | '\'' => {
+            let mut escaped = false;
+            let len = rest[1..].find(|d: char| {
+                let end = !escaped && (d == c || d == '\n');
+                escaped = !escaped && d == '\\';
+                end
+            });
+            match len {
+                Some(n) => 1 + n + 1,
+                None => rest.len(),
+            }
+        },
+        _ if c.is_alphanumeric() || c == '_' =>
+            rest.find(|d: char| !(d.is_alphanumeric() || d == '_' || (c.is_ascii_digit() && d == '.')))
+                .unwrap_or(rest.len()),
+        _ => c.len_utf8(),
+    };
+    (i + end, c.is_alphabetic() || c == '_')
+}
+
+/// Identifier at the start of the text, and the text after it
+fn ident(text: &str) -> Option<(&str, &str)> {
+    match text.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            let end = text.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(text.len());
+            Some((&text[..end], &text[end..]))
+        },
+        _ => None,
+    }
+}
+
+/// Offset after the end of the line at `i`
+fn line_end(src: &str, i: usize) -> usize {
+    match src[i..].find('\n') {
+        Some(n) => i + n + 1,
+        None => src.len(),
+    }
+}
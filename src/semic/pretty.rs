@@ -0,0 +1,535 @@
+use ast::*;
+
+const INDENT: &'static str = "    ";
+
+/// Render a program as canonically formatted source, which parses back to
+/// the same program. With the source it was parsed from, single blank
+/// lines between declarations and statements are kept.
+///
+/// The parser turns `for` loops, `i++` and initialized declarations into
+/// plainer statements, which are recognized by their shared locations and
+/// written the way they were.
+pub fn format_prog(ast: &CProg, source: Option<&str>) -> String {
+    let mut printer = Printer { source: source, out: String::new() };
+    printer.prog(ast);
+    printer.out
+}
+
+/// Render an expression, with only the parentheses it needs
+pub fn format_expr(expr: &CExpr) -> String {
+    expr_at(expr, 0)
+}
+
+/// Render the signature of a function, as declared without the `;`
+pub fn format_proto(proto: &CProto) -> String {
+    format!("{} {}{}({})", ret_base(&proto.ret), ret_stars(&proto.ret), proto.name, params_str(&proto.params))
+}
+
+/// Render the declaration of a variable without the `;`, where arrays
+/// show as the pointers they are typed as
+pub fn format_var(t: &CType, name: &str) -> String {
+    let (base, stars) = split_ref(t);
+    format!("{:?} {}{}", base, stars, name)
+}
+
+struct Printer<'s> {
+    source: Option<&'s str>,
+    out: String,
+}
+
+impl<'s> Printer<'s> {
+    fn line(&mut self, level: usize, s: &str) {
+        for _ in 0..level {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    /// Whether the source has a blank line between the locations
+    fn blank_between(&self, end: usize, start: usize) -> bool {
+        match self.source.and_then(|src| src.get(end..start)) {
+            Some(s) => {
+                let lines: Vec<&str> = s.split('\n').collect();
+                lines.len() > 2 && lines[1..(lines.len() - 1)].iter().any(|l| l.trim().is_empty())
+            },
+            None => false,
+        }
+    }
+
+    fn prog(&mut self, ast: &CProg) {
+        let mut prev: Option<(bool, usize)> = None;
+        let mut i = 0;
+        while i < ast.len() {
+            // names declared together share the location of the declaration
+            let (loc, n, is_func) = match ast[i] {
+                CProgElem::Decl(loc, ..) |
+                CProgElem::Extern(loc, ..) |
+                CProgElem::Proto(loc, _) => (loc, same_loc(&ast[i..], loc), false),
+                CProgElem::Func(loc, _) => (loc, 1, true),
+                CProgElem::Include(loc, _) => (loc, 1, false),
+                CProgElem::Error => {
+                    i += 1;
+                    continue;
+                },
+            };
+
+            if let Some((prev_func, end)) = prev {
+                if prev_func || is_func || self.blank_between(end, loc.0) {
+                    self.out.push('\n');
+                }
+            }
+
+            match ast[i] {
+                CProgElem::Decl(_, ref t, ..) => {
+                    let names: Vec<String> = ast[i..(i + n)].iter().map(|elem| match *elem {
+                        CProgElem::Decl(_, _, ref id, Some(ref e)) => format!("{}[{}]", id, expr_at(e, 1)),
+                        CProgElem::Decl(_, _, ref id, None) => id.to_owned(),
+                        _ => unreachable!(),
+                    }).collect();
+                    self.line(0, &format!("{} {};", type_str(t), names.join(", ")));
+                },
+                CProgElem::Extern(_, ref t, _) => {
+                    let base = match *t {
+                        CType::Ref(ref t) => &**t,
+                        ref t => t,
+                    };
+                    let names: Vec<String> = ast[i..(i + n)].iter().map(|elem| match *elem {
+                        CProgElem::Extern(_, CType::Ref(_), ref id) => format!("{}[]", id),
+                        CProgElem::Extern(_, _, ref id) => id.to_owned(),
+                        _ => unreachable!(),
+                    }).collect();
+                    self.line(0, &format!("extern {} {};", type_str(base), names.join(", ")));
+                },
+                CProgElem::Proto(_, ref proto) => {
+                    let funcs: Vec<String> = ast[i..(i + n)].iter().map(|elem| match *elem {
+                        CProgElem::Proto(_, ref p) => format!("{}{}({})", ret_stars(&p.ret), p.name, params_str(&p.params)),
+                        _ => unreachable!(),
+                    }).collect();
+                    self.line(0, &format!("{} {};", ret_base(&proto.ret), funcs.join(", ")));
+                },
+                CProgElem::Func(_, CFunc { ref proto, ref body }) => {
+                    self.line(0, &format!("{} {{", format_proto(proto)));
+                    match *body {
+                        CStmt::Block(_, ref ss) => self.stmts(ss, 1),
+                        ref s => self.stmt(s, 1),
+                    }
+                    self.line(0, "}");
+                },
+                CProgElem::Include(_, ref path) => self.line(0, &format!("#include \"{}\"", path)),
+                CProgElem::Error => (),
+            }
+
+            prev = Some((is_func, loc.1));
+            i += n;
+        }
+    }
+
+    /// Statements of a block
+    fn stmts(&mut self, ss: &[Box<CStmt>], level: usize) {
+        let mut prev_end = None;
+        for s in ss.iter() {
+            if let (Some(end), Some((start, _))) = (prev_end, stmt_loc(s)) {
+                if self.blank_between(end, start) {
+                    self.out.push('\n');
+                }
+            }
+            self.stmt(s, level);
+            prev_end = stmt_loc(s).map(|(_, r)| r);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &CStmt, level: usize) {
+        match *stmt {
+            CStmt::Decl(..) => self.line(level, &format!("{};", decl_group(&[stmt]))),
+            CStmt::Assign(..) => self.line(level, &format!("{};", assign_str(stmt))),
+            CStmt::Call(_, ref id, ref args) => self.line(level, &format!("{};", call_str(id, args))),
+            CStmt::Return(_, Some(ref e)) => self.line(level, &format!("return {};", expr_at(e, 1))),
+            CStmt::Return(_, None) => self.line(level, "return;"),
+            CStmt::Decls(_, ref ss) => {
+                let group: Vec<&CStmt> = ss.iter().map(|s| &**s).collect();
+                self.line(level, &format!("{};", decl_group(&group)));
+            },
+            CStmt::Block(loc, ref ss) => {
+                if let Some((inits, w)) = for_block(loc, ss) {
+                    self.for_loop(inits, w, level);
+                } else {
+                    self.line(level, "{");
+                    self.stmts(ss, level + 1);
+                    self.line(level, "}");
+                }
+            },
+            CStmt::If(..) => self.if_stmt("", stmt, level),
+            CStmt::While(loc, ref cond, ref s) => {
+                if is_for(loc, cond, s) {
+                    self.for_loop(&[], stmt, level);
+                } else if self.body(&format!("while ({})", expr_at(cond, 1)), s, level) {
+                    self.line(level, "}");
+                }
+            },
+            CStmt::DoWhile(_, ref s, ref cond) => {
+                let tail = format!("while ({});", expr_at(cond, 1));
+                match self.body("do", s, level) {
+                    true => self.line(level, &format!("}} {}", tail)),
+                    false => self.line(level, &tail),
+                }
+            },
+            CStmt::Switch(_, ref e, ref ss) => {
+                self.line(level, &format!("switch ({}) {{", expr_at(e, 1)));
+                self.stmts(ss, level + 1);
+                self.line(level, "}");
+            },
+            // labels line up with their switch
+            CStmt::Case(_, Some(ref e)) => self.line(level.saturating_sub(1), &format!("case {}:", expr_at(e, 1))),
+            CStmt::Case(_, None) => self.line(level.saturating_sub(1), "default:"),
+            CStmt::Break(_) => self.line(level, "break;"),
+            CStmt::Print(_, Some(ref fmt), ref e) => self.line(level, &format!("printf(\"{}\", {});", fmt.as_str(), expr_at(e, 1))),
+            CStmt::Print(_, None, ref e) => self.line(level, &format!("printf({});", expr_at(e, 1))),
+            CStmt::Error => (),
+        }
+    }
+
+    /// Statement under a header, opening a brace on the header line when
+    /// it is a block, which the caller closes
+    fn body(&mut self, head: &str, stmt: &CStmt, level: usize) -> bool {
+        match *stmt {
+            CStmt::Block(loc, ref ss) if for_block(loc, ss).is_none() => {
+                self.line(level, &format!("{} {{", head));
+                self.stmts(ss, level + 1);
+                true
+            },
+            _ => {
+                self.line(level, head);
+                self.stmt(stmt, level + 1);
+                false
+            },
+        }
+    }
+
+    fn if_stmt(&mut self, prefix: &str, stmt: &CStmt, level: usize) {
+        let (cond, then, other) = match *stmt {
+            CStmt::If(_, ref cond, ref then, ref other) => (cond, then, other),
+            _ => return self.stmt(stmt, level),
+        };
+        let head = format!("{}if ({})", prefix, expr_at(cond, 1));
+
+        // an `if` without `else` would take this one's `else`
+        let open = match (other.is_some(), unmatched(then)) {
+            (true, true) => {
+                self.line(level, &format!("{} {{", head));
+                self.stmt(then, level + 1);
+                true
+            },
+            _ => self.body(&head, then, level),
+        };
+
+        match *other {
+            None => if open { self.line(level, "}") },
+            Some(ref other) => {
+                let prefix = if open { "} else" } else { "else" };
+                match **other {
+                    CStmt::If(..) => self.if_stmt(&format!("{} ", prefix), other, level),
+                    _ => if self.body(prefix, other, level) { self.line(level, "}") },
+                }
+            },
+        }
+    }
+
+    /// `for` loop, from its initializers and the `while` it became
+    fn for_loop(&mut self, inits: &[Box<CStmt>], stmt: &CStmt, level: usize) {
+        let (loc, cond, body) = match *stmt {
+            CStmt::While(loc, ref cond, ref body) => (loc, cond, body),
+            _ => return self.stmt(stmt, level),
+        };
+
+        let cond = match *cond {
+            CExpr::Int(l, 1) if l == loc => String::new(),
+            ref e => format!(" {}", expr_at(e, 0)),
+        };
+        // increments are added to the end of the body
+        let (ss, incs) = match **body {
+            CStmt::Block((0, 0), ref ss) => {
+                let n = incs_len(ss);
+                (Some(&ss[..(ss.len() - n)]), &ss[(ss.len() - n)..])
+            },
+            _ => (None, &[][..]),
+        };
+
+        let inits: Vec<String> = inits.iter().map(|s| assign_str(s)).collect();
+        let incs: Vec<String> = incs.iter().map(|s| assign_str(s)).collect();
+        let incs = match incs.is_empty() {
+            true => String::new(),
+            false => format!(" {}", incs.join(", ")),
+        };
+        let head = format!("for ({};{};{})", inits.join(", "), cond, incs);
+
+        match ss {
+            Some(ss) => {
+                self.line(level, &format!("{} {{", head));
+                self.stmts(ss, level + 1);
+                self.line(level, "}");
+            },
+            None => if self.body(&head, body, level) { self.line(level, "}") },
+        }
+    }
+}
+
+/// Whether the location spans some source. Nodes built in code have none,
+/// so are never taken for sugar the parser removed
+fn parsed(loc: CLoc) -> bool {
+    loc.0 < loc.1
+}
+
+/// Number of program elements declared together with the first, which
+/// are all of the same kind
+fn same_loc(elems: &[CProgElem], loc: CLoc) -> usize {
+    if !parsed(loc) {
+        return 1;
+    }
+    elems.iter().take_while(|elem| match (&elems[0], *elem) {
+        (&CProgElem::Decl(..), &CProgElem::Decl(l, ..)) |
+        (&CProgElem::Extern(..), &CProgElem::Extern(l, ..)) |
+        (&CProgElem::Proto(..), &CProgElem::Proto(l, _)) => l == loc,
+        _ => false,
+    }).count()
+}
+
+/// Initializers and loop of a block that was a `for` loop
+fn for_block<'a>(loc: CLoc, ss: &'a [Box<CStmt>]) -> Option<(&'a [Box<CStmt>], &'a CStmt)> {
+    match ss.split_last() {
+        Some((last, inits)) => match **last {
+            CStmt::While(l, ..) if l == loc && parsed(loc) && inits.iter().all(|s| match **s { CStmt::Assign(..) => true, _ => false }) =>
+                Some((inits, &**last)),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+/// Loop that can only have been a `for`, as it has no condition or has
+/// increments
+fn is_for(loc: CLoc, cond: &CExpr, body: &CStmt) -> bool {
+    match (cond, body) {
+        _ if !parsed(loc) => false,
+        (&CExpr::Int(l, 1), _) if l == loc => true,
+        (_, &CStmt::Block((0, 0), _)) => true,
+        _ => false,
+    }
+}
+
+/// Number of increments at the end of the body of a `for` loop. They come
+/// from the header, so are in order and before the rest of the body
+fn incs_len(ss: &[Box<CStmt>]) -> usize {
+    let mut n = 0;
+    while n < ss.len() {
+        let s = &ss[ss.len() - 1 - n];
+        let ordered = match (stmt_loc(s), ss.get(ss.len() - n).and_then(|next| stmt_loc(next))) {
+            (Some((l, _)), Some((next, _))) => l < next,
+            (Some(_), None) => n == 0,
+            _ => false,
+        };
+        match **s {
+            CStmt::Assign(..) if ordered => n += 1,
+            _ => break,
+        }
+    }
+    n
+}
+
+/// Whether an `else` after the statement would be taken by an `if` in it
+fn unmatched(stmt: &CStmt) -> bool {
+    match *stmt {
+        CStmt::If(_, _, _, None) => true,
+        CStmt::If(_, _, _, Some(ref s)) |
+        CStmt::While(_, _, ref s) => unmatched(s),
+        _ => false,
+    }
+}
+
+fn stmt_loc(stmt: &CStmt) -> Option<CLoc> {
+    match *stmt {
+        CStmt::Decl(l, ..) |
+        CStmt::Assign(l, ..) |
+        CStmt::Call(l, ..) |
+        CStmt::Return(l, _) |
+        CStmt::Block(l, _) |
+        CStmt::Decls(l, _) |
+        CStmt::If(l, ..) |
+        CStmt::While(l, ..) |
+        CStmt::DoWhile(l, ..) |
+        CStmt::Switch(l, ..) |
+        CStmt::Case(l, _) |
+        CStmt::Break(l) |
+        CStmt::Print(l, ..) => Some(l),
+        CStmt::Error => None,
+    }
+}
+
+/// Declaration of one or more names, some of them initialized
+fn decl_group(ss: &[&CStmt]) -> String {
+    let mut base = None;
+    let mut names = vec![];
+    let mut i = 0;
+    while i < ss.len() {
+        match *ss[i] {
+            CStmt::Decl(_, ref t, ref id, ref so) => {
+                let (t, name) = match (t, so) {
+                    (&CType::Ref(ref t), &Some(ref e)) => ((**t).clone(), format!("{}[{}]", id, expr_at(e, 1))),
+                    (_, &Some(ref e)) => (t.clone(), format!("{}[{}]", id, expr_at(e, 1))),
+                    (&CType::Ref(ref t), &None) => ((**t).clone(), format!("*{}", id)),
+                    (_, &None) => (t.clone(), id.to_owned()),
+                };
+                base.get_or_insert(t);
+
+                // the initializer assigns the name, even to an element
+                let init = match ss.get(i + 1) {
+                    Some(&&CStmt::Assign(_, ref id2, _, ref e)) if id2 == id => {
+                        i += 1;
+                        format!(" = {}", expr_at(e, 1))
+                    },
+                    _ => String::new(),
+                };
+                names.push(format!("{}{}", name, init));
+            },
+            ref s => names.push(assign_str(s)),
+        }
+        i += 1;
+    }
+    format!("{} {}", type_str(&base.unwrap_or(CType::Int)), names.join(", "))
+}
+
+/// Assignment without the `;`, as `i++` and `i--` when it was written so
+fn assign_str(stmt: &CStmt) -> String {
+    let (loc, id, eo, e) = match *stmt {
+        CStmt::Assign(loc, ref id, ref eo, ref e) => (loc, id, eo, e),
+        _ => return String::new(),
+    };
+    let target = match *eo {
+        Some(ref i) => format!("{}[{}]", id, expr_at(i, 1)),
+        None => id.to_owned(),
+    };
+
+    if let CExpr::BinOp(l, op, ref e1, ref e2) = *e {
+        if let (&CExpr::Ident(l1, ref id1), &CExpr::Int(l2, 1)) = (&**e1, &**e2) {
+            if parsed(loc) && l == loc && l1 == loc && l2 == loc && id1 == id {
+                match op {
+                    COp::Add => return format!("{}++", target),
+                    COp::Sub => return format!("{}--", target),
+                    _ => (),
+                }
+            }
+        }
+    }
+    format!("{} = {}", target, expr_at(e, 1))
+}
+
+fn call_str(id: &str, args: &[Box<CExpr>]) -> String {
+    let args: Vec<String> = args.iter().map(|e| expr_at(e, 1)).collect();
+    format!("{}({})", id, args.join(", "))
+}
+
+/// How tightly an expression binds, following the grammar's levels from
+/// the comma operator up to terms
+fn prec(expr: &CExpr) -> u8 {
+    match *expr {
+        CExpr::Comma(..) => 0,
+        CExpr::Ternary(..) => 1,
+        CExpr::BinOp(_, op, ..) => match op {
+            COp::Or => 2,
+            COp::And => 3,
+            COp::Eq | COp::Neq => 4,
+            COp::Lt | COp::Lte | COp::Gt | COp::Gte => 5,
+            COp::Add | COp::Sub => 6,
+            COp::Mul | COp::Div => 7,
+            COp::Neg | COp::Not => 8,
+        },
+        _ => 8,
+    }
+}
+
+/// Expression where the grammar expects one binding at least as tightly
+/// as `min`, in parentheses otherwise
+fn expr_at(expr: &CExpr, min: u8) -> String {
+    let s = match *expr {
+        CExpr::Int(_, i) => i.to_string(),
+        CExpr::Float(_, f) => {
+            // literals always have a fraction
+            let s = f.to_string();
+            match s.contains('.') {
+                true => s,
+                false => format!("{}.0", s),
+            }
+        },
+        CExpr::Str(_, ref s) => format!("\"{}\"", s.as_str()),
+        CExpr::Char(_, '\0') => "'\\0'".to_owned(),
+        CExpr::Char(_, '\n') => "'\\n'".to_owned(),
+        CExpr::Char(_, c) => format!("'{}'", c),
+        CExpr::Ident(_, ref id) => id.to_owned(),
+        CExpr::UnOp(_, op, ref e) => {
+            let e = expr_at(e, 8);
+            // keep `- -x` from reading as `--x`
+            match e.starts_with('-') {
+                true => format!("{:?}({})", op, e),
+                false => format!("{:?}{}", op, e),
+            }
+        },
+        CExpr::BinOp(_, op, ref e1, ref e2) => {
+            let p = prec(expr);
+            format!("{} {:?} {}", expr_at(e1, p), op, expr_at(e2, p + 1))
+        },
+        CExpr::Call(_, ref id, ref args) => call_str(id, args),
+        CExpr::Index(_, ref id, ref e) => format!("{}[{}]", id, expr_at(e, 1)),
+        CExpr::Cast(_, ref t, ref e) => format!("({}) {}", type_str(t), expr_at(e, 8)),
+        CExpr::Ternary(_, ref c, ref e1, ref e2) => format!("{} ? {} : {}", expr_at(c, 2), expr_at(e1, 0), expr_at(e2, 1)),
+        CExpr::Comma(_, ref e1, ref e2) => format!("{}, {}", expr_at(e1, 0), expr_at(e2, 1)),
+        CExpr::Error => String::new(),
+    };
+
+    match prec(expr) < min {
+        true => format!("({})", s),
+        false => s,
+    }
+}
+
+/// Type without its pointers, and the pointers as written before a name
+fn split_ref(t: &CType) -> (&CType, String) {
+    match *t {
+        CType::Ref(ref t) => {
+            let (base, stars) = split_ref(t);
+            (base, format!("{}*", stars))
+        },
+        ref t => (t, String::new()),
+    }
+}
+
+fn type_str(t: &CType) -> String {
+    match split_ref(t) {
+        (base, ref stars) if stars.is_empty() => format!("{:?}", base),
+        (base, stars) => format!("{:?} {}", base, stars),
+    }
+}
+
+fn params_str(params: &[(CType, CIdent)]) -> String {
+    if params.is_empty() {
+        return "void".to_owned();
+    }
+    let ps: Vec<String> = params.iter().map(|&(ref t, ref id)| {
+        let (base, stars) = split_ref(t);
+        format!("{:?} {}{}", base, stars, id)
+    }).collect();
+    ps.join(", ")
+}
+
+fn ret_base(ret: &Option<CType>) -> String {
+    match *ret {
+        Some(ref t) => format!("{:?}", split_ref(t).0),
+        None => "void".to_owned(),
+    }
+}
+
+fn ret_stars(ret: &Option<CType>) -> String {
+    match *ret {
+        Some(ref t) => split_ref(t).1,
+        None => String::new(),
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use json::Json;
+use source::{FileId, SourceMap};
+
+/// Calls of a function and the time spent in them
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncProfile {
+    pub name: String,
+    pub calls: u64,
+    /// Time including the functions it called
+    pub total: Duration,
+    /// Time in the function itself
+    pub self_time: Duration,
+}
+
+/// Counts gathered while profiling a run
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// Times each statement ran, by location
+    pub stmts: HashMap<usize, u64>,
+    pub funcs: Vec<FuncProfile>,
+    index: HashMap<String, usize>,
+    /// Calls not returned from: function, start, and time spent in callees
+    stack: Vec<(usize, Instant, Duration)>,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile { stmts: HashMap::new(), funcs: vec![], index: HashMap::new(), stack: vec![] }
+    }
+
+    pub fn stmt(&mut self, loc: usize) {
+        *self.stmts.entry(loc).or_insert(0) += 1;
+    }
+
+    pub fn enter(&mut self, name: &str) {
+        let i = match self.index.get(name) {
+            Some(&i) => i,
+            None => {
+                let i = self.funcs.len();
+                self.funcs.push(FuncProfile {
+                    name: name.to_owned(),
+                    calls: 0,
+                    total: Duration::new(0, 0),
+                    self_time: Duration::new(0, 0),
+                });
+                self.index.insert(name.to_owned(), i);
+                i
+            },
+        };
+        self.funcs[i].calls += 1;
+        self.stack.push((i, Instant::now(), Duration::new(0, 0)));
+    }
+
+    pub fn leave(&mut self) {
+        if let Some((i, start, callees)) = self.stack.pop() {
+            let elapsed = start.elapsed();
+            // time of recursive calls is already in the outermost one
+            if !self.stack.iter().any(|&(j, ..)| j == i) {
+                self.funcs[i].total += elapsed;
+            }
+            self.funcs[i].self_time += elapsed - callees.min(elapsed);
+            if let Some(caller) = self.stack.last_mut() {
+                caller.2 += elapsed;
+            }
+        }
+    }
+
+    /// Stop the clock on calls that never returned, as when the run failed
+    pub fn finish(&mut self) {
+        while !self.stack.is_empty() {
+            self.leave();
+        }
+    }
+
+    /// Statements run on each line of a file, in order, with lines
+    /// numbered as in the debugger
+    pub fn lines(&self, sources: &SourceMap, file: FileId) -> Vec<(usize, u64)> {
+        let mut lines: HashMap<usize, u64> = HashMap::new();
+        for (&loc, &n) in self.stmts.iter() {
+            match sources.line(loc) {
+                Some((id, line)) if id == file => *lines.entry(line).or_insert(0) += n,
+                _ => (),
+            }
+        }
+        let mut lines: Vec<(usize, u64)> = lines.into_iter().collect();
+        lines.sort();
+        lines
+    }
+
+    /// Functions with the most time spent in themselves first
+    pub fn funcs_by_time(&self) -> Vec<&FuncProfile> {
+        let mut funcs: Vec<&FuncProfile> = self.funcs.iter().collect();
+        funcs.sort_by(|a, b| b.self_time.cmp(&a.self_time).then(a.name.cmp(&b.name)));
+        funcs
+    }
+
+    /// Source annotated with the statements run on each line, followed by
+    /// the functions. Included files that ran follow the program, each
+    /// under its name
+    pub fn listing(&self, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        for (id, file) in sources.files().iter().enumerate() {
+            let counts: HashMap<usize, u64> = self.lines(sources, id).into_iter().collect();
+            if id > 0 {
+                if counts.is_empty() {
+                    continue;
+                }
+                out.push_str(&format!("\n {}\n", file.name));
+            }
+
+            let mut srcs: Vec<&str> = file.src.split('\n').collect();
+            if srcs.last() == Some(&"") {
+                srcs.pop();
+            }
+            for (i, src) in srcs.into_iter().enumerate() {
+                let count = counts.get(&(i + 1)).map_or(String::new(), |n| n.to_string());
+                out.push_str(&format!("{:>9} {:>4} | {}\n", count, i + 1, src));
+            }
+        }
+
+        out.push_str(&format!("\n {:<20} {:>9} {:>12} {:>12}\n", "function", "calls", "total ms", "self ms"));
+        for f in self.funcs_by_time() {
+            out.push_str(&format!(" {:<20} {:>9} {:>12.3} {:>12.3}\n", f.name, f.calls, millis(f.total), millis(f.self_time)));
+        }
+        out
+    }
+
+    /// Line counts and functions as a JSON object, with times in
+    /// milliseconds
+    pub fn to_json(&self, sources: &SourceMap) -> Json {
+        let lines = sources.files().iter().enumerate().flat_map(|(id, file)| {
+            self.lines(sources, id).into_iter().map(move |(line, n)| Json::object(vec![
+                ("file", Json::str(&file.name)),
+                ("line", Json::Num(line as f64)),
+                ("count", Json::Num(n as f64)),
+            ]))
+        }).collect();
+        let funcs = self.funcs_by_time().into_iter().map(|f| Json::object(vec![
+            ("name", Json::str(&f.name)),
+            ("calls", Json::Num(f.calls as f64)),
+            ("total_ms", Json::Num(millis(f.total))),
+            ("self_ms", Json::Num(millis(f.self_time))),
+        ])).collect();
+
+        Json::object(vec![("lines", Json::Array(lines)), ("functions", Json::Array(funcs))])
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1_000_000.0
+}
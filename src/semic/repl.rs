@@ -1,37 +1,91 @@
 use std::process;
+use std::env;
 use std::io;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+use std::rc::Rc;
 
-use ast::CStmt;
+use rustyline::{self, Editor, Helper};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+
+use ast::{CStmt, CType};
+use dap::Adapter;
 use error::CError;
-use env::SymTab;
-use util;
+use env::{SymTab, SymVal};
+use engine;
+use resolver::{Resolution, Scope};
+use source::{FileId, SourceMap};
+
+/// Commands of the debugger, as completed
+const COMMANDS: [&'static str; 7] = ["next", "print", "trace", "continue", "break", "list", "quit"];
 
 #[derive(Clone)]
-pub struct Repl {
+pub struct Repl<'r> {
     verbose: bool,
     skip: usize,
-    map: Vec<usize>,
-    last_line: usize,
+    sources: Rc<SourceMap>,
+    /// File and line of the last statement shown
+    last_line: (FileId, usize),
+    /// Lines to stop at, and whether to run until one is reached. Calls
+    /// get a copy of the REPL, so these are shared with it
+    breaks: Rc<RefCell<Vec<(FileId, usize)>>>,
+    running: Rc<Cell<bool>>,
+    /// What names refer to, and the location of the current statement
+    resolution: Rc<Resolution<'r>>,
+    loc: usize,
+    /// Adapter of the editor debugging the program, which takes over
+    /// deciding where to stop and talking to the user
+    adapter: Option<Rc<RefCell<Adapter>>>,
+    /// Editor of the lines typed at a terminal, and the last command when
+    /// it was 'next', which an empty line repeats
+    editor: Option<Rc<RefCell<LineEditor>>>,
+    repeat: Rc<RefCell<Option<String>>>,
+}
 
+/// Editor with history, kept across sessions in `~/.semic_history`
+struct LineEditor {
+    editor: Editor<Names>,
+    history: Option<PathBuf>,
 }
 
-impl<'a> Repl {
-    pub fn new(program: &'a str, verbose: bool) -> Repl {
-        let lines: Vec<&'a str> = program.split('\n').collect();
-        let map = lines.into_iter().map(|line| line.len() + 1).collect();
+/// Completes commands, and the variables in scope after the commands
+/// taking one
+struct Names(Vec<String>);
 
+impl<'r> Repl<'r> {
+    pub fn new(
+        sources: &SourceMap,
+        verbose: bool,
+        resolution: Resolution<'r>,
+        adapter: Option<Rc<RefCell<Adapter>>>,
+    ) -> Repl<'r>
+    {
         Repl {
             verbose: verbose,
             skip: 0,
-            map: map,
-            last_line: 0,
+            sources: Rc::new(sources.clone()),
+            last_line: (0, 0),
+            breaks: Rc::new(RefCell::new(vec![])),
+            running: Rc::new(Cell::new(false)),
+            resolution: Rc::new(resolution),
+            loc: 0,
+            editor: match adapter.is_none() && io::stdin().is_terminal() {
+                true => Some(Rc::new(RefCell::new(LineEditor::new()))),
+                false => None,
+            },
+            adapter: adapter,
+            repeat: Rc::new(RefCell::new(None)),
         }
     }
 
     pub fn show<'input>(
         &mut self,
-        stmt: &'input CStmt<'input>,
+        stmt: &'input CStmt,
         global_symtab: &'input SymTab<'input>,
         local_symtab: &'input SymTab<'input>,
     ) -> Result<(), CError>
@@ -40,6 +94,10 @@ impl<'a> Repl {
             CStmt::Decl((l, _), ..) |
             CStmt::Assign((l, _), ..) |
             CStmt::While((l, _), ..) |
+            CStmt::DoWhile((l, _), ..) |
+            CStmt::Switch((l, _), ..) |
+            CStmt::Case((l, _), ..) |
+            CStmt::Break((l, _)) |
             CStmt::Call((l, _), ..) |
             CStmt::Return((l, _), ..) |
             CStmt::If((l, _), ..) |
@@ -47,22 +105,43 @@ impl<'a> Repl {
             _ => None,
         };
         let lineo = match loc {
-            Some(x) => util::line_from(x, &self.map),
+            Some(x) => self.sources.line(x),
             None => None
         };
+        if let Some(l) = loc {
+            self.loc = l;
+        }
+
+        if let Some(ref adapter) = self.adapter {
+            return match loc {
+                Some(l) => adapter.borrow_mut().statement(l, global_symtab, local_symtab, &self.resolution),
+                None => Ok(()),
+            };
+        }
 
         if self.verbose {
             println!(" REPL ({:?}/{:?} skip: {}) {:?}", lineo, loc, self.skip, stmt);
         }
 
-        if let Some(line) = lineo  {
-            if line == self.last_line {
+        if let Some((file, line)) = lineo  {
+            if (file, line) == self.last_line {
                 return Ok(());
             }
 
+            // continuing runs until a breakpoint
+            if self.running.get() {
+                if !self.breaks.borrow().contains(&(file, line)) {
+                    self.last_line = (file, line);
+                    return Ok(());
+                }
+                self.running.set(false);
+                self.skip = 0;
+                println!(" Breakpoint at {}", self.place(file, line));
+            }
+
             // blank lines counts when skipping
-            if line > self.last_line {
-                for _ in 0..(line - self.last_line) {
+            if file == self.last_line.0 && line > self.last_line.1 {
+                for _ in 0..(line - self.last_line.1) {
                     if self.skip > 0 {
                         self.skip -= 1;
                     } else {
@@ -71,14 +150,14 @@ impl<'a> Repl {
                 }
             }
 
+            self.last_line = (file, line);
+
             // check if should skip
             if self.skip > 0 {
                 self.skip -= 1;
             } else {
                 self.read(global_symtab, local_symtab, false)?;
             }
-
-            self.last_line = line;
         }
 
         Ok(())
@@ -90,6 +169,11 @@ impl<'a> Repl {
         local_symtab: &'input SymTab<'input>,
     ) -> Result<(), CError>
     {
+        // the adapter reports the end itself, with how the run went
+        if self.adapter.is_some() {
+            return Ok(());
+        }
+
         println!(" End of program");
 
         self.read(global_symtab, local_symtab, true)?;
@@ -104,17 +188,38 @@ impl<'a> Repl {
         finished: bool
     ) -> Result<(), CError> {
         loop {
-            print!(">> ");
-            let _ = io::stdout().flush().unwrap(); // TODO: error handling
+            let mut names: Vec<String> = local_symtab.names().into_iter()
+                .chain(global_symtab.names())
+                .map(|x| x.to_owned())
+                .collect();
+            names.sort();
+            names.dedup();
 
-            // read input
-            let mut input = String::new();
-            let (command, arg) = match io::stdin().read_line(&mut input) {
-                Err(error) => return Err(CError::UnknownError(error.to_string())),
-                Ok(_) => {
-                    let mut matches = input.split_whitespace();
-                    (matches.next(), matches.next())
-                }
+            // the end of input quits
+            let input = match self.read_line(names)? {
+                Some(x) => x,
+                None => "quit".to_owned(),
+            };
+            let input = match (input.trim().is_empty(), self.repeat.borrow().clone()) {
+                (true, Some(x)) => x,
+                _ => input,
+            };
+            let (command, arg) = {
+                let mut matches = input.split_whitespace();
+                (matches.next(), matches.next())
+            };
+            *self.repeat.borrow_mut() = match command {
+                Some("next") | Some("n") => Some(input.clone()),
+                _ => None,
+            };
+
+            // split off print format, e.g. 'print/d'
+            let (command, format) = match command {
+                Some(x) => match x.find('/') {
+                    Some(i) => (Some(&x[..i]), x[(i + 1)..].chars().next()),
+                    None => (Some(x), None),
+                },
+                None => (None, None),
             };
 
             // match command
@@ -150,21 +255,28 @@ impl<'a> Repl {
                         },
                     };
 
-                    match local_symtab.get_type(id) {
-                        Some(_) => match local_symtab.get_val(id) {
-                            Some(x) => println!(" {:?}", x),
+                    match format {
+                        None | Some('d') | Some('c') => (),
+                        Some(x) => {
+                            println!(" Unknown format '/{}': try 'print/d' or 'print/c'", x);
+                            continue;
+                        },
+                    };
+
+                    match self.resolution.scope_at(id, self.loc) {
+                        Scope::Local(_) => match local_symtab.get_val(id) {
+                            Some(x) => println!(" {}", format_val(&x, format)),
                             None => println!(" N\\A"),
                         },
-                        None => match global_symtab.get_type(id) {
-                            Some(_) => match global_symtab.get_val(id) {
-                                Some(x) => println!(" {:?} (global)", x),
-                                None => println!(" N\\A (global)"),
-                            },
-                            None => match local_symtab.get_val_parent(id) {
-                                Some(x) => println!(" {:?} (invisible)", x),
-                                None => println!(" Not declared"),
-                            }
-                        }
+                        Scope::Global(_) => match global_symtab.get_val(id) {
+                            Some(x) => println!(" {} (global)", format_val(&x, format)),
+                            None => println!(" N\\A (global)"),
+                        },
+                        // only the callers have it
+                        Scope::Undeclared => match local_symtab.get_val_parent(id) {
+                            Some(x) => println!(" {} (invisible)", format_val(&x, format)),
+                            None => println!(" Not declared"),
+                        },
                     };
                 },
                 Some("trace") | Some("t") => {
@@ -178,34 +290,79 @@ impl<'a> Repl {
                     };
 
                     // get val
-                    let trace = match local_symtab.get_trace(id) {
+                    let trace = match self.resolution.scope_at(id, self.loc) {
+                        Scope::Local(_) => local_symtab.get_trace(id),
+                        Scope::Global(_) => global_symtab.get_trace(id),
+                        Scope::Undeclared => None,
+                    };
+                    let (dropped, trace) = match trace {
                         Some(x) => x,
-                        _ => match global_symtab.get_trace(id) {
-                            Some(x) => x,
-                            _ => {
-                                println!{" N\\A"};
-                                continue;
-                            }
+                        None => {
+                            println!{" N\\A"};
+                            continue;
                         }
                     };
 
-                    for (valo, loco) in trace {
+                    if dropped > 0 {
+                        println!(" ... {} earlier", dropped);
+                    }
+                    for (io, valo, loco) in trace {
                         let val = match valo {
                             Some(v) => format!("{:?}", v),
                             None => format!("N\\A"),
                         };
+                        let id = match io {
+                            Some(i) => format!("{}[{}]", id, i),
+                            None => id.to_owned(),
+                        };
 
                         let lineo = match loco {
-                            Some(x) => util::line_from(x, &self.map),
+                            Some((x, _)) => self.sources.line(x),
                             None => None
                         };
 
                         match lineo {
-                            Some(line) => println!(" {} = {} at line {}", id, val, line),
+                            Some((file, line)) => println!(" {} = {} at {}", id, val, self.place(file, line)),
                             None => println!(" {} = {}", id, val)
                         }
                     }
                 },
+                Some("continue") | Some("c") => {
+                    self.running.set(true);
+                    break;
+                },
+                Some("break") | Some("b") => {
+                    let (file, line) = match arg.and_then(|x| self.parse_line(x)) {
+                        Some(x) => x,
+                        None => {
+                            println!(" Incorrect command usage: try 'break [file:]line'");
+                            continue;
+                        },
+                    };
+                    match self.sources.file(file).map_or(0, |f| f.line_count()) >= line {
+                        true => {
+                            let mut breaks = self.breaks.borrow_mut();
+                            if !breaks.contains(&(file, line)) {
+                                breaks.push((file, line));
+                            }
+                            println!(" Breakpoint at {}", self.place(file, line));
+                        },
+                        false => println!(" No {}", self.place(file, line)),
+                    }
+                },
+                Some("list") | Some("l") => {
+                    let (file, line) = match arg {
+                        Some(x) => match self.parse_line(x) {
+                            Some(x) => x,
+                            None => {
+                                println!(" Incorrect command usage: try 'list [[file:]line]'");
+                                continue;
+                            },
+                        },
+                        None => self.last_line,
+                    };
+                    self.list(file, line);
+                },
                 Some("quit") | Some("q") => {
                     println!(" Bye, bye");
                     if finished {
@@ -222,3 +379,167 @@ impl<'a> Repl {
         Ok(())
     }
 }
+
+impl<'r> Repl<'r> {
+    /// Line typed at the prompt, or none at the end of input. Input that is
+    /// not typed, as from a script, is read plainly
+    fn read_line(&self, names: Vec<String>) -> Result<Option<String>, CError> {
+        if let Some(ref editor) = self.editor {
+            return editor.borrow_mut().read_line(names);
+        }
+
+        print!(">> ");
+        let _ = io::stdout().flush();
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(input)),
+            Err(error) => Err(CError::UnknownError(error.to_string())),
+        }
+    }
+
+    /// Line given as 'line' in the current file, or as 'file:line', where
+    /// the file may be named without its directory
+    fn parse_line(&self, arg: &str) -> Option<(FileId, usize)> {
+        let (file, line) = match arg.rfind(':') {
+            Some(i) => {
+                let name = &arg[..i];
+                let file = self.sources.files().iter().position(|f| {
+                    f.name == name || f.name.ends_with(&format!("/{}", name))
+                });
+                (file?, &arg[(i + 1)..])
+            },
+            None => (self.last_line.0, arg),
+        };
+        match line.parse() {
+            Ok(n) if n > 0 => Some((file, n)),
+            _ => None,
+        }
+    }
+
+    /// Lines around a line of a file, marking the one about to run
+    fn list(&self, file: FileId, line: usize) {
+        let source = match self.sources.file(file) {
+            Some(x) => x,
+            None => return,
+        };
+        let first = line.saturating_sub(5).max(1);
+        for i in first..(line + 6) {
+            let text = match source.line_text(i) {
+                Some(x) if i < source.line_count() || !x.is_empty() => x,
+                _ => break,
+            };
+            let mark = if (file, i) == self.last_line { ">" } else { " " };
+            println!(" {} {:>4} | {}", mark, i, text);
+        }
+    }
+
+    /// Line for messages, naming the file when it is an included one
+    fn place(&self, file: FileId, line: usize) -> String {
+        match file {
+            0 => format!("line {}", line),
+            _ => format!("line {} of {}", line, self.sources.file(file).map_or("", |f| f.name.as_str())),
+        }
+    }
+}
+
+impl LineEditor {
+    fn new() -> LineEditor {
+        let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".semic_history"));
+        let mut editor = Editor::new();
+        if let Some(ref path) = history {
+            // there is none the first time
+            let _ = editor.load_history(path);
+        }
+        LineEditor { editor: editor, history: history }
+    }
+
+    fn read_line(&mut self, names: Vec<String>) -> Result<Option<String>, CError> {
+        self.editor.set_helper(Some(Names(names)));
+        loop {
+            match self.editor.readline(">> ") {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        self.editor.add_history_entry(line.as_str());
+                        if let Some(ref path) = self.history {
+                            let _ = self.editor.save_history(path);
+                        }
+                    }
+                    return Ok(Some(line));
+                },
+                // ctrl-c gives a new prompt, as in a shell
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(error) => return Err(CError::UnknownError(error.to_string())),
+            }
+        }
+    }
+}
+
+impl Completer for Names {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &rustyline::Context) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.0))
+    }
+}
+
+impl Hinter for Names {
+    type Hint = String;
+}
+
+impl Highlighter for Names {}
+
+impl Validator for Names {}
+
+impl Helper for Names {}
+
+/// Completions of the word before the cursor, which is a command when it
+/// is the first word, or one of the names after 'print' and 'trace'. Gives
+/// where the word starts, with the candidates
+///
+/// # Examples
+///
+/// ```
+/// use semic::repl::complete;
+/// let names = vec!["count".to_owned(), "x".to_owned()];
+/// assert_eq!((0, vec!["continue".to_owned()]), complete("cont", 4, &names));
+/// assert_eq!((8, vec!["count".to_owned()]), complete("print/d c", 9, &names));
+/// ```
+pub fn complete(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before.rfind(|c: char| c.is_ascii_whitespace()).map_or(0, |i| i + 1);
+    let word = &before[start..];
+
+    let mut words = before[..start].split_whitespace();
+    let candidates = match (words.next(), words.next()) {
+        (None, _) => COMMANDS.iter().filter(|c| c.starts_with(word)).map(|c| c.to_string()).collect(),
+        (Some(command), None) => match command.split('/').next() {
+            Some("print") | Some("p") | Some("trace") | Some("t") =>
+                names.iter().filter(|n| n.starts_with(word)).cloned().collect(),
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+    (start, candidates)
+}
+
+/// Format value for printing, optionally as numbers ('d') or characters ('c')
+fn format_val(val: &SymVal, format: Option<char>) -> String {
+    let t = match format {
+        Some('d') => CType::Int,
+        Some('c') => CType::Char,
+        _ => return format!("{:?}", val),
+    };
+
+    match *val {
+        SymVal::Array(ref a) => {
+            let vs: Vec<String> = a.iter().map(|v| format_val(v, format)).collect();
+            format!("[{}]", vs.join(", "))
+        },
+        ref v => match engine::cast(v, (0, 0), &t) {
+            Ok(x) => format!("{:?}", x),
+            Err(_) => format!("{:?}", v),
+        },
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use ast::*;
+use env::SymTab;
+
+/// What a name refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    /// Slot among the locals of the enclosing function
+    Local(usize),
+    /// Slot among the globals
+    Global(usize),
+    /// Declared nowhere, which is an error once used
+    Undeclared,
+}
+
+/// Local variable, where parameters come first
+#[derive(Debug, Clone)]
+pub struct Local<'input> {
+    pub name: &'input str,
+    pub t: CType,
+    /// Location of the declaration, none for parameters
+    pub decl: Option<CLoc>,
+    /// Span of the block it was declared in, none for the outermost one,
+    /// and `(0, 0)` when unknown, which no location is in
+    pub within: Option<CLoc>,
+}
+
+/// Locals of a function, and what each identifier in its body refers to.
+/// Uses are looked up by the node they are in, so the AST they were
+/// resolved from has to be the one asked about.
+#[derive(Debug, Clone)]
+pub struct Scopes<'input> {
+    pub locals: Vec<Local<'input>>,
+    /// Slots of locals given by a symbol table, when resolving a statement
+    /// or expression on its own
+    pub seeded: Vec<usize>,
+    exprs: HashMap<*const CExpr, Scope>,
+    stmts: HashMap<*const CStmt, Scope>,
+}
+
+/// Resolution of a whole program
+#[derive(Debug, Clone)]
+pub struct Resolution<'input> {
+    pub globals: Vec<(&'input str, CType)>,
+    /// Span of each function with a body, and its scopes
+    pub funcs: Vec<((usize, usize), Scopes<'input>)>,
+}
+
+/// Resolves names in source order, like the tree-walker finds them: a local
+/// declared so far in a block around the use, otherwise a global. Blocks and
+/// the statements under `if`, `else`, `while` and `do` open a new scope, and
+/// each declaration gets a new slot, even when the name was declared before.
+pub struct Resolver<'a, 'input: 'a> {
+    global_symtab: &'a SymTab<'input>,
+    globals: Vec<(&'input str, CType)>,
+    global_index: HashMap<&'input str, usize>,
+}
+
+/// State while resolving a single function
+struct Names<'a, 'input: 'a> {
+    scopes: Scopes<'input>,
+    names: HashMap<&'input str, usize>,
+    outer: Option<&'a SymTab<'input>>,
+    /// Span of each block open, with the slots the names declared in it
+    /// hid, which are put back when it ends
+    blocks: Vec<(CLoc, Vec<(&'input str, Option<usize>)>)>,
+}
+
+impl<'input> Scopes<'input> {
+    pub fn new() -> Scopes<'input> {
+        Scopes { locals: vec![], seeded: vec![], exprs: HashMap::new(), stmts: HashMap::new() }
+    }
+
+    /// What an identifier or index expression refers to
+    pub fn of_expr(&self, expr: &CExpr) -> Option<Scope> {
+        self.exprs.get(&(expr as *const _)).cloned()
+    }
+
+    /// What the variable declared or assigned by a statement refers to
+    pub fn of_stmt(&self, stmt: &CStmt) -> Option<Scope> {
+        self.stmts.get(&(stmt as *const _)).cloned()
+    }
+
+    /// Local a name refers to at a location in the body, which is the last
+    /// one declared before it in a block around it
+    pub fn local_at(&self, name: &str, loc: usize) -> Option<usize> {
+        self.locals.iter().rposition(|l| {
+            l.name == name &&
+            l.decl.map_or(true, |d| d.0 < loc) &&
+            l.within.map_or(true, |(wl, wr)| wl <= loc && loc < wr)
+        })
+    }
+}
+
+impl<'input> Resolution<'input> {
+    /// What a name refers to at a location in the program
+    pub fn scope_at(&self, name: &str, loc: usize) -> Scope {
+        let func = self.funcs.iter().find(|&&((l, r), _)| l <= loc && loc < r);
+        if let Some(&(_, ref scopes)) = func {
+            if let Some(i) = scopes.local_at(name, loc) {
+                return Scope::Local(i);
+            }
+        }
+        match self.globals.iter().position(|&(g, _)| g == name) {
+            Some(i) => Scope::Global(i),
+            None => Scope::Undeclared,
+        }
+    }
+}
+
+/// Resolve every function of a program, with the global symbol table from
+/// the checker
+pub fn resolve_prog<'a, 'input>(ast: &'input CProg, global_symtab: &'a SymTab<'input>) -> Resolution<'input> {
+    let mut resolver = Resolver::new(global_symtab);
+    for elem in ast.iter() {
+        if let CProgElem::Decl(_, _, ref name, _) = *elem {
+            resolver.global(name);
+        }
+    }
+
+    let funcs = ast.iter().filter_map(|elem| match *elem {
+        CProgElem::Func((l, r), ref func) => Some(((l, r), resolver.func(func))),
+        _ => None,
+    }).collect();
+
+    Resolution { globals: resolver.globals, funcs: funcs }
+}
+
+impl<'a, 'input> Names<'a, 'input> {
+    fn new(outer: Option<&'a SymTab<'input>>) -> Names<'a, 'input> {
+        Names { scopes: Scopes::new(), names: HashMap::new(), outer: outer, blocks: vec![] }
+    }
+
+    fn declare(&mut self, name: &'input str, t: CType, decl: Option<CLoc>) -> usize {
+        let slot = self.scopes.locals.len();
+        let within = self.blocks.last().map(|&(span, _)| span);
+        self.scopes.locals.push(Local { name: name, t: t, decl: decl, within: within });
+        let old = self.names.insert(name, slot);
+        if let Some(&mut (_, ref mut hidden)) = self.blocks.last_mut() {
+            hidden.push((name, old));
+        }
+        slot
+    }
+
+    /// Open a block, which spans the enclosing one when its own span is
+    /// unknown, like the body of a `for` loop
+    fn enter(&mut self, span: Option<CLoc>) {
+        let span = match span {
+            Some(loc) if loc != (0, 0) => loc,
+            _ => self.blocks.last().map_or((0, 0), |&(span, _)| span),
+        };
+        self.blocks.push((span, vec![]));
+    }
+
+    fn leave(&mut self) {
+        if let Some((_, hidden)) = self.blocks.pop() {
+            for (name, old) in hidden.into_iter().rev() {
+                match old {
+                    Some(slot) => self.names.insert(name, slot),
+                    None => self.names.remove(name),
+                };
+            }
+        }
+    }
+}
+
+impl<'a, 'input> Resolver<'a, 'input> {
+    pub fn new(global_symtab: &'a SymTab<'input>) -> Resolver<'a, 'input> {
+        Resolver { global_symtab: global_symtab, globals: vec![], global_index: HashMap::new() }
+    }
+
+    /// Globals referred to so far, by slot
+    pub fn globals(&self) -> &[(&'input str, CType)] {
+        &self.globals
+    }
+
+    pub fn global(&mut self, name: &'input str) -> Scope {
+        if let Some(&i) = self.global_index.get(name) {
+            return Scope::Global(i);
+        }
+        match self.global_symtab.get_type(name) {
+            Some((t, _)) => {
+                let i = self.globals.len();
+                self.globals.push((name, t));
+                self.global_index.insert(name, i);
+                Scope::Global(i)
+            },
+            None => Scope::Undeclared,
+        }
+    }
+
+    pub fn func(&mut self, func: &'input CFunc) -> Scopes<'input> {
+        let CFunc { ref proto, ref body } = *func;
+
+        let mut names = Names::new(None);
+        if proto.name == "main" {
+            // main is given the command line instead of its parameters
+            names.declare("argc", CType::Int, None);
+            names.declare("argv", CType::Ref(Box::new(CType::Ref(Box::new(CType::Char)))), None);
+        } else {
+            for &(ref t, ref id) in proto.params.iter() {
+                names.declare(id, t.clone(), None);
+            }
+        }
+
+        // the outermost block shares the scope of the parameters
+        match *body {
+            CStmt::Block(_, ref stmts) => for s in stmts.iter() { self.stmt(s, &mut names); },
+            ref body => self.stmt(body, &mut names),
+        }
+        names.scopes
+    }
+
+    /// Resolve the sizes of global arrays, which can only refer to globals
+    pub fn global_sizes(&mut self, exprs: &[&'input CExpr]) -> Scopes<'input> {
+        let mut names = Names::new(None);
+        for e in exprs.iter() {
+            self.expr(e, &mut names);
+        }
+        names.scopes
+    }
+
+    /// Resolve a statement on its own, where the local symbol table gives
+    /// the variables in scope
+    pub fn snippet_stmt(&mut self, stmt: &'input CStmt, local_symtab: &'a SymTab<'input>) -> Scopes<'input> {
+        let mut names = Names::new(Some(local_symtab));
+        self.stmt(stmt, &mut names);
+        names.scopes
+    }
+
+    /// Resolve an expression on its own, like `snippet_stmt`
+    pub fn snippet_expr(&mut self, expr: &'input CExpr, local_symtab: &'a SymTab<'input>) -> Scopes<'input> {
+        let mut names = Names::new(Some(local_symtab));
+        self.expr(expr, &mut names);
+        names.scopes
+    }
+
+    fn name(&mut self, name: &'input str, names: &mut Names<'a, 'input>) -> Scope {
+        if let Some(&i) = names.names.get(name) {
+            return Scope::Local(i);
+        }
+        if let Some(tab) = names.outer {
+            if let Some((t, _)) = tab.get_type(name) {
+                let decl = tab.get_decl(name);
+                let i = names.declare(name, t, decl);
+                names.scopes.seeded.push(i);
+                return Scope::Local(i);
+            }
+        }
+        self.global(name)
+    }
+
+    fn stmt(&mut self, stmt: &'input CStmt, names: &mut Names<'a, 'input>) {
+        let scope = match *stmt {
+            CStmt::Decl(loc, ref t, ref id, ref eo) => {
+                // the size is evaluated before the name is declared
+                if let Some(ref e) = *eo { self.expr(e, names); }
+                Scope::Local(names.declare(id, t.clone(), Some(loc)))
+            },
+            CStmt::Assign(_, ref id, ref eo, ref e) => {
+                let scope = self.name(id, names);
+                if let Some(ref i) = *eo { self.expr(i, names); }
+                self.expr(e, names);
+                scope
+            },
+            CStmt::Call(_, _, ref args) => {
+                for e in args.iter() { self.expr(e, names); }
+                return;
+            },
+            CStmt::Return(_, Some(ref e)) |
+            CStmt::Case(_, Some(ref e)) |
+            CStmt::Print(_, _, ref e) => return self.expr(e, names),
+            CStmt::Block(loc, ref stmts) => {
+                names.enter(Some(loc));
+                for s in stmts.iter() { self.stmt(s, names); }
+                return names.leave();
+            },
+            CStmt::Decls(_, ref stmts) => {
+                for s in stmts.iter() { self.stmt(s, names); }
+                return;
+            },
+            CStmt::If(_, ref cond, ref s1, ref s2) => {
+                self.expr(cond, names);
+                self.scoped(s1, names);
+                if let Some(ref s2) = *s2 { self.scoped(s2, names); }
+                return;
+            },
+            CStmt::While(_, ref cond, ref s) => {
+                self.expr(cond, names);
+                return self.scoped(s, names);
+            },
+            CStmt::DoWhile(_, ref s, ref cond) => {
+                self.scoped(s, names);
+                return self.expr(cond, names);
+            },
+            CStmt::Switch(loc, ref e, ref stmts) => {
+                self.expr(e, names);
+                names.enter(Some(loc));
+                for s in stmts.iter() { self.stmt(s, names); }
+                return names.leave();
+            },
+            CStmt::Return(_, None) |
+            CStmt::Case(_, None) |
+            CStmt::Break(_) |
+            CStmt::Error => return,
+        };
+        names.scopes.stmts.insert(stmt as *const _, scope);
+    }
+
+    /// Resolve a statement under `if`, `else`, `while` or `do`, which is a
+    /// scope of its own even when it is not a block
+    fn scoped(&mut self, stmt: &'input CStmt, names: &mut Names<'a, 'input>) {
+        let span = match *stmt {
+            CStmt::Block(..) => return self.stmt(stmt, names),
+            CStmt::Decl(loc, ..) |
+            CStmt::Decls(loc, ..) => Some(loc),
+            _ => None,
+        };
+        names.enter(span);
+        self.stmt(stmt, names);
+        names.leave();
+    }
+
+    fn expr(&mut self, expr: &'input CExpr, names: &mut Names<'a, 'input>) {
+        let scope = match *expr {
+            CExpr::Ident(_, ref id) => self.name(id, names),
+            CExpr::Index(_, ref id, ref e) => {
+                self.expr(e, names);
+                self.name(id, names)
+            },
+            CExpr::Call(_, _, ref args) => {
+                for e in args.iter() { self.expr(e, names); }
+                return;
+            },
+            CExpr::UnOp(_, _, ref e) |
+            CExpr::Cast(_, _, ref e) => return self.expr(e, names),
+            CExpr::BinOp(_, _, ref e1, ref e2) |
+            CExpr::Comma(_, ref e1, ref e2) => {
+                self.expr(e1, names);
+                return self.expr(e2, names);
+            },
+            CExpr::Ternary(_, ref c, ref e1, ref e2) => {
+                self.expr(c, names);
+                self.expr(e1, names);
+                return self.expr(e2, names);
+            },
+            CExpr::Int(..) |
+            CExpr::Float(..) |
+            CExpr::Str(..) |
+            CExpr::Char(..) |
+            CExpr::Error => return,
+        };
+        names.scopes.exprs.insert(expr as *const _, scope);
+    }
+}
@@ -0,0 +1,176 @@
+use ast::CLoc;
+use error::Span;
+
+/// Index of a file in a `SourceMap`
+pub type FileId = usize;
+
+/// Program text with the start of each line, for finding the line and
+/// column of a location. Columns count characters, not bytes, and like
+/// lines start at 1
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+    starts: Vec<usize>,
+}
+
+/// Files of a program, by id. Each file is given its own range of
+/// locations, starting where the previous one ended, so a location alone
+/// says which file it is in
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    bases: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, src: &str) -> SourceFile {
+        let mut starts = vec![0];
+        starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+        SourceFile { name: name.to_owned(), src: src.to_owned(), starts: starts }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Line of a location, which may be just past the end
+    pub fn line(&self, loc: usize) -> Option<usize> {
+        if loc > self.src.len() {
+            return None;
+        }
+        match self.starts.binary_search(&loc) {
+            Ok(i) => Some(i + 1),
+            Err(i) => Some(i),
+        }
+    }
+
+    pub fn line_col(&self, loc: usize) -> Option<(usize, usize)> {
+        let line = self.line(loc)?;
+        let before = self.src.get(self.starts[line - 1]..loc)?;
+        Some((line, before.chars().count() + 1))
+    }
+
+    /// Location of a line and column, the inverse of `line_col`. Columns
+    /// past the end of the line give its end
+    pub fn loc(&self, line: usize, column: usize) -> Option<usize> {
+        let text = self.line_text(line)?;
+        let start = self.starts[line - 1];
+        Some(match text.char_indices().nth(column.saturating_sub(1)) {
+            Some((i, _)) => start + i,
+            None => start + text.len(),
+        })
+    }
+
+    /// Text of a line, without its newline
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        if line == 0 || line > self.starts.len() {
+            return None;
+        }
+        let start = self.starts[line - 1];
+        let end = match self.starts.get(line) {
+            Some(&next) => next - 1,
+            None => self.src.len(),
+        };
+        Some(self.src[start..end].trim_end_matches('\r'))
+    }
+
+    /// Span of a location. Empty locations, as from errors found between
+    /// tokens, cover the word or character there
+    pub fn span(&self, loc: CLoc) -> Option<Span> {
+        let (line, column) = self.line_col(loc.0)?;
+        let (end_line, end_column) = match loc.1 > loc.0 {
+            true => self.line_col(loc.1)?,
+            false => {
+                let rest = self.line_text(line).unwrap_or("").chars().skip(column - 1);
+                let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+                let len = match rest.clone().next() {
+                    Some(ref c) if is_word(c) => rest.take_while(is_word).count(),
+                    _ => 1,
+                };
+                (line, column + len)
+            },
+        };
+
+        Some(Span {
+            offset: loc.0,
+            line: line,
+            column: column,
+            end_line: end_line,
+            end_column: end_column,
+        })
+    }
+
+    /// Line of the span with the span underlined, up to the end of the line
+    /// when it goes on past it
+    pub fn underline(&self, span: &Span) -> String {
+        let line = self.line_text(span.line).unwrap_or("");
+        let end = match span.end_line == span.line {
+            true => span.end_column,
+            false => line.chars().count() + 1,
+        };
+        let len = end.saturating_sub(span.column).max(1);
+        format!(" │ {}\n │ {}{}\n", line, " ".repeat(span.column - 1), "^".repeat(len))
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: vec![], bases: vec![] }
+    }
+
+    /// Add a file, whose locations start one past the end of the last
+    pub fn add(&mut self, name: &str, src: &str) -> FileId {
+        let base = match self.files.last() {
+            Some(f) => self.bases[self.bases.len() - 1] + f.src.len() + 1,
+            None => 0,
+        };
+        self.files.push(SourceFile::new(name, src));
+        self.bases.push(base);
+        self.files.len() - 1
+    }
+
+    /// Location of the start of a file
+    pub fn base(&self, id: FileId) -> usize {
+        self.bases[id]
+    }
+
+    /// File of a location, and the location within it
+    pub fn locate(&self, loc: usize) -> Option<(FileId, usize)> {
+        let id = match self.bases.binary_search(&loc) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        match loc - self.bases[id] <= self.files[id].src.len() {
+            true => Some((id, loc - self.bases[id])),
+            false => None,
+        }
+    }
+
+    /// File and line of a location
+    pub fn line(&self, loc: usize) -> Option<(FileId, usize)> {
+        let (id, off) = self.locate(loc)?;
+        Some((id, self.files[id].line(off)?))
+    }
+
+    /// File and span of a location, with the span's offset within the file
+    pub fn span(&self, loc: CLoc) -> Option<(FileId, Span)> {
+        let (id, l) = self.locate(loc.0)?;
+        let r = loc.1.saturating_sub(self.bases[id]).max(l).min(self.files[id].src.len());
+        Some((id, self.files[id].span((l, r))?))
+    }
+
+    pub fn file(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id)
+    }
+
+    /// File added with the name, if any
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files.iter().position(|f| f.name == name)
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+}
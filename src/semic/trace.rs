@@ -0,0 +1,111 @@
+use std::fmt::{Debug, Formatter, Error};
+use std::io::Write;
+
+use ast::CLoc;
+use env::SymVal;
+use json::Json;
+use source::{FileId, SourceMap};
+
+/// Writes every statement run and variable assigned as it happens, either
+/// for reading or as JSON lines. Lines in included files are given with
+/// the name of the file
+pub struct Tracer {
+    sources: SourceMap,
+    out: Box<dyn Write>,
+    json: bool,
+}
+
+impl Tracer {
+    pub fn new(sources: &SourceMap, out: Box<dyn Write>, json: bool) -> Tracer {
+        Tracer {
+            sources: sources.clone(),
+            out: out,
+            json: json,
+        }
+    }
+
+    /// Statement at the location, with the number of calls below main
+    pub fn stmt(&mut self, loc: CLoc, depth: usize) {
+        let (file, line) = self.sources.line(loc.0).unwrap_or((0, 0));
+        let text = self.text(loc);
+
+        // tracing must not stop the run, so write errors are ignored
+        let _ = if self.json {
+            let mut pairs = vec![("event", Json::str("stmt"))];
+            pairs.extend(self.file_pair(file));
+            pairs.push(("line", Json::Num(line as f64)));
+            pairs.push(("depth", Json::Num(depth as f64)));
+            pairs.push(("text", Json::str(&text)));
+            writeln!(self.out, "{}", Json::object(pairs))
+        } else {
+            let place = match file {
+                0 => line.to_string(),
+                _ => format!("{}:{}", self.name(file), line),
+            };
+            writeln!(self.out, "{:>5} | {}{}", place, indent(depth), text)
+        };
+    }
+
+    /// Value assigned to a variable, or one of its elements
+    pub fn assign(&mut self, name: &str, index: Option<usize>, val: &SymVal, loc: usize, depth: usize) {
+        let (file, line) = self.sources.line(loc).unwrap_or((0, 0));
+
+        let _ = if self.json {
+            let mut pairs = vec![("event", Json::str("assign"))];
+            pairs.extend(self.file_pair(file));
+            pairs.push(("line", Json::Num(line as f64)));
+            pairs.push(("depth", Json::Num(depth as f64)));
+            pairs.push(("name", Json::str(name)));
+            pairs.push(("index", Json::opt_num(index)));
+            pairs.push(("value", Json::str(&format!("{:?}", val))));
+            writeln!(self.out, "{}", Json::object(pairs))
+        } else {
+            let target = match index {
+                Some(i) => format!("{}[{}]", name, i),
+                None => name.to_owned(),
+            };
+            writeln!(self.out, "      | {}  {} = {:?}", indent(depth), target, val)
+        };
+    }
+
+    /// Source of a statement, up to the end of its first line
+    fn text(&self, loc: CLoc) -> String {
+        let (file, l) = match self.sources.locate(loc.0) {
+            Some(x) => x,
+            None => return String::new(),
+        };
+        let src = match self.sources.file(file) {
+            Some(x) => &x.src,
+            None => return String::new(),
+        };
+        let mut r = loc.1.saturating_sub(self.sources.base(file)).max(l).min(src.len());
+        // declarations and assignments end before their `;`
+        if src[r..].starts_with(';') {
+            r += 1;
+        }
+        let text = src.get(l..r).unwrap_or("");
+        text.lines().next().unwrap_or("").trim().to_owned()
+    }
+
+    fn name(&self, file: FileId) -> &str {
+        self.sources.file(file).map_or("", |f| f.name.as_str())
+    }
+
+    /// Name of an included file, for JSON
+    fn file_pair(&self, file: FileId) -> Option<(&'static str, Json)> {
+        match file {
+            0 => None,
+            _ => Some(("file", Json::str(self.name(file)))),
+        }
+    }
+}
+
+impl Debug for Tracer {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "Tracer {{ json: {} }}", self.json)
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
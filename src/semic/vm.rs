@@ -0,0 +1,512 @@
+use ast::*;
+use bytecode::{Op, Program, Var};
+use checker;
+use compiler;
+use engine::{self, Context, Flow};
+use env::{FuncTab, SymTab, SymVal};
+use error::CError;
+
+/// Storage of a variable, like an entry of the symbol table without its trace
+#[derive(Debug, Clone)]
+struct Slot {
+    val: Option<SymVal>,
+    /// Declared array size
+    size: Option<usize>,
+    /// Which array elements have been written, empty when all have
+    written: Vec<bool>,
+    /// Where the variable was declared, or passed as argument
+    decl: Option<CLoc>,
+}
+
+struct Frame {
+    /// Function running, where `None` is the entry code
+    func: Option<usize>,
+    pc: usize,
+    slots: Vec<Slot>,
+    /// Height of the value stack when called
+    base: usize,
+    /// Whether the caller uses the returned value
+    value: bool,
+    loc: CLoc,
+    /// Globals when called. Like in the tree-walker, which runs a call on a
+    /// copy of the global table, they are restored on return
+    globals: Option<Vec<Slot>>,
+}
+
+/// How the outermost frame stopped
+enum Exit {
+    Return(Option<SymVal>),
+    End,
+}
+
+struct Vm<'p, 'input: 'p> {
+    prog: &'p Program<'input>,
+    ctx: &'p Context,
+    stack: Vec<SymVal>,
+    frames: Vec<Frame>,
+    globals: Vec<Slot>,
+}
+
+impl Slot {
+    fn new(val: Option<SymVal>, size: Option<usize>, decl: Option<CLoc>) -> Slot {
+        Slot { val: val, size: size, written: vec![], decl: decl }
+    }
+
+    /// Length of array, either allocated or declared
+    fn len(&self) -> Option<usize> {
+        match self.val {
+            Some(SymVal::Array(ref a)) => Some(a.len()),
+            _ => self.size,
+        }
+    }
+
+    fn is_written(&self, i: usize) -> bool {
+        self.val.is_some() && (self.written.is_empty() || self.written.get(i) == Some(&true))
+    }
+
+    /// Assign a variable or an array element, allocating the array on its
+    /// first write
+    fn set(&mut self, name: &str, i: Option<usize>, val: SymVal) -> Result<(), String> {
+        let i = match i {
+            Some(i) => i,
+            None => {
+                self.val = Some(val);
+                self.written = vec![];
+                return Ok(());
+            },
+        };
+
+        match self.val {
+            Some(SymVal::Array(ref mut a)) => {
+                if i >= a.len() {
+                    return Err(format!("Index {} out of bounds for array '{}' of length {}", i, name, a.len()));
+                }
+                *a[i] = val;
+                if let Some(w) = self.written.get_mut(i) {
+                    *w = true;
+                }
+                return Ok(());
+            },
+            Some(ref x) => return Err(format!("Expected array, got {:?}", x)),
+            None => (),
+        }
+
+        let size = match self.size {
+            Some(size) if i < size => size,
+            Some(size) => return Err(format!("Index {} out of bounds for array '{}' of length {}", i, name, size)),
+            None => return Err(format!("Cannot index '{}', which has no size", name)),
+        };
+        let mut a = vec![Box::new(SymVal::Int(0)); size];
+        *a[i] = val;
+        self.val = Some(SymVal::Array(a));
+        self.written = (0..size).map(|j| j == i).collect();
+        Ok(())
+    }
+}
+
+pub fn run_prog<'input>(
+    ast: &'input CProg,
+    args: &'input Vec<String>,
+    ctx: &Context,
+) -> Result<Option<SymVal>, CError>
+{
+    let (vtab, global_symtab) = try!(checker::analyze_prog(ast));
+    let prog = try!(compiler::compile_prog(ast, &vtab, &global_symtab));
+    ctx.reset_usage();
+
+    let mut vm = Vm::new(&prog, ctx);
+
+    // allocate global arrays
+    try!(vm.run(None, vec![]));
+    vm.frames.pop();
+
+    // load command line args
+    let argc = SymVal::Int(args.len() as i32);
+    let argv = SymVal::Array(args.iter().map(|arg| {
+        let mut s: Vec<Box<SymVal>> = arg.chars().map(|c| Box::new(SymVal::Char(c))).collect();
+        // add null char
+        s.push(Box::new(SymVal::Char('\0')));
+        Box::new(SymVal::Array(s))
+    }).collect());
+
+    let main = prog.main.unwrap();
+    let mut slots = vec![Slot::new(None, None, None); prog.funcs[main].slots.len()];
+    slots[0] = Slot::new(Some(argc), None, None);
+    slots[1] = Slot::new(Some(argv), None, None);
+
+    ctx.enter_func("main");
+    let exit = try!(vm.run(Some(main), slots));
+    ctx.leave_func();
+
+    match exit {
+        Exit::Return(v) => Ok(v),
+        Exit::End => Ok(None),
+    }
+}
+
+/// Run a statement with the variables of the symbol tables, like
+/// `engine::run_stmt`, and return the tables with the updated values
+pub fn run_stmt<'input>(
+    stmt: &'input CStmt,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: SymTab<'input>,
+    local_symtab: SymTab<'input>,
+    ctx: &Context,
+) -> Result<(Option<Flow>, SymTab<'input>, SymTab<'input>), CError>
+{
+    let (prog, seeded, declared) = try!(compiler::compile_stmt(stmt, vtab, &global_symtab, &local_symtab));
+
+    let mut vm = Vm::new(&prog, ctx);
+    let slots = seed_slots(&prog, &seeded, &local_symtab);
+    let res = match try!(vm.run(None, slots)) {
+        Exit::Return(v) => Some(Flow::Return(v)),
+        Exit::End => None,
+    };
+
+    let (global_symtab, local_symtab) = vm.write_back(&seeded, &declared, global_symtab, local_symtab);
+    Ok((res, global_symtab, local_symtab))
+}
+
+/// Evaluate an expression with the variables of the symbol tables, like
+/// `engine::run_expr`
+pub fn run_expr<'input>(
+    expr: &'input CExpr,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: &'input SymTab<'input>,
+    local_symtab: &'input SymTab<'input>,
+    ctx: &Context,
+) -> Result<SymVal, CError>
+{
+    let (prog, seeded) = try!(compiler::compile_expr(expr, vtab, global_symtab, local_symtab));
+
+    let mut vm = Vm::new(&prog, ctx);
+    let slots = seed_slots(&prog, &seeded, local_symtab);
+    match try!(vm.run(None, slots)) {
+        Exit::Return(Some(v)) => Ok(v),
+        _ => Err(CError::UnknownError("expression left no value".to_owned())),
+    }
+}
+
+fn seed_slots<'input>(prog: &Program<'input>, seeded: &[(&'input str, usize)], tab: &SymTab<'input>) -> Vec<Slot> {
+    let mut slots = vec![Slot::new(None, None, None); prog.entry.slots.len()];
+    for &(name, i) in seeded.iter() {
+        let size = tab.get_type(name).and_then(|(_, s)| s);
+        let decl = tab.get_decl(name);
+        let mut slot = Slot::new(tab.get_val(name), size, decl);
+        if let Some(SymVal::Array(ref a)) = slot.val {
+            slot.written = (0..a.len()).map(|j| tab.is_written(name, Some(j))).collect();
+        }
+        slots[i] = slot;
+    }
+    slots
+}
+
+impl<'p, 'input> Vm<'p, 'input> {
+    fn new(prog: &'p Program<'input>, ctx: &'p Context) -> Vm<'p, 'input> {
+        Vm {
+            prog: prog,
+            ctx: ctx,
+            stack: vec![],
+            frames: vec![],
+            globals: prog.globals.iter().map(|g| Slot::new(g.val.clone(), g.size, g.decl)).collect(),
+        }
+    }
+
+    /// Put the values of the slots given by symbol tables back into them
+    fn write_back(
+        &self,
+        seeded: &[(&'input str, usize)],
+        declared: &[usize],
+        mut global_symtab: SymTab<'input>,
+        mut local_symtab: SymTab<'input>,
+    ) -> (SymTab<'input>, SymTab<'input>)
+    {
+        // the variables given, and those the code itself declared outside
+        // its blocks, which end with them
+        if let Some(frame) = self.frames.first() {
+            for (i, slot) in frame.slots.iter().enumerate() {
+                if (slot.decl.is_some() && declared.contains(&i)) || seeded.iter().any(|&(_, j)| j == i) {
+                    let (name, ref t) = self.prog.entry.slots[i];
+                    local_symtab.insert(name, t.clone(), slot.size, slot.val.clone(), slot.decl);
+                }
+            }
+        }
+        for (g, slot) in self.prog.globals.iter().zip(self.globals.iter()) {
+            global_symtab.insert(g.name, g.t.clone(), slot.size, slot.val.clone(), slot.decl);
+        }
+        (global_symtab, local_symtab)
+    }
+
+    fn name(&self, var: Var<'input>) -> &'input str {
+        match var {
+            Var::Local(i) => {
+                let frame = self.frames.last().unwrap();
+                match frame.func {
+                    Some(f) => self.prog.funcs[f].slots[i].0,
+                    None => self.prog.entry.slots[i].0,
+                }
+            },
+            Var::Global(i) => self.prog.globals[i].name,
+            Var::Undeclared(name) => name,
+        }
+    }
+
+    fn slot(&self, var: Var<'input>) -> Option<&Slot> {
+        match var {
+            Var::Local(i) => Some(&self.frames.last().unwrap().slots[i]),
+            Var::Global(i) => Some(&self.globals[i]),
+            Var::Undeclared(_) => None,
+        }
+    }
+
+    fn slot_mut(&mut self, var: Var<'input>) -> Option<&mut Slot> {
+        match var {
+            Var::Local(i) => Some(&mut self.frames.last_mut().unwrap().slots[i]),
+            Var::Global(i) => Some(&mut self.globals[i]),
+            Var::Undeclared(_) => None,
+        }
+    }
+
+    /// Attach the declaration site of a variable to an error, when known
+    fn with_decl_note(&self, err: CError, var: Var<'input>) -> CError {
+        match self.slot(var).and_then(|s| s.decl) {
+            Some(l) => err.with_note(format!("'{}' declared here", self.name(var)), l),
+            None => err,
+        }
+    }
+
+    /// Check array index against length, pointing at the declaration if out of bounds
+    fn check_bounds(&self, var: Var<'input>, i: i32, len: Option<usize>, loc: CLoc) -> Result<usize, CError> {
+        let id = self.name(var);
+        let err = match len {
+            Some(len) if i >= 0 && (i as usize) < len => return Ok(i as usize),
+            Some(len) => CError::RuntimeError(format!("Index {} out of bounds for array '{}' of length {}", i, id, len), loc),
+            None => CError::RuntimeError(format!("Cannot index '{}', which has no size", id), loc),
+        };
+
+        Err(self.with_decl_note(err, var))
+    }
+
+    fn uninit_read(&self, what: String, var: Var<'input>, loc: CLoc) -> CError {
+        let err = CError::RuntimeError(format!("Read of uninitialized {}", what), loc);
+        self.with_decl_note(err, var)
+    }
+
+    fn pop(&mut self) -> SymVal {
+        self.stack.pop().expect("value stack underflow")
+    }
+
+    fn load(&self, var: Var<'input>, loc: CLoc) -> Result<SymVal, CError> {
+        let id = self.name(var);
+        match self.slot(var) {
+            Some(&Slot { val: Some(ref v), .. }) => Ok(v.clone()),
+            Some(_) if self.ctx.sanitize_uninit => Err(self.uninit_read(format!("variable '{}'", id), var, loc)),
+            _ => Err(CError::RuntimeError(format!("Variable '{}' not initialized", id), loc)),
+        }
+    }
+
+    fn load_index(&self, var: Var<'input>, index: SymVal, loc: CLoc) -> Result<SymVal, CError> {
+        let i = match engine::promote(index) {
+            SymVal::Int(n) => n,
+            x => return Err(CError::RuntimeError(format!("Expected array index, got {:?}", x), loc)),
+        };
+
+        let id = self.name(var);
+        let slot = match self.slot(var) {
+            Some(slot) if slot.val.is_some() => slot,
+            Some(slot) if self.ctx.sanitize_uninit => {
+                try!(self.check_bounds(var, i, slot.len(), loc));
+                return Err(self.uninit_read(format!("element '{}[{}]'", id, i), var, loc));
+            },
+            _ => return Err(CError::RuntimeError(format!("Variable '{}' not initialized", id), loc)),
+        };
+        let a = match slot.val {
+            Some(SymVal::Array(ref a)) => a,
+            Some(ref x) => return Err(CError::RuntimeError(format!("Expected array, got {:?}", x), loc)),
+            None => unreachable!(),
+        };
+
+        // check bounds
+        let i = try!(self.check_bounds(var, i, Some(a.len()), loc));
+
+        if self.ctx.sanitize_uninit && !slot.is_written(i) {
+            return Err(self.uninit_read(format!("element '{}[{}]'", id, i), var, loc));
+        }
+
+        Ok((*a[i]).clone())
+    }
+
+    /// Run code until the frame it starts in returns
+    fn run(&mut self, func: Option<usize>, slots: Vec<Slot>) -> Result<Exit, CError> {
+        let base = self.stack.len();
+        self.frames.push(Frame { func: func, pc: 0, slots: slots, base: base, value: false, loc: (0, 0), globals: None });
+        let depth = self.frames.len();
+
+        loop {
+            let prog = self.prog;
+            let op = {
+                let frame = self.frames.last_mut().unwrap();
+                let code = match frame.func {
+                    Some(f) => &prog.funcs[f].code,
+                    None => &prog.entry.code,
+                };
+                frame.pc += 1;
+                &code[frame.pc - 1]
+            };
+
+            match *op {
+                Op::Const(ref v) => self.stack.push(v.clone()),
+                Op::Pop => { self.pop(); },
+                Op::Load(var, l) => {
+                    let v = try!(self.load(var, l));
+                    self.stack.push(v);
+                },
+                Op::LoadIndex(var, l) => {
+                    let index = self.pop();
+                    let v = try!(self.load_index(var, index, l));
+                    self.stack.push(v);
+                },
+                Op::Decl { var, sized, loc, size_loc } => {
+                    let size = match sized {
+                        true => match engine::promote(self.pop()) {
+                            SymVal::Int(i) if i < 0 => return Err(CError::RuntimeError(
+                                format!("Array '{}' declared with negative size {}", self.name(var), i), size_loc)),
+                            SymVal::Int(i) => Some(i as usize),
+                            _ => return Err(CError::RuntimeError("Array size must be int".to_owned(), size_loc)),
+                        },
+                        false => None,
+                    };
+                    if let Some(size) = size {
+                        try!(self.ctx.alloc(size, loc));
+                    }
+                    *self.slot_mut(var).unwrap() = Slot::new(None, size, Some(loc));
+                },
+                Op::CheckIndex(var, l) => {
+                    let i = match engine::promote(self.pop()) {
+                        SymVal::Int(i) => {
+                            let len = self.slot(var).and_then(|s| s.len());
+                            try!(self.check_bounds(var, i, len, l))
+                        },
+                        _ => return Err(CError::RuntimeError("Array index must be int".to_owned(), l)),
+                    };
+                    self.stack.push(SymVal::Int(i as i32));
+                },
+                Op::Store { var, indexed, ref t, loc, val_loc } => {
+                    let val = self.pop();
+                    let i = match indexed {
+                        true => match self.pop() {
+                            SymVal::Int(i) => Some(i as usize),
+                            _ => unreachable!(),
+                        },
+                        false => None,
+                    };
+                    if let Var::Undeclared(id) = var {
+                        return Err(CError::RuntimeError(format!("Variable '{:?}' not declared", id), loc));
+                    }
+                    let casted = try!(engine::cast(&val, val_loc, t));
+                    let id = self.name(var);
+                    let traced = match self.ctx.tracing() {
+                        true => Some(casted.clone()),
+                        false => None,
+                    };
+                    if let Err(s) = self.slot_mut(var).unwrap().set(id, i, casted) {
+                        return Err(CError::RuntimeError(s, loc));
+                    }
+                    if let Some(v) = traced {
+                        self.ctx.assign(id, i, &v, loc.0);
+                    }
+                },
+                Op::Operand(l) => {
+                    let v = try!(engine::operand(self.pop(), l));
+                    self.stack.push(v);
+                },
+                Op::BinOp(op, l) => {
+                    let v2 = self.pop();
+                    let v1 = self.pop();
+                    let v = try!(engine::bin_op(op, v1, v2, l, self.ctx));
+                    self.stack.push(v);
+                },
+                Op::UnOp(op, l) => {
+                    let v = try!(engine::un_op(op, self.pop(), l, self.ctx));
+                    self.stack.push(v);
+                },
+                Op::Cast(ref t, l) => {
+                    let v = try!(engine::cast(&self.pop(), l, t));
+                    self.stack.push(v);
+                },
+                Op::Truth(l) => {
+                    let b = try!(engine::to_bool(&self.pop(), l));
+                    self.stack.push(SymVal::Bool(b));
+                },
+                Op::CaseValue(l) => {
+                    let i = try!(engine::case_value(&self.pop(), l));
+                    self.stack.push(SymVal::Int(i));
+                },
+                Op::Switch { ref targets, default, end } => {
+                    let n = self.stack.len() - targets.len();
+                    let cases = self.stack.split_off(n);
+                    let val = self.pop();
+                    let target = match cases.iter().position(|cv| *cv == val) {
+                        Some(i) => targets[i],
+                        None => default.unwrap_or(end),
+                    };
+                    self.frames.last_mut().unwrap().pc = target;
+                },
+                Op::Jump(target) => self.frames.last_mut().unwrap().pc = target,
+                Op::JumpIfFalse(target) => {
+                    if let SymVal::Bool(false) = self.pop() {
+                        self.frames.last_mut().unwrap().pc = target;
+                    }
+                },
+                Op::Call { func, args, value, loc } => {
+                    try!(self.ctx.enter_call(self.prog.funcs[func].name, loc));
+                    let n = self.stack.len() - args;
+                    let vals = self.stack.split_off(n);
+                    let mut slots = vec![Slot::new(None, None, None); self.prog.funcs[func].slots.len()];
+                    for (i, v) in vals.into_iter().enumerate() {
+                        slots[i] = Slot::new(Some(v), None, Some(loc));
+                    }
+                    let base = self.stack.len();
+                    let globals = Some(self.globals.clone());
+                    self.frames.push(Frame {
+                        func: Some(func), pc: 0, slots: slots, base: base, value: value, loc: loc, globals: globals,
+                    });
+                },
+                Op::Return(has_value) => {
+                    let v = match has_value {
+                        true => Some(self.pop()),
+                        false => None,
+                    };
+                    let mut frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.base);
+                    if self.frames.len() < depth {
+                        self.frames.push(frame);
+                        return Ok(Exit::Return(v));
+                    }
+                    if let Some(globals) = frame.globals.take() {
+                        self.globals = globals;
+                    }
+                    self.ctx.leave_call();
+                    match (v, frame.value) {
+                        (Some(v), true) => self.stack.push(v),
+                        (None, true) => return Err(CError::RuntimeError("Expression returned void".to_owned(), frame.loc)),
+                        (_, false) => (),
+                    }
+                },
+                Op::End => return Ok(Exit::End),
+                Op::Print(ref fmt, l) => {
+                    let v = self.pop();
+                    self.ctx.print(&try!(engine::format_print(fmt, &v, l)));
+                },
+                Op::Fail(ref msg, l) => return Err(CError::RuntimeError(msg.clone(), l)),
+                Op::Step(l) => try!(self.ctx.step(l)),
+                Op::Count(l) => try!(self.ctx.count(l)),
+                Op::Branch(l) => {
+                    if let Some(&SymVal::Bool(b)) = self.stack.last() {
+                        self.ctx.branch(l, b);
+                    }
+                },
+            }
+        }
+    }
+}
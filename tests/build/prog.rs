@@ -0,0 +1,80 @@
+extern crate semic;
+
+use std::thread;
+
+use semic::ast::*;
+use semic::build::*;
+use semic::engine::{self, Context};
+use semic::env::SymVal;
+use semic::vm;
+
+/// Sum of the squares below `n`, counted in a global
+fn sum_squares(n: i32) -> CProg {
+    vec![
+        global(CType::Int, "total"),
+        func_decl(proto(Some(CType::Int), "sq", vec![(CType::Int, "x")])),
+        func(proto(Some(CType::Int), "main", vec![]), vec![
+            decl(CType::Int, "i"),
+            assign("total", int(0)),
+            assign("i", int(0)),
+            while_stmt(binop(COp::Lt, ident("i"), int(n)), block(vec![
+                assign("total", binop(COp::Add, ident("total"), call("sq", vec![ident("i")]))),
+                assign("i", binop(COp::Add, ident("i"), int(1))),
+            ])),
+            ret(Some(ident("total"))),
+        ]),
+        func(proto(Some(CType::Int), "sq", vec![(CType::Int, "x")]), vec![
+            ret(Some(binop(COp::Mul, ident("x"), ident("x")))),
+        ]),
+    ]
+}
+
+#[test]
+fn run_built() {
+    let ast = sum_squares(4);
+    let ctx = Context::new();
+
+    assert_eq!(Some(SymVal::Int(14)), engine::run_prog(&ast, "", &vec![], false, false, &ctx).unwrap());
+    assert_eq!(Some(SymVal::Int(14)), vm::run_prog(&ast, &vec![], &ctx).unwrap());
+}
+
+#[test]
+fn format_built() {
+    let ast = sum_squares(4);
+
+    let expected = "int total;\n\
+int sq(int x);\n\
+\n\
+int main(void) {\n\
+\x20   int i;\n\
+\x20   total = 0;\n\
+\x20   i = 0;\n\
+\x20   while (i < 4) {\n\
+\x20       total = total + sq(i);\n\
+\x20       i = i + 1;\n\
+\x20   }\n\
+\x20   return total;\n\
+}\n\
+\n\
+int sq(int x) {\n\
+\x20   return x * x;\n\
+}\n";
+
+    let formatted = semic::pretty::format_prog(&ast, None);
+    assert_eq!(expected, formatted);
+    assert_eq!(format!("{:?}", ast), format!("{:?}", semic::parse_prog(&formatted).unwrap()));
+}
+
+#[test]
+fn outlives_source() {
+    let ast = {
+        let program = String::from("int main () { return 2 * 21; }");
+        semic::parse_prog(&program).unwrap()
+    };
+
+    // owned, so it can move to another thread
+    let res = thread::spawn(move || {
+        vm::run_prog(&ast, &vec![], &Context::new()).unwrap()
+    }).join().unwrap();
+    assert_eq!(Some(SymVal::Int(42)), res);
+}
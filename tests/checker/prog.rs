@@ -17,3 +17,58 @@ fn prog_main() {
 
     assert!(actual.is_ok());
 }
+
+#[test]
+fn prog_ternary_mismatch() {
+    let ast = semic::parse_prog(r#"
+    int main () {
+        char *s;
+        int i;
+        i = 1 ? s : 2;
+        return 0 ? 1 : 2.0;
+    }
+    "#).unwrap();
+
+    let actual = semic::check_prog(&ast);
+
+    assert!(actual.is_err());
+}
+
+#[test]
+fn prog_break_outside_loop() {
+    let ast = semic::parse_prog(r#"int main () { break; }"#).unwrap();
+
+    let actual = semic::check_prog(&ast);
+
+    assert!(actual.is_err());
+}
+
+#[test]
+fn prog_switch_labels() {
+    let ast = semic::parse_prog(r#"
+    int main () {
+        int i;
+        switch (i) { case 1: break; case 1: default: default: break; }
+    }
+    "#).unwrap();
+
+    match semic::check_prog(&ast) {
+        Err(semic::error::CError::CheckerError(es)) => assert_eq!(2, es.len()),
+        _ => panic!("expected checker errors"),
+    }
+}
+
+#[test]
+fn prog_cast_pointer() {
+    let ast = semic::parse_prog(r#"
+    int main () {
+        char *s;
+        int i;
+        i = (int) s;
+    }
+    "#).unwrap();
+
+    let actual = semic::check_prog(&ast);
+
+    assert!(actual.is_err());
+}
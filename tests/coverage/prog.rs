@@ -0,0 +1,86 @@
+extern crate semic;
+
+use semic::ast::*;
+use semic::build::*;
+use semic::source::SourceMap;
+
+use engine::run_prog_reported;
+
+#[test]
+fn lcov() {
+    let prog = "int unused(int n) {\n  if (n) {\n    return 1;\n  }\n  return 0;\n}\nint main () {\n  int i;\n  i = 0;\n  while (i < 2) {\n    if (i == 5) { return 5; }\n    i = i + 1;\n  }\n  do {\n    i = i - 1;\n  } while (i > 0);\n  return i;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut sources = SourceMap::new();
+    sources.add("foo.semic", prog);
+
+    let expected = "TN:\n\
+SF:foo.semic\n\
+FN:1,unused\n\
+FN:7,main\n\
+FNDA:0,unused\n\
+FNDA:1,main\n\
+FNF:2\n\
+FNH:1\n\
+BRDA:2,0,0,-\n\
+BRDA:2,0,1,-\n\
+BRDA:10,1,0,2\n\
+BRDA:10,1,1,1\n\
+BRDA:11,2,0,0\n\
+BRDA:11,2,1,2\n\
+BRDA:14,3,0,1\n\
+BRDA:14,3,1,1\n\
+BRF:8\n\
+BRH:5\n\
+DA:2,0\n\
+DA:3,0\n\
+DA:5,0\n\
+DA:8,1\n\
+DA:9,1\n\
+DA:10,3\n\
+DA:11,2\n\
+DA:12,2\n\
+DA:14,2\n\
+DA:15,2\n\
+DA:17,1\n\
+LF:11\n\
+LH:8\n\
+end_of_record\n";
+
+    let (res, lcov) = run_prog_reported(&ast, prog, |c| c.coverage = true, |c| c.coverage().lcov(&ast, &sources));
+    assert!(res.is_ok());
+    assert_eq!(expected, lcov);
+}
+
+#[test]
+fn declaration_with_initializer_once() {
+    let prog = "int main () {\n  int i = 0, n = 0;\n  while (i < 2) {\n    int j = i;\n    i = i + 1;\n  }\n  return i;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut sources = SourceMap::new();
+    sources.add("foo.semic", prog);
+
+    let expected = vec!["DA:2,1", "DA:3,3", "DA:4,2", "DA:5,2", "DA:7,1"];
+
+    let (res, lcov) = run_prog_reported(&ast, prog, |c| c.coverage = true, |c| c.coverage().lcov(&ast, &sources));
+    assert!(res.is_ok());
+    assert_eq!(expected, lcov.lines().filter(|l| l.starts_with("DA:")).collect::<Vec<_>>());
+}
+
+#[test]
+fn built_statements_all_counted() {
+    // built nodes all share the location (0, 0)
+    let ast = vec![
+        func(proto(Some(CType::Int), "main", vec![]), vec![
+            decl_init(CType::Int, "i", int(1)),
+            decl(CType::Int, "j"),
+            assign("j", int(2)),
+            assign("i", binop(COp::Add, ident("i"), ident("j"))),
+            ret(Some(ident("i"))),
+        ]),
+    ];
+
+    let (res, count) = run_prog_reported(&ast, "", |c| c.coverage = true, |c| c.coverage().stmts.get(&0).cloned());
+    assert_eq!("Ok(Some(3))", format!("{:?}", res));
+    assert_eq!(Some(5), count);
+}
@@ -0,0 +1,129 @@
+extern crate semic;
+
+mod session;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+
+use semic::dap;
+use semic::json::Json;
+use semic::lsp;
+
+/// Output shared with the test after the adapter is handed it
+#[derive(Clone)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Client writing the requests of a debugging session up front, as an
+/// editor would on the adapter's stdin
+pub struct Client {
+    input: Vec<u8>,
+    seq: usize,
+}
+
+impl Client {
+    pub fn new() -> Client {
+        let mut client = Client { input: vec![], seq: 0 };
+        client.request("initialize", r#"{"adapterID": "semic"}"#);
+        client
+    }
+
+    /// Client that launched a program, written to a file of the name in the
+    /// temporary directory, and set breakpoints on lines of it
+    pub fn launch(name: &str, program: &str, stop_on_entry: bool, lines: &[usize]) -> (Client, String) {
+        let path = write_program(name, program);
+        let mut client = Client::new();
+        client.request("launch", &format!(r#"{{"program": {}, "stopOnEntry": {}}}"#, Json::str(&path), stop_on_entry));
+        client.breakpoints(&path, lines);
+        client.request("configurationDone", "{}");
+        (client, path)
+    }
+
+    pub fn request(&mut self, command: &str, args: &str) -> usize {
+        self.seq += 1;
+        let body = format!(r#"{{"seq": {}, "type": "request", "command": "{}", "arguments": {}}}"#, self.seq, command, args);
+        self.input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes());
+        self.seq
+    }
+
+    pub fn breakpoints(&mut self, path: &str, lines: &[usize]) -> usize {
+        let bs: Vec<String> = lines.iter().map(|l| format!(r#"{{"line": {}}}"#, l)).collect();
+        self.request("setBreakpoints", &format!(r#"{{"source": {{"path": {}}}, "breakpoints": [{}]}}"#, Json::str(path), bs.join(", ")))
+    }
+
+    /// Run the adapter on the requests, giving what it sent back
+    pub fn run(self) -> Vec<Json> {
+        let output = Buffer(Rc::new(RefCell::new(vec![])));
+        dap::serve(Box::new(Cursor::new(self.input)), Box::new(output.clone())).unwrap();
+
+        let output = output.0.borrow();
+        let mut msgs = vec![];
+        let mut rest = &output[..];
+        while let Some(msg) = lsp::read_message(&mut rest).unwrap() {
+            msgs.push(Json::parse(&msg).unwrap());
+        }
+        msgs
+    }
+}
+
+pub fn write_program(name: &str, program: &str) -> String {
+    let path = std::env::temp_dir().join(format!("semic-dap-{}", name));
+    File::create(&path).and_then(|mut f| f.write_all(program.as_bytes())).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Response to a request
+pub fn response(msgs: &[Json], seq: usize) -> &Json {
+    msgs.iter()
+        .find(|m| m.get("type").and_then(Json::as_str) == Some("response") &&
+              m.get("request_seq") == Some(&Json::Num(seq as f64)))
+        .expect("no response")
+}
+
+/// Body of the successful response to a request
+pub fn body(msgs: &[Json], seq: usize) -> &Json {
+    let res = response(msgs, seq);
+    assert_eq!(Some(&Json::Bool(true)), res.get("success"), "{}", res);
+    res.get("body").expect("no body")
+}
+
+/// Bodies of the events of a kind, in order
+pub fn events<'a>(msgs: &'a [Json], event: &str) -> Vec<&'a Json> {
+    msgs.iter()
+        .filter(|m| m.get("event").and_then(Json::as_str) == Some(event))
+        .map(|m| m.get("body").unwrap())
+        .collect()
+}
+
+/// Name and line of each frame of a stack trace, innermost first
+pub fn frames(body: &Json) -> Vec<(String, usize)> {
+    match body.get("stackFrames") {
+        Some(&Json::Array(ref fs)) => fs.iter().map(|f| (
+            f.get("name").and_then(Json::as_str).unwrap().to_owned(),
+            f.get("line").and_then(Json::as_num).unwrap() as usize,
+        )).collect(),
+        _ => vec![],
+    }
+}
+
+/// Name and value of each variable of a scope
+pub fn variables(body: &Json) -> Vec<(String, String)> {
+    match body.get("variables") {
+        Some(&Json::Array(ref vs)) => vs.iter().map(|v| (
+            v.get("name").and_then(Json::as_str).unwrap().to_owned(),
+            v.get("value").and_then(Json::as_str).unwrap().to_owned(),
+        )).collect(),
+        _ => vec![],
+    }
+}
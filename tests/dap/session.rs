@@ -0,0 +1,196 @@
+use semic::json::Json;
+
+use super::{body, events, frames, response, variables, write_program, Client};
+
+const PROG: &str = "int total;
+
+int square(int x) {
+    int y;
+    y = x * x;
+    return y;
+}
+
+int main(void) {
+    int a;
+    a = 3;
+    total = square(a);
+    printf(\"%d\\n\", total);
+    return 0;
+}
+";
+
+fn reasons(msgs: &[Json]) -> Vec<&str> {
+    events(msgs, "stopped").iter().map(|e| e.get("reason").and_then(Json::as_str).unwrap()).collect()
+}
+
+fn exit_code(msgs: &[Json]) -> Option<f64> {
+    events(msgs, "exited").first().and_then(|e| e.get("exitCode")).and_then(Json::as_num)
+}
+
+#[test]
+fn stop_on_entry() {
+    let (mut client, path) = Client::launch("entry.semic", PROG, true, &[]);
+    let trace = client.request("stackTrace", r#"{"threadId": 1}"#);
+    client.request("continue", r#"{"threadId": 1}"#);
+    client.request("disconnect", "{}");
+    let msgs = client.run();
+
+    assert_eq!(Some(&Json::Bool(true)), body(&msgs, 1).get("supportsConfigurationDoneRequest"));
+    assert_eq!(1, events(&msgs, "initialized").len());
+    assert_eq!(vec!["entry"], reasons(&msgs));
+
+    let trace = body(&msgs, trace);
+    assert_eq!(vec![("main".to_owned(), 10)], frames(trace));
+    let source = trace.get("stackFrames").and_then(|fs| match *fs {
+        Json::Array(ref fs) => fs[0].get("source"),
+        _ => None,
+    }).unwrap();
+    assert_eq!(Some(path.as_str()), source.get("path").and_then(Json::as_str));
+
+    let output: Vec<&str> = events(&msgs, "output").iter().map(|e| e.get("output").and_then(Json::as_str).unwrap()).collect();
+    assert_eq!(vec!["9\n"], output);
+    assert_eq!(Some(0.0), exit_code(&msgs));
+    assert_eq!(1, events(&msgs, "terminated").len());
+}
+
+#[test]
+fn step_over_in_and_out() {
+    let (mut client, _) = Client::launch("step.semic", PROG, true, &[]);
+    let mut traces = vec![];
+    for command in ["next", "next", "stepIn", "next", "stepOut", "next"].iter() {
+        client.request(command, r#"{"threadId": 1}"#);
+        traces.push(client.request("stackTrace", r#"{"threadId": 1}"#));
+    }
+    client.request("continue", r#"{"threadId": 1}"#);
+    let msgs = client.run();
+
+    let main = |line| ("main".to_owned(), line);
+    let square = |line| vec![("square".to_owned(), line), main(12)];
+    assert_eq!(vec![main(11)], frames(body(&msgs, traces[0])));
+    assert_eq!(vec![main(12)], frames(body(&msgs, traces[1])));
+    assert_eq!(square(4), frames(body(&msgs, traces[2])));
+    assert_eq!(square(5), frames(body(&msgs, traces[3])));
+    assert_eq!(vec![main(13)], frames(body(&msgs, traces[4])));
+    assert_eq!(vec![main(14)], frames(body(&msgs, traces[5])));
+    assert_eq!(vec!["entry", "step", "step", "step", "step", "step", "step"], reasons(&msgs));
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let (mut client, _) = Client::launch("next.semic", PROG, true, &[]);
+    client.request("next", r#"{"threadId": 1}"#);
+    client.request("next", r#"{"threadId": 1}"#);
+    client.request("next", r#"{"threadId": 1}"#);
+    let trace = client.request("stackTrace", r#"{"threadId": 1}"#);
+    client.request("disconnect", "{}");
+    let msgs = client.run();
+
+    assert_eq!(vec![("main".to_owned(), 13)], frames(body(&msgs, trace)));
+}
+
+#[test]
+fn breakpoints() {
+    let (mut client, path) = Client::launch("breakpoints.semic", PROG, false, &[5, 99]);
+    let trace = client.request("stackTrace", r#"{"threadId": 1}"#);
+    let scopes = client.request("scopes", r#"{"frameId": 2}"#);
+    let locals = client.request("variables", r#"{"variablesReference": 3}"#);
+    let caller = client.request("variables", r#"{"variablesReference": 2}"#);
+    let globals = client.request("variables", r#"{"variablesReference": 1}"#);
+    // no longer stops at line 5
+    let cleared = client.breakpoints(&path, &[]);
+    client.request("continue", r#"{"threadId": 1}"#);
+    let msgs = client.run();
+
+    let verified: Vec<Option<&Json>> = match body(&msgs, 3).get("breakpoints") {
+        Some(&Json::Array(ref bs)) => bs.iter().map(|b| b.get("verified")).collect(),
+        _ => vec![],
+    };
+    assert_eq!(vec![Some(&Json::Bool(true)), Some(&Json::Bool(false))], verified);
+
+    assert_eq!(vec!["breakpoint"], reasons(&msgs));
+    assert_eq!(vec![("square".to_owned(), 5), ("main".to_owned(), 12)], frames(body(&msgs, trace)));
+
+    let names: Vec<&str> = match body(&msgs, scopes).get("scopes") {
+        Some(&Json::Array(ref ss)) => ss.iter().map(|s| s.get("name").and_then(Json::as_str).unwrap()).collect(),
+        _ => vec![],
+    };
+    assert_eq!(vec!["Locals", "Globals"], names);
+
+    let var = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+    assert_eq!(vec![var("x", "3"), var("y", "N\\A")], variables(body(&msgs, locals)));
+    assert!(variables(body(&msgs, caller)).contains(&var("a", "3")));
+    assert_eq!(vec![var("total", "N\\A")], variables(body(&msgs, globals)));
+
+    assert!(response(&msgs, cleared).get("success") == Some(&Json::Bool(true)));
+    assert_eq!(Some(0.0), exit_code(&msgs));
+}
+
+#[test]
+fn breakpoints_in_included_files() {
+    write_program("square.h", "int square(int x) {\n    return x * x;\n}\n");
+    let prog = "#include \"semic-dap-square.h\"\n\nint main(void) {\n    return square(4);\n}\n";
+    let path = write_program("include.semic", prog);
+    let header = path.replace("semic-dap-include.semic", "semic-dap-square.h");
+
+    let mut client = Client::new();
+    client.request("launch", &format!(r#"{{"program": {}}}"#, Json::str(&path)));
+    client.breakpoints(&header, &[2]);
+    client.request("configurationDone", "{}");
+    let trace = client.request("stackTrace", r#"{"threadId": 1}"#);
+    client.request("continue", r#"{"threadId": 1}"#);
+    let msgs = client.run();
+
+    assert_eq!(vec!["breakpoint"], reasons(&msgs));
+    assert_eq!(vec![("square".to_owned(), 2), ("main".to_owned(), 4)], frames(body(&msgs, trace)));
+}
+
+#[test]
+fn errors_are_output() {
+    let prog = "int main(void) {\n    int x;\n    x = 0;\n    return 1 / x;\n}\n";
+    let (client, _) = Client::launch("errors.semic", prog, false, &[]);
+    let msgs = client.run();
+
+    let stderr: Vec<&str> = events(&msgs, "output").iter()
+        .filter(|e| e.get("category").and_then(Json::as_str) == Some("stderr"))
+        .map(|e| e.get("output").and_then(Json::as_str).unwrap())
+        .collect();
+    assert_eq!(1, stderr.len());
+    assert!(stderr[0].starts_with("Run-time error: line 4:"), "{}", stderr[0]);
+    assert_eq!(Some(1.0), exit_code(&msgs));
+}
+
+#[test]
+fn requests_out_of_place() {
+    let mut client = Client::new();
+    let missing = client.request("launch", r#"{"program": "/nonexistent/prog.semic"}"#);
+    let trace = client.request("stackTrace", r#"{"threadId": 1}"#);
+    let unknown = client.request("evaluate", r#"{"expression": "x"}"#);
+    client.request("configurationDone", "{}");
+    client.request("disconnect", "{}");
+    let msgs = client.run();
+
+    let failed = |seq| {
+        let res = response(&msgs, seq);
+        assert_eq!(Some(&Json::Bool(false)), res.get("success"));
+        res.get("message").and_then(Json::as_str).unwrap().to_owned()
+    };
+    assert!(failed(missing).starts_with("Failed opening file '/nonexistent/prog.semic'"));
+    assert_eq!("Program is not stopped", failed(trace));
+    assert_eq!("Unknown command 'evaluate'", failed(unknown));
+    // nothing was launched to run
+    assert!(events(&msgs, "initialized").is_empty());
+    assert!(events(&msgs, "exited").is_empty());
+}
+
+#[test]
+fn disconnect_while_stopped() {
+    let (mut client, _) = Client::launch("disconnect.semic", PROG, true, &[]);
+    let disconnect = client.request("disconnect", "{}");
+    client.request("continue", r#"{"threadId": 1}"#);
+    let msgs = client.run();
+
+    assert_eq!(Some(&Json::Bool(true)), response(&msgs, disconnect).get("success"));
+    assert_eq!(disconnect, msgs.iter().filter(|m| m.get("type").and_then(Json::as_str) == Some("response")).count());
+    assert!(events(&msgs, "output").is_empty());
+    assert!(events(&msgs, "exited").is_empty());
+}
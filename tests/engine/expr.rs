@@ -2,6 +2,7 @@ extern crate semic;
 
 use semic::engine;
 use semic::env::{FuncTab, SymTab, SymVal};
+use semic::error::CError;
 
 #[test]
 fn bin_op_int() {
@@ -11,7 +12,7 @@ fn bin_op_int() {
 
     let ast = semic::parse_expr(r#" 1 + 1 "#).unwrap();
 
-    let actual = engine::run_expr(&ast, &vtab, &global, &local, &None);
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
 
     let expected = SymVal::Int(2);
 
@@ -27,7 +28,7 @@ fn bin_op_float() {
 
     let ast = semic::parse_expr(r#" 1.0 + 0.1 "#).unwrap();
 
-    let actual = engine::run_expr(&ast, &vtab, &global, &local, &None);
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
 
     let expected = SymVal::Float(1.1);
 
@@ -43,7 +44,7 @@ fn rel_op() {
 
     let ast = semic::parse_expr(r#" 1 == 1 "#).unwrap();
 
-    let actual = engine::run_expr(&ast, &vtab, &global, &local, &None);
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
 
     let expected = SymVal::Bool(true);
 
@@ -59,7 +60,7 @@ fn log_op() {
 
     let ast = semic::parse_expr(r#" (1 == 1) && (1 != 0) "#).unwrap();
 
-    let actual = engine::run_expr(&ast, &vtab, &global, &local, &None);
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
 
     let expected = SymVal::Bool(true);
 
@@ -67,3 +68,160 @@ fn log_op() {
     assert!(actual.is_ok());
     assert_eq!(expected, actual.unwrap());
 }
+
+#[test]
+fn ternary_op() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+
+    // untaken branch is never evaluated
+    let ast = semic::parse_expr(r#" 1 < 2 ? 3 : undefined "#).unwrap();
+
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
+
+    let expected = SymVal::Int(3);
+
+    assert!(actual.is_ok());
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn comma_op() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+
+    let ast = semic::parse_expr(r#" (1, 2.0) "#).unwrap();
+
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
+
+    let expected = SymVal::Float(2.0);
+
+    assert!(actual.is_ok());
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn cast_char_int() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+
+    let cases = vec![
+        (r#" (int) 'A' "#, SymVal::Int(65)),
+        (r#" (char) 66 "#, SymVal::Char('B')),
+        (r#" (char) 322 "#, SymVal::Char('B')),
+        (r#" (char) 67.9 "#, SymVal::Char('C')),
+        (r#" (float) 'a' "#, SymVal::Float(97.0)),
+        (r#" 'a' + 1 "#, SymVal::Int(98)),
+        (r#" 1 / 2.0 "#, SymVal::Float(0.5)),
+        (r#" (1 < 2) + 1 "#, SymVal::Int(2)),
+        (r#" !5 "#, SymVal::Bool(false)),
+    ];
+
+    for (prog, expected) in cases {
+        let ast = semic::parse_expr(prog).unwrap();
+
+        let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
+
+        assert!(actual.is_ok());
+        assert_eq!(expected, actual.unwrap());
+    }
+}
+
+#[test]
+fn log_op_or() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+
+    let cases = vec![
+        (r#" 1 || 0 "#, SymVal::Bool(true)),
+        (r#" 0 || 2.5 "#, SymVal::Bool(true)),
+        (r#" 0 || 0 "#, SymVal::Bool(false)),
+        (r#" (1 < 2) || (2 < 1) "#, SymVal::Bool(true)),
+        (r#" 1 || undefined "#, SymVal::Bool(true)),
+        (r#" 0 && undefined "#, SymVal::Bool(false)),
+    ];
+
+    for (prog, expected) in cases {
+        let ast = semic::parse_expr(prog).unwrap();
+
+        let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
+
+        assert!(actual.is_ok());
+        assert_eq!(expected, actual.unwrap());
+    }
+}
+
+#[test]
+fn overflow_wrap() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+
+    let ast = semic::parse_expr(r#" 2147483647 + 1 "#).unwrap();
+
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
+
+    let expected = SymVal::Int(-2147483648);
+
+    assert!(actual.is_ok());
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn overflow_trap() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+    let mut context = engine::Context::new();
+    context.overflow = engine::Overflow::Trap;
+
+    for prog in vec![r#" 2147483647 + 1 "#, r#" 0 - 2147483647 - 2 "#, r#" 65536 * 65536 "#] {
+        let ast = semic::parse_expr(prog).unwrap();
+
+        let actual = super::run_expr(&ast, &vtab, &global, &local, &context);
+
+        match actual {
+            Err(CError::RuntimeError(msg, _)) => assert!(msg.starts_with("Signed overflow")),
+            x => panic!("expected overflow error, got {:?}", x),
+        }
+    }
+}
+
+#[test]
+fn overflow_trap_neg() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+    let mut context = engine::Context::new();
+    context.overflow = engine::Overflow::Trap;
+
+    let ast = semic::parse_expr(r#" -(0 - 2147483647 - 1) "#).unwrap();
+
+    match super::run_expr(&ast, &vtab, &global, &local, &context) {
+        Err(CError::RuntimeError(msg, _)) => assert_eq!("Signed overflow in `-(-2147483648)`", msg),
+        x => panic!("expected overflow error, got {:?}", x),
+    }
+}
+
+#[test]
+fn div_by_zero() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let local = SymTab::new();
+
+    let ast = semic::parse_expr(r#" 1 + 7 / (2 - 2) "#).unwrap();
+
+    let actual = super::run_expr(&ast, &vtab, &global, &local, &engine::Context::new());
+
+    match actual {
+        Err(CError::RuntimeError(msg, loc)) => {
+            assert!(msg.starts_with("Division by zero"));
+            assert_eq!((5, 16), loc);
+        },
+        x => panic!("expected division error, got {:?}", x),
+    }
+}
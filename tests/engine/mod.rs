@@ -3,3 +3,75 @@ extern crate semic;
 mod prog;
 mod expr;
 mod stmt;
+
+use std::fmt::Debug;
+
+use semic::ast::{CProg, CStmt, CExpr};
+use semic::engine::{self, Context, Flow};
+use semic::env::{FuncTab, SymTab, SymVal};
+use semic::error::CError;
+use semic::vm;
+
+// run on both backends, which have to agree
+
+pub fn run_prog<'input>(
+    ast: &'input CProg,
+    program: &'input str,
+    args: &'input Vec<String>,
+    ctx: &Context,
+) -> Result<Option<SymVal>, CError>
+{
+    let tree = engine::run_prog(ast, program, args, false, false, ctx);
+    let vm = vm::run_prog(ast, args, ctx);
+    assert_eq!(format!("{:?}", tree), format!("{:?}", vm), "backends disagree");
+    tree
+}
+
+/// Result and report from running a program on both backends, each with a
+/// new context given to `setup`, where the report is what `report` reads
+/// from the context afterwards and has to agree too
+pub fn run_prog_reported<S, R, T>(ast: &CProg, program: &str, setup: S, report: R) -> (Result<Option<SymVal>, CError>, T)
+    where S: Fn(&mut Context), R: Fn(&Context) -> T, T: PartialEq + Debug
+{
+    let mut context = Context::new();
+    setup(&mut context);
+    let tree = engine::run_prog(ast, program, &vec![], false, false, &context);
+    let tree_report = report(&context);
+
+    let mut context = Context::new();
+    setup(&mut context);
+    let vm = vm::run_prog(ast, &vec![], &context);
+    let vm_report = report(&context);
+
+    assert_eq!(format!("{:?}", tree), format!("{:?}", vm), "backends disagree");
+    assert_eq!(tree_report, vm_report, "backends report differently");
+    (tree, tree_report)
+}
+
+pub fn run_expr<'input>(
+    expr: &'input CExpr,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: &'input SymTab<'input>,
+    local_symtab: &'input SymTab<'input>,
+    ctx: &Context,
+) -> Result<SymVal, CError>
+{
+    let tree = engine::run_expr(expr, vtab, global_symtab, local_symtab, &None, ctx);
+    let vm = vm::run_expr(expr, vtab, global_symtab, local_symtab, ctx);
+    assert_eq!(format!("{:?}", tree), format!("{:?}", vm), "backends disagree");
+    tree
+}
+
+/// Result and local symbol table from each backend
+pub fn run_stmt<'input>(
+    stmt: &'input CStmt,
+    vtab: &'input FuncTab<'input>,
+    global_symtab: SymTab<'input>,
+    local_symtab: SymTab<'input>,
+    ctx: &Context,
+) -> Vec<(Option<Flow>, SymTab<'input>)>
+{
+    let (tree, _, tree_tab, _) = engine::run_stmt(stmt, vtab, global_symtab.clone(), local_symtab.clone(), None, ctx).unwrap();
+    let (vm, _, vm_tab) = vm::run_stmt(stmt, vtab, global_symtab, local_symtab, ctx).unwrap();
+    vec![(tree, tree_tab), (vm, vm_tab)]
+}
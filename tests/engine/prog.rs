@@ -2,6 +2,7 @@ extern crate semic;
 
 use semic::engine;
 use semic::env::SymVal;
+use semic::error::CError;
 
 #[test]
 fn empty() {
@@ -12,7 +13,7 @@ fn empty() {
 
     let ast = semic::parse_prog(prog).unwrap();
 
-    let actual = engine::run_prog(&ast, prog, &vec![], false, false);
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
     assert!(actual.is_ok());
 
     assert_eq!(None, actual.unwrap());
@@ -28,7 +29,7 @@ fn int() {
 
     let ast = semic::parse_prog(prog).unwrap();
 
-    let actual = engine::run_prog(&ast, prog, &vec![], false, false);
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
     assert!(actual.is_ok());
 
     assert_eq!(Some(SymVal::Int(0)), actual.unwrap());
@@ -44,8 +45,542 @@ fn argc() {
 
     let ast = semic::parse_prog(prog).unwrap();
 
-    let actual = engine::run_prog(&ast, prog, &vec![], false, false);
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
     assert!(actual.is_ok());
 
     assert_eq!(Some(SymVal::Int(0)), actual.unwrap());
 }
+
+#[test]
+fn for_comma() {
+    let prog = r#"
+    int main () {
+        int i, j, n;
+        n = 0;
+        for (i = 0, j = 9; i < j; i++, j--) {
+            n = n + (i < 2 ? 1 : 10);
+        }
+        return n;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(32)), actual.unwrap());
+}
+
+#[test]
+fn do_while() {
+    let prog = r#"
+    int main () {
+        int i = 10;
+        do {
+            i++;
+        } while (i < 5);
+        return i;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(11)), actual.unwrap());
+}
+
+#[test]
+fn conditions() {
+    // every condition takes any number, not only ints
+    let prog = r#"
+    int main () {
+        float f = 0.5;
+        char c = 'a';
+        int n = 0;
+        if (f)
+            n = n + 1;
+        while (c) {
+            n = n + 10;
+            c = c - 'a';
+        }
+        do {
+            n = n + 100;
+        } while (f - 0.5);
+        return f ? n + 1000 : n;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok(), "{:?}", actual);
+
+    assert_eq!(Some(SymVal::Int(1111)), actual.unwrap());
+}
+
+#[test]
+fn switch_fallthrough() {
+    let prog = r#"
+    int f(int x) {
+        int n = 0;
+        switch (x) {
+        case 1:
+            n = n + 1;
+        case 2:
+            n = n + 10;
+            break;
+        default:
+            n = 100;
+        case 3:
+            n = n + 1000;
+        }
+        return n;
+    }
+
+    int main () {
+        return f(1) + f(2) + f(3) + f(4);
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(11 + 10 + 1000 + 1100)), actual.unwrap());
+}
+
+#[test]
+fn while_break() {
+    let prog = r#"
+    int main () {
+        int i;
+        for (i = 0; i < 10; i++) {
+            if (i == 3)
+                break;
+        }
+        return i;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(3)), actual.unwrap());
+}
+
+#[test]
+fn char_arith() {
+    let prog = r#"
+    char main () {
+        char c = 'a';
+        c = c + 2;
+        return c;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Char('c')), actual.unwrap());
+}
+
+#[test]
+fn short_circuit_guard() {
+    let prog = r#"
+    int main () {
+        int a[3];
+        int p = 3;
+        a[0] = 1;
+        if (p < 3 && a[p] > 0)
+            return 1;
+        if (p >= 3 || a[p] > 0)
+            return 2;
+        return 0;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(2)), actual.unwrap());
+}
+
+#[test]
+fn short_circuit_side_effects() {
+    let prog = r#"
+    int boom () {
+        int a[1];
+        return a[5];
+    }
+
+    int main () {
+        int x = 0 && boom();
+        int y = 1 || boom();
+        return x + y;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(1)), actual.unwrap());
+
+    // right operand runs when the left does not decide
+    let prog2 = r#"
+    int boom () {
+        int a[1];
+        return a[5];
+    }
+
+    int main () {
+        return 1 && boom();
+    }
+    "#;
+
+    let ast2 = semic::parse_prog(prog2).unwrap();
+
+    let actual2 = super::run_prog(&ast2, prog2, &vec![], &engine::Context::new());
+    assert!(actual2.is_err());
+}
+
+#[test]
+fn index_out_of_bounds() {
+    let progs = vec![
+        // negative read
+        (r#"
+    int main () {
+        int a[3];
+        int i = 0 - 1;
+        a[0] = 1;
+        return a[i];
+    }
+    "#, -1),
+        // write past end
+        (r#"
+    int main () {
+        int a[3];
+        a[3] = 1;
+        return 0;
+    }
+    "#, 3),
+        // write before anything is allocated
+        (r#"
+    int main () {
+        int a[3];
+        a[0 - 2] = 1;
+        return 0;
+    }
+    "#, -2),
+    ];
+
+    for (prog, index) in progs {
+        let ast = semic::parse_prog(prog).unwrap();
+
+        let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+
+        match actual {
+            Err(CError::WithNote(err, note, decl)) => {
+                match *err {
+                    CError::RuntimeError(msg, _) =>
+                        assert_eq!(format!("Index {} out of bounds for array 'a' of length 3", index), msg),
+                    x => panic!("expected runtime error, got {:?}", x),
+                }
+                assert_eq!("'a' declared here", note);
+                assert_eq!(prog.find("int a[3]"), Some(decl.0));
+            },
+            x => panic!("expected bounds error, got {:?}", x),
+        }
+    }
+}
+
+#[test]
+fn negative_array_size() {
+    let prog = r#"
+    int main () {
+        int a[0 - 1];
+        return 0;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+
+    match actual {
+        Err(CError::RuntimeError(msg, _)) => assert_eq!("Array 'a' declared with negative size -1", msg),
+        x => panic!("expected size error, got {:?}", x),
+    }
+}
+
+#[test]
+fn global_array() {
+    let prog = r#"
+    int g[2];
+
+    int main () {
+        g[1] = 7;
+        return g[1];
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(7)), actual.unwrap());
+}
+
+#[test]
+fn sanitize_uninit() {
+    let progs = vec![
+        (r#"
+    int main () {
+        int a[3];
+        a[0] = 1;
+        return a[0] + a[2];
+    }
+    "#, "Read of uninitialized element 'a[2]'", "a[2]", "int a[3]"),
+        (r#"
+    int main () {
+        int a[3];
+        return a[1];
+    }
+    "#, "Read of uninitialized element 'a[1]'", "a[1]", "int a[3]"),
+        (r#"
+    int x;
+    int main () {
+        int y;
+        x = 1;
+        return x + y;
+    }
+    "#, "Read of uninitialized variable 'y'", "y;\n    }", "int y"),
+    ];
+
+    let mut context = engine::Context::new();
+    context.sanitize_uninit = true;
+
+    for (prog, expected, read, decl) in progs {
+        let ast = semic::parse_prog(prog).unwrap();
+
+        let actual = super::run_prog(&ast, prog, &vec![], &context);
+
+        match actual {
+            Err(CError::WithNote(err, _, l2)) => {
+                match *err {
+                    CError::RuntimeError(msg, l) => {
+                        assert_eq!(expected, msg);
+                        assert_eq!(prog.find(read), Some(l.0));
+                    },
+                    x => panic!("expected runtime error, got {:?}", x),
+                }
+                assert_eq!(prog.find(decl), Some(l2.0));
+            },
+            x => panic!("expected uninit error, got {:?}", x),
+        }
+    }
+
+    // whole array writes count
+    let prog = r#"
+    int main () {
+        char s[3];
+        s = "ab";
+        return (int) s[2];
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let actual = super::run_prog(&ast, prog, &vec![], &context);
+    assert!(actual.is_ok());
+
+    assert_eq!(Some(SymVal::Int(0)), actual.unwrap());
+}
+
+#[test]
+fn limits() {
+    let prog = r#"
+    int f(int n) {
+        return f(n + 1);
+    }
+
+    int main () {
+        int a[10];
+        while (1) {
+            a[0] = 1;
+        }
+        return f(0);
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let cases = vec![
+        (engine::Limits { steps: Some(50), depth: None, cells: None }, "Step limit of 50 exceeded", "a[0] = 1"),
+        (engine::Limits { steps: None, depth: None, cells: Some(9) }, "Array cell limit of 9 exceeded", "int a[10]"),
+    ];
+    for (limits, expected, at) in cases {
+        let mut context = engine::Context::new();
+        context.limits = limits;
+
+        match super::run_prog(&ast, prog, &vec![], &context) {
+            Err(CError::LimitError(_, msg, l)) => {
+                assert_eq!(expected, msg);
+                assert_eq!(prog.find(at), Some(l.0));
+            },
+            x => panic!("expected limit error, got {:?}", x),
+        }
+    }
+
+    let prog = r#"
+    int f(int n) {
+        return f(n + 1);
+    }
+
+    int main () {
+        return f(0);
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let mut context = engine::Context::new();
+    context.limits.depth = Some(20);
+
+    match super::run_prog(&ast, prog, &vec![], &context) {
+        Err(CError::LimitError(engine::Limit::Depth, msg, l)) => {
+            assert_eq!("Call depth limit of 20 exceeded", msg);
+            assert_eq!("f(n + 1)", &prog[l.0..l.1]);
+        },
+        x => panic!("expected depth error, got {:?}", x),
+    }
+}
+
+#[test]
+fn limits_on_long_runs() {
+    let prog = r#"
+    int main () {
+        int i;
+        i = 0;
+        while (1) {
+            i++;
+        }
+        return i;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let mut context = engine::Context::new();
+    context.limits.steps = Some(1000000);
+
+    match engine::run_prog(&ast, prog, &vec![], false, false, &context) {
+        Err(CError::LimitError(engine::Limit::Steps, msg, _)) => assert_eq!("Step limit of 1000000 exceeded", msg),
+        x => panic!("expected step error, got {:?}", x),
+    }
+
+    // without a depth limit, the tree walker stops recursing at its own
+    let prog = r#"
+    int f(int n) {
+        if (n >= 0) {
+            do {
+                return 1 + f(n + 1);
+            } while (0);
+        }
+        return 0;
+    }
+
+    int main () {
+        return f(0);
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let context = engine::Context::new();
+    match engine::run_prog(&ast, prog, &vec![], false, false, &context) {
+        Err(CError::LimitError(engine::Limit::Depth, msg, l)) => {
+            assert_eq!(format!("Call depth of {} exceeded, the most the tree backend supports", engine::TREE_DEPTH), msg);
+            assert_eq!("f(n + 1)", &prog[l.0..l.1]);
+        },
+        x => panic!("expected depth error, got {:?}", x),
+    }
+}
+
+#[test]
+fn step_count() {
+    let prog = r#"
+    int main () {
+        int i;
+        i = 0;
+        do {
+            switch (i) {
+            case 0:
+                i = i + 1;
+            default:
+                i = i + 1;
+            }
+        } while (i < 4);
+        return i;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    // decl, assign and return, and per round the do, the switch, and a
+    // label and an assign, or both of each when starting from case 0
+    let steps = 3 + (2 + 4) + (2 + 2) + (2 + 2);
+
+    let mut context = engine::Context::new();
+    context.limits.steps = Some(steps);
+    assert_eq!(Some(SymVal::Int(4)), super::run_prog(&ast, prog, &vec![], &context).unwrap());
+
+    context.limits.steps = Some(steps - 1);
+    match super::run_prog(&ast, prog, &vec![], &context) {
+        Err(CError::LimitError(engine::Limit::Steps, _, l)) => assert_eq!(prog.find("return i"), Some(l.0)),
+        x => panic!("expected step error, got {:?}", x),
+    }
+}
+
+#[test]
+fn block_scope() {
+    let prog = r#"
+    int x;
+    int main () {
+        int i;
+        int s;
+        if (0) {
+            int x;
+        }
+        x = 2;
+        s = 0;
+        i = 0;
+        while (i < 2) {
+            s = s * 10 + x;
+            int x;
+            x = 7;
+            i = i + 1;
+        }
+        return s * 10 + x;
+    }
+    "#;
+
+    let ast = semic::parse_prog(prog).unwrap();
+
+    // a declaration only hides the global until the end of its block,
+    // whether or not it ran
+    let actual = super::run_prog(&ast, prog, &vec![], &engine::Context::new());
+    assert_eq!(Some(SymVal::Int(222)), actual.unwrap());
+}
@@ -14,18 +14,19 @@ fn assign_int() {
 
     let ast = semic::parse_stmt(r#" i = 7; "#).unwrap();
 
-    let (actual, _, tab, _) = engine::run_stmt(&ast, &vtab, global, local, None).unwrap();
-    let expected = None;
-    assert_eq!(expected, actual);
-
-    let meta = tab.get_type("i");
-    assert!(meta.is_some());
-
-    let (t, s) = meta.unwrap();
-    let val = tab.get_val("i");
-    assert_eq!(CType::Int, t);
-    assert_eq!(None, s);
-    assert_eq!(Some(SymVal::Int(7)), val);
+    for (actual, tab) in super::run_stmt(&ast, &vtab, global, local, &engine::Context::new()) {
+        let expected = None;
+        assert_eq!(expected, actual);
+
+        let meta = tab.get_type("i");
+        assert!(meta.is_some());
+
+        let (t, s) = meta.unwrap();
+        let val = tab.get_val("i");
+        assert_eq!(CType::Int, t);
+        assert_eq!(None, s);
+        assert_eq!(Some(SymVal::Int(7)), val);
+    }
 }
 
 #[test]
@@ -38,19 +39,20 @@ fn assign_array() {
 
     let ast = semic::parse_stmt(r#" s[1] = '\0'; "#).unwrap();
 
-    let (actual, _, tab, _) = engine::run_stmt(&ast, &vtab, global, local, None).unwrap();
-    let expected = None;
-    assert_eq!(expected, actual);
+    for (actual, tab) in super::run_stmt(&ast, &vtab, global, local, &engine::Context::new()) {
+        let expected = None;
+        assert_eq!(expected, actual);
 
-    let meta = tab.get_type("s");
-    assert!(meta.is_some());
+        let meta = tab.get_type("s");
+        assert!(meta.is_some());
 
-    let (t, s) = meta.unwrap();
-    let val = tab.get_val("s");
-    assert_eq!(CType::Char, t);
-    assert_eq!(Some(2), s);
-    assert_eq!(Some(SymVal::Array(vec![Box::new(SymVal::Int(0)),
-                                       Box::new(SymVal::Char('\0'))])), val);
+        let (t, s) = meta.unwrap();
+        let val = tab.get_val("s");
+        assert_eq!(CType::Char, t);
+        assert_eq!(Some(2), s);
+        assert_eq!(Some(SymVal::Array(vec![Box::new(SymVal::Int(0)),
+                                           Box::new(SymVal::Char('\0'))])), val);
+    }
 }
 
 #[test]
@@ -63,17 +65,41 @@ fn assign_string() {
 
     let ast = semic::parse_stmt(r#" s = "a"; "#).unwrap();
 
-    let (actual, _, tab, _) = engine::run_stmt(&ast, &vtab, global, local, None).unwrap();
-    let expected = None;
-    assert_eq!(expected, actual);
+    for (actual, tab) in super::run_stmt(&ast, &vtab, global, local, &engine::Context::new()) {
+        let expected = None;
+        assert_eq!(expected, actual);
 
-    let meta = tab.get_type("s");
-    assert!(meta.is_some());
+        let meta = tab.get_type("s");
+        assert!(meta.is_some());
 
-    let (t, s) = meta.unwrap();
-    let val = tab.get_val("s");
-    assert_eq!(CType::Ref(Box::new(CType::Char)), t);
-    assert_eq!(Some(2), s);
-    assert_eq!(Some(SymVal::Array(vec![Box::new(SymVal::Char('a')),
-                                       Box::new(SymVal::Char('\0'))])), val);
+        let (t, s) = meta.unwrap();
+        let val = tab.get_val("s");
+        assert_eq!(CType::Ref(Box::new(CType::Char)), t);
+        assert_eq!(Some(2), s);
+        assert_eq!(Some(SymVal::Array(vec![Box::new(SymVal::Char('a')),
+                                           Box::new(SymVal::Char('\0'))])), val);
+    }
+}
+
+#[test]
+fn block_scope() {
+    let vtab = FuncTab::new();
+    let global = SymTab::new();
+    let mut local = SymTab::new();
+
+    local.insert("x", CType::Int, None, Some(SymVal::Int(1)), None);
+
+    // declarations in a block end with it, others stay
+    let ast = semic::parse_stmt(r#" { int x; x = 5; int y; y = 2; } "#).unwrap();
+    for (actual, tab) in super::run_stmt(&ast, &vtab, global.clone(), local.clone(), &engine::Context::new()) {
+        assert_eq!(None, actual);
+        assert_eq!(Some(SymVal::Int(1)), tab.get_val("x"));
+        assert_eq!(None, tab.get_type("y"));
+    }
+
+    let ast = semic::parse_stmt(r#" int y = 2; "#).unwrap();
+    for (actual, tab) in super::run_stmt(&ast, &vtab, global.clone(), local.clone(), &engine::Context::new()) {
+        assert_eq!(None, actual);
+        assert_eq!(Some(SymVal::Int(2)), tab.get_val("y"));
+    }
 }
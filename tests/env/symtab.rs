@@ -1,7 +1,7 @@
 extern crate semic;
 
 use semic::ast::CType;
-use semic::env::{SymTab, SymVal};
+use semic::env::{SymTab, SymVal, TRACE_LEN};
 
 #[test]
 fn get_type() {
@@ -89,24 +89,90 @@ fn get_trace() {
 
     assert!(tab.get_trace("i").is_none());
 
-    tab.insert("i", CType::Int, None, None, Some(0));
+    tab.insert("i", CType::Int, None, None, Some((0, 5)));
 
     let traceo = tab.get_trace("i");
     assert!(traceo.is_some());
-    let trace = traceo.unwrap();
+    let (dropped, trace) = traceo.unwrap();
+    assert_eq!(0, dropped);
     assert_eq!(1, trace.len());
     let val = trace.get(0);
     assert!(val.is_some());
-    assert_eq!((None, Some(0)), *val.unwrap());
+    assert_eq!((None, None, Some((0, 5))), *val.unwrap());
 
-    let res = tab.set_val("i", None, SymVal::Int(2), Some(2));
+    let res = tab.set_val("i", None, SymVal::Int(2), Some((6, 11)));
     assert!(res.is_ok());
 
     let traceo2 = tab.get_trace("i");
     assert!(traceo2.is_some());
-    let trace2 = traceo2.unwrap();
+    let (_, trace2) = traceo2.unwrap();
     assert_eq!(2, trace2.len());
     let val2 = trace2.get(1);
     assert!(val2.is_some());
-    assert_eq!((Some(SymVal::Int(2)), Some(2)), *val2.unwrap());
+    assert_eq!((None, Some(SymVal::Int(2)), Some((6, 11))), *val2.unwrap());
+}
+
+#[test]
+fn trace_bounded() {
+    let mut tab = SymTab::new();
+
+    tab.insert("a", CType::Ref(Box::new(CType::Int)), Some(100), None, Some((0, 5)));
+    for n in 0..1000 {
+        assert!(tab.set_val("a", Some(n % 100), SymVal::Int(n as i32), Some((6, 11))).is_ok());
+    }
+
+    // only the last writes are kept, each with just the element written,
+    // however many there were
+    let (dropped, trace) = tab.get_trace("a").unwrap();
+    assert_eq!(1001 - TRACE_LEN, dropped);
+    assert_eq!(TRACE_LEN, trace.len());
+    assert_eq!((Some(99), Some(SymVal::Int(999)), Some((6, 11))), trace[TRACE_LEN - 1]);
+    assert!(trace.iter().all(|&(_, ref v, _)| match *v {
+        Some(SymVal::Array(_)) => false,
+        _ => true,
+    }));
+
+    assert_eq!(Some((0, 5)), tab.get_decl("a"));
+    match tab.get_val("a") {
+        Some(SymVal::Array(a)) => assert_eq!(SymVal::Int(999), *a[99]),
+        x => panic!("expected array, got {:?}", x),
+    }
+}
+
+#[test]
+fn set_val_out_of_bounds() {
+    let mut tab = SymTab::new();
+
+    tab.insert("a", CType::Ref(Box::new(CType::Int)), Some(2), None, None);
+
+    assert!(tab.set_val("a", Some(2), SymVal::Int(1), None).is_err());
+    assert!(tab.set_val("a", Some(1), SymVal::Int(1), None).is_ok());
+    assert!(tab.set_val("a", Some(2), SymVal::Int(1), None).is_err());
+
+    tab.insert("p", CType::Ref(Box::new(CType::Int)), None, None, None);
+
+    assert!(tab.set_val("p", Some(0), SymVal::Int(1), None).is_err());
+}
+
+#[test]
+fn is_written() {
+    let mut tab = SymTab::new();
+
+    tab.insert("i", CType::Int, None, None, None);
+    tab.insert("a", CType::Ref(Box::new(CType::Int)), Some(2), None, None);
+
+    assert!(!tab.is_written("i", None));
+    assert!(!tab.is_written("a", Some(0)));
+
+    assert!(tab.set_val("i", None, SymVal::Int(2), None).is_ok());
+    assert!(tab.set_val("a", Some(1), SymVal::Int(2), None).is_ok());
+
+    assert!(tab.is_written("i", None));
+    assert!(!tab.is_written("a", Some(0)));
+    assert!(tab.is_written("a", Some(1)));
+
+    assert!(tab.set_val("a", Some(0), SymVal::Int(2), None).is_ok());
+
+    assert!(tab.is_written("a", Some(0)));
+    assert!(tab.is_written("a", Some(1)));
 }
@@ -0,0 +1,72 @@
+extern crate semic;
+
+use semic::engine;
+use semic::error::{CError, ErrorPrinter, Kind, Severity};
+
+#[test]
+fn runtime_with_note() {
+    let prog = "int main () {\n    int a[2];\n    a[0] = 1;\n    return a[5];\n}";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let err = engine::run_prog(&ast, prog, &vec![], false, false, &engine::Context::new()).unwrap_err();
+
+    let diags = ErrorPrinter::new("foo.semic", prog).diagnostics(err);
+    assert_eq!(1, diags.len());
+
+    let d = &diags[0];
+    assert_eq!(Severity::Error, d.severity);
+    assert_eq!(Kind::Runtime, d.kind);
+    assert_eq!("foo.semic", d.file);
+
+    let span = d.span.as_ref().unwrap();
+    // the whole of `a[5]`
+    assert_eq!((4, 12), (span.line, span.column));
+    assert_eq!((4, 16), (span.end_line, span.end_column));
+
+    assert_eq!(1, d.notes.len());
+    assert_eq!("'a' declared here", d.notes[0].message);
+    assert_eq!(2, d.notes[0].span.as_ref().unwrap().line);
+}
+
+#[test]
+fn checker_errors() {
+    let err = CError::CheckerError(vec![
+        ("first".to_owned(), Some((0, 3))),
+        ("second".to_owned(), None),
+    ]);
+
+    let diags = ErrorPrinter::new("foo.semic", "int main () {}").diagnostics(err);
+    assert_eq!(2, diags.len());
+    assert_eq!(Kind::Type, diags[0].kind);
+
+    let span = diags[0].span.as_ref().unwrap();
+    assert_eq!((1, 1, 4), (span.line, span.column, span.end_column));
+    assert_eq!(None, diags[1].span);
+}
+
+#[test]
+fn syntax_error_json() {
+    let prog = "int main () {\n    return \"a\\b\"\n}";
+
+    let err = semic::parse_prog(prog).unwrap_err();
+    let diags = ErrorPrinter::new("dir/foo.semic", prog).diagnostics(err);
+    assert_eq!(Kind::Syntax, diags[0].kind);
+
+    let json = format!("{}", diags[0].to_json());
+    assert!(json.starts_with(r#"{"severity":"error","kind":"syntax","message":"#));
+    assert!(json.ends_with(concat!(
+        r#""file":"dir/foo.semic","line":2,"column":17,"end_line":2,"end_column":18,"code":null,"#,
+        r#""notes":[{"message":"the last statement before '}' needs a ';' too","file":"dir/foo.semic","#,
+        r#""line":null,"column":null,"end_line":null,"end_column":null}]}"#)));
+}
+
+#[test]
+fn unknown_json() {
+    let err = CError::UnknownError("say \"hi\"".to_owned());
+
+    let diags = ErrorPrinter::new("foo.semic", "").diagnostics(err);
+    assert_eq!(
+        r#"{"severity":"error","kind":"other","message":"say \"hi\"","file":"foo.semic","line":null,"column":null,"end_line":null,"end_column":null,"code":null,"notes":[]}"#,
+        format!("{}", diags[0].to_json())
+    );
+}
@@ -0,0 +1,141 @@
+extern crate semic;
+
+use std::collections::HashMap;
+use std::io;
+
+use semic::ast::CProgElem;
+use semic::engine;
+use semic::error::{CError, ErrorPrinter};
+use semic::include;
+use semic::source::SourceMap;
+use semic::vm;
+
+fn load(files: &[(&str, &str)]) -> (SourceMap, Result<semic::ast::CProg, CError>) {
+    let files: HashMap<&str, &str> = files.iter().cloned().collect();
+    include::load("main.semic", files["main.semic"], |path| match files.get(path) {
+        Some(src) => Ok(src.to_string()),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+    })
+}
+
+const HEADER: &str = "extern int offset;\nint square(int x);\n";
+const LIB: &str = "#include \"math.h\"\nint offset;\n\nint square(int x) {\n    return x * x + offset;\n}\n";
+
+#[test]
+fn runs_on_both_backends() {
+    let (sources, ast) = load(&[
+        ("main.semic", "#include \"lib/math.h\"\n#include \"lib/math.semic\"\nint main () {\n    offset = 1;\n    return square(3);\n}\n"),
+        ("lib/math.h", HEADER),
+        ("lib/math.semic", LIB),
+    ]);
+    let ast = ast.unwrap();
+    assert_eq!(3, sources.files().len());
+
+    let ctx = engine::Context::new();
+    let tree = engine::run_sources(&ast, &sources, &vec![], false, false, &ctx);
+    let vm = vm::run_prog(&ast, &vec![], &ctx);
+    assert_eq!(format!("{:?}", tree), format!("{:?}", vm));
+    assert_eq!("Ok(Some(10))", format!("{:?}", tree));
+}
+
+#[test]
+fn included_once() {
+    // the header includes itself, and is included twice
+    let (sources, ast) = load(&[
+        ("main.semic", "#include \"a.h\"\n#include \"a.h\"\nint main () { return f(); }\nint f() { return 1; }\n"),
+        ("a.h", "#include \"a.h\"\nint f();\n"),
+    ]);
+    let ast = ast.unwrap();
+    assert_eq!(2, sources.files().len());
+    assert_eq!(1, ast.iter().filter(|e| match **e { CProgElem::Proto(..) => true, _ => false }).count());
+}
+
+#[test]
+fn included_once_by_any_path() {
+    // the files include each other through '..', and themselves through '.'
+    let (sources, ast) = load(&[
+        ("main.semic", "#include \"lib/a.h\"\n#include \"./main.semic\"\nint main () { return f() + g(); }\n"),
+        ("lib/a.h", "#include \"../inc/b.h\"\n#include \"./a.h\"\nint f() { return 1; }\n"),
+        ("inc/b.h", "#include \"../lib/a.h\"\nint g() { return 2; }\n"),
+    ]);
+    let ast = ast.unwrap();
+    let names: Vec<&str> = sources.files().iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(vec!["main.semic", "lib/a.h", "inc/b.h"], names);
+
+    let ctx = engine::Context::new();
+    assert_eq!("Ok(Some(3))", format!("{:?}", engine::run_sources(&ast, &sources, &vec![], false, false, &ctx)));
+}
+
+#[test]
+fn missing_file() {
+    let (sources, ast) = load(&[("main.semic", "int x;\n#include \"gone.h\"\nint main () {}\n")]);
+    let diags = ErrorPrinter::from_map(sources).diagnostics(ast.unwrap_err());
+
+    assert_eq!(1, diags.len());
+    assert!(diags[0].message.starts_with("Cannot include 'gone.h'"));
+    assert_eq!("main.semic", diags[0].file);
+    assert_eq!(2, diags[0].span.as_ref().unwrap().line);
+}
+
+#[test]
+fn syntax_error_names_file() {
+    let (sources, ast) = load(&[
+        ("main.semic", "#include \"bad.h\"\nint main () {}\n"),
+        ("bad.h", "int f();\nint g(;\n"),
+    ]);
+    let diags = ErrorPrinter::from_map(sources).diagnostics(ast.unwrap_err());
+
+    assert_eq!("bad.h", diags[0].file);
+    assert_eq!(2, diags[0].span.as_ref().unwrap().line);
+}
+
+#[test]
+fn runtime_error_names_file() {
+    let (sources, ast) = load(&[
+        ("main.semic", "#include \"lib.semic\"\nint main () {\n    return div(1, 0);\n}\n"),
+        ("lib.semic", "int div(int a, int b) {\n    return a / b;\n}\n"),
+    ]);
+    let ast = ast.unwrap();
+    let err = engine::run_sources(&ast, &sources, &vec![], false, false, &engine::Context::new()).unwrap_err();
+    let diags = ErrorPrinter::from_map(sources).diagnostics(err);
+
+    let d = &diags[0];
+    assert_eq!("lib.semic", d.file);
+    let span = d.span.as_ref().unwrap();
+    assert_eq!((2, 12), (span.line, span.column));
+    assert_eq!((2, 17), (span.end_line, span.end_column));
+}
+
+#[test]
+fn extern_mismatch() {
+    let (_, ast) = load(&[
+        ("main.semic", "#include \"a.h\"\nchar n;\nint main () {}\n"),
+        ("a.h", "extern int n;\n"),
+    ]);
+    match semic::check_prog(&ast.unwrap()) {
+        Err(CError::CheckerError(errs)) =>
+            assert_eq!("Variable 'n' declared 'extern' with another type", errs[0].0),
+        res => panic!("{:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn extern_undefined() {
+    let ast = semic::parse_prog("extern int n, a[];\nint a[2];\nint main () { return n; }\n").unwrap();
+    match semic::check_prog(&ast) {
+        Err(CError::CheckerError(errs)) => {
+            assert_eq!(1, errs.len());
+            assert_eq!("Variable 'n' declared 'extern' but never defined", errs[0].0);
+        },
+        res => panic!("{:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn format() {
+    // `extern` on a function says nothing more than the prototype alone
+    let prog = "#include  \"a.h\"\nextern   int a,b [];\nextern int f(int x);\nint main(){}";
+    assert_eq!(
+        "#include \"a.h\"\nextern int a, b[];\nint f(int x);\n\nint main(void) {\n}\n",
+        semic::format(prog).unwrap());
+}
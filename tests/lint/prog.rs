@@ -0,0 +1,158 @@
+extern crate semic;
+
+use semic::engine::Context;
+use semic::error::{ErrorPrinter, Severity};
+use semic::lint::{self, Lint, Level};
+
+/// Lint name, message and line of each warning
+fn warnings(prog: &str) -> Vec<(String, String, usize)> {
+    let ast = semic::parse_prog(prog).unwrap();
+    let printer = ErrorPrinter::new("foo.semic", prog);
+
+    lint::lint_prog(&ast).into_iter().flat_map(|w| printer.diagnostics(w)).map(|d| {
+        let line = d.span.as_ref().unwrap().line;
+        (d.code.unwrap(), d.message, line)
+    }).collect()
+}
+
+fn w(lint: &str, msg: &str, line: usize) -> (String, String, usize) {
+    (lint.to_owned(), msg.to_owned(), line)
+}
+
+#[test]
+fn unused() {
+    let prog = r#"
+    int foo(int a, int b) {
+        int x, y;
+        int z = 2;
+        y = a;
+        return y;
+    }
+    int main(int argc, char *argv[]) {
+        return foo(1, 2);
+    }
+    "#;
+
+    assert_eq!(vec![
+        w("unused", "Unused parameter 'b' of 'foo'", 2),
+        w("unused", "Unused variable 'x'", 3),
+        w("unused", "Unused variable 'z'", 4),
+    ], warnings(prog));
+}
+
+#[test]
+fn unreachable() {
+    let prog = r#"
+    int main () {
+        int i;
+        for (i = 0; i < 3; i++) {
+            return i;
+        }
+        switch (i) {
+            case 1:
+                break;
+                i = 2;
+            case 2:
+                return 0;
+        }
+        if (i) { return 1; } else { return 2; }
+        i = 3;
+        i = 4;
+    }
+    "#;
+
+    assert_eq!(vec![
+        w("unreachable", "Unreachable code after 'break'", 10),
+        w("unreachable", "Unreachable code after 'return'", 15),
+    ], warnings(prog));
+}
+
+#[test]
+fn missing_return() {
+    let prog = r#"
+    int none(int x) {
+        if (x) return 1;
+    }
+    int branches(int x) {
+        if (x) return 1; else return 2;
+    }
+    int forever(int x) {
+        while (1) { x = x + 1; }
+    }
+    int escapes(int x) {
+        while (1) { if (x) break; }
+    }
+    int leaves(int a) {
+        do { if (a) break; return 1; } while (1);
+    }
+    int once(int a) {
+        do { return a; } while (0);
+    }
+    int cases(int x) {
+        switch (x) {
+            case 1: x = 2;
+            default: return x;
+        }
+    }
+    void nothing() {
+    }
+    int main() {
+    }
+    "#;
+
+    assert_eq!(vec![
+        w("missing-return", "Function 'none' can reach its end without returning a value", 4),
+        w("missing-return", "Function 'escapes' can reach its end without returning a value", 13),
+        w("missing-return", "Function 'leaves' can reach its end without returning a value", 16),
+    ], warnings(prog));
+}
+
+#[test]
+fn shadow_global() {
+    let prog = r#"
+    int a, b;
+    int main(int b) {
+        int a = b;
+        return a;
+    }
+    "#;
+
+    assert_eq!(vec![
+        w("shadow-global", "Parameter 'b' shadows a global variable", 3),
+        w("shadow-global", "Variable 'a' shadows a global variable", 4),
+    ], warnings(prog));
+}
+
+#[test]
+fn levels() {
+    let prog = "int a;\nint main () {\n  int a, x;\n  return 0;\n}";
+
+    // shadowing is allowed by default
+    let context = Context::new();
+    let diags = semic::lint("foo.semic", prog, &context);
+    let codes: Vec<_> = diags.iter().map(|d| d.code.clone().unwrap()).collect();
+    assert_eq!(vec!["unused", "unused"], codes);
+    assert!(diags.iter().all(|d| d.severity == Severity::Warning));
+
+    let mut context = Context::new();
+    context.lints.push((Lint::ShadowGlobal, Level::Warn));
+    context.lints.push((Lint::Unused, Level::Allow));
+    let diags = semic::lint("foo.semic", prog, &context);
+    assert_eq!(1, diags.len());
+    assert_eq!(Some("shadow-global".to_owned()), diags[0].code);
+    assert_eq!("global 'a' declared here", diags[0].notes[0].message);
+}
+
+#[test]
+fn warnings_as_errors() {
+    let prog = "int main () {\n  int x;\n  return 0;\n}";
+
+    let mut context = Context::new();
+    assert!(semic::run("foo.semic".to_owned(), prog.to_owned(), vec![], false, false, &context).is_ok());
+
+    context.warnings_as_errors = true;
+    let diags = semic::run("foo.semic".to_owned(), prog.to_owned(), vec![], false, false, &context).unwrap_err();
+    assert_eq!(1, diags.len());
+    assert_eq!(Severity::Error, diags[0].severity);
+    assert_eq!("Unused variable 'x'", diags[0].message);
+}
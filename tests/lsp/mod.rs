@@ -0,0 +1,97 @@
+extern crate semic;
+
+mod server;
+
+use semic::json::Json;
+use semic::lsp;
+
+/// Client writing messages for the server to read, as an editor would on
+/// its stdin
+pub struct Client {
+    input: Vec<u8>,
+    next_id: usize,
+}
+
+impl Client {
+    pub fn new() -> Client {
+        let mut client = Client { input: vec![], next_id: 1 };
+        client.request("initialize", "{}");
+        client.notify("initialized", "{}");
+        client
+    }
+
+    pub fn request(&mut self, method: &str, params: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&format!(r#"{{"jsonrpc": "2.0", "id": {}, "method": "{}", "params": {}}}"#, id, method, params));
+        id
+    }
+
+    pub fn notify(&mut self, method: &str, params: &str) {
+        self.send(&format!(r#"{{"jsonrpc": "2.0", "method": "{}", "params": {}}}"#, method, params));
+    }
+
+    pub fn send(&mut self, body: &str) {
+        self.input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes());
+    }
+
+    pub fn open(&mut self, uri: &str, text: &str) {
+        self.notify("textDocument/didOpen", &format!(
+            r#"{{"textDocument": {{"uri": "{}", "languageId": "c", "version": 1, "text": {}}}}}"#, uri, Json::str(text)));
+    }
+
+    pub fn change(&mut self, uri: &str, text: &str) {
+        self.notify("textDocument/didChange", &format!(
+            r#"{{"textDocument": {{"uri": "{}", "version": 2}}, "contentChanges": [{{"text": {}}}]}}"#, uri, Json::str(text)));
+    }
+
+    /// Request about a position in a document
+    pub fn at(&mut self, method: &str, uri: &str, line: usize, character: usize) -> usize {
+        self.request(method, &format!(
+            r#"{{"textDocument": {{"uri": "{}"}}, "position": {{"line": {}, "character": {}}}}}"#, uri, line, character))
+    }
+
+    /// Run the server on the messages, giving what it sent back and whether
+    /// it was shut down
+    pub fn run(self) -> (Vec<Json>, bool) {
+        let mut output = vec![];
+        let shut_down = lsp::serve(&self.input[..], &mut output).unwrap();
+
+        let mut replies = vec![];
+        let mut rest = &output[..];
+        while let Some(msg) = lsp::read_message(&mut rest).unwrap() {
+            replies.push(Json::parse(&msg).unwrap());
+        }
+        (replies, shut_down)
+    }
+}
+
+/// Result of the reply to a request
+pub fn result(replies: &[Json], id: usize) -> &Json {
+    replies.iter()
+        .find(|r| r.get("id") == Some(&Json::Num(id as f64)))
+        .and_then(|r| r.get("result"))
+        .expect("no result")
+}
+
+/// Each list of diagnostics published for a document, in order
+pub fn published<'a>(replies: &'a [Json], uri: &str) -> Vec<Vec<&'a Json>> {
+    replies.iter()
+        .filter(|r| r.get("method").and_then(Json::as_str) == Some("textDocument/publishDiagnostics"))
+        .map(|r| r.get("params").unwrap())
+        .filter(|p| p.get("uri").and_then(Json::as_str) == Some(uri))
+        .map(|p| match p.get("diagnostics") {
+            Some(&Json::Array(ref ds)) => ds.iter().collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// Start and end of a range as (line, character) pairs
+pub fn range(json: &Json) -> ((usize, usize), (usize, usize)) {
+    let pos = |p: &Json| (
+        p.get("line").and_then(Json::as_num).unwrap() as usize,
+        p.get("character").and_then(Json::as_num).unwrap() as usize,
+    );
+    (pos(json.get("start").unwrap()), pos(json.get("end").unwrap()))
+}
@@ -0,0 +1,192 @@
+use semic::json::Json;
+
+use super::{published, range, result, Client};
+
+const URI: &str = "file:///work/main.semic";
+
+const PROG: &str = "#include \"lib.h\"
+
+int scale;
+int table[3];
+
+int main(void) {
+    int y;
+    scale = 2;
+    y = twice(table[0]);
+    return y;
+}
+";
+
+const LIB: &str = "int twice(int x) {
+    return x * scale;
+}
+";
+
+#[test]
+fn initialize() {
+    let mut client = Client::new();
+    let shutdown = client.request("shutdown", "null");
+    client.notify("exit", "null");
+    let (replies, shut_down) = client.run();
+
+    let caps = result(&replies, 1).get("capabilities").unwrap();
+    assert_eq!(Some(&Json::Num(1.0)), caps.get("textDocumentSync"));
+    assert_eq!(Some(&Json::Bool(true)), caps.get("definitionProvider"));
+    assert_eq!(Some(&Json::Bool(true)), caps.get("hoverProvider"));
+    assert_eq!(Some(&Json::Bool(true)), caps.get("documentSymbolProvider"));
+    assert_eq!(&Json::Null, result(&replies, shutdown));
+    assert!(shut_down);
+}
+
+#[test]
+fn exit_without_shutdown() {
+    let mut client = Client::new();
+    client.notify("exit", "null");
+    // nothing after exit is read
+    client.request("shutdown", "null");
+    let (replies, shut_down) = client.run();
+
+    assert_eq!(1, replies.len());
+    assert!(!shut_down);
+}
+
+#[test]
+fn bad_messages() {
+    let mut client = Client::new();
+    client.send("{\"id\": ");
+    let unknown = client.request("textDocument/rename", "{}");
+    let (replies, _) = client.run();
+
+    let error = |r: &Json| r.get("error").and_then(|e| e.get("code")).and_then(Json::as_num);
+    assert_eq!(Some(-32700.0), error(&replies[1]));
+    assert_eq!(Some(&Json::Null), replies[1].get("id"));
+    assert_eq!(Some(-32601.0), error(&replies[2]));
+    assert_eq!(Some(&Json::Num(unknown as f64)), replies[2].get("id"));
+}
+
+#[test]
+fn publish_diagnostics() {
+    let mut client = Client::new();
+    client.open(URI, "int main(void) {\n    return 0\n}\n");
+    client.change(URI, "int f(void) {\n    return 0;\n}\n");
+    client.change(URI, "int x;\nint x;\nint main(void) {\n    return 0;\n}\n");
+    client.change(URI, "int main(void) {\n    return 0;\n}\n");
+    client.notify("textDocument/didClose", &format!(r#"{{"textDocument": {{"uri": "{}"}}}}"#, URI));
+    let (replies, _) = client.run();
+
+    let lists = published(&replies, URI);
+    assert_eq!(5, lists.len());
+
+    let d = lists[0][0];
+    assert_eq!(Some("Missing ';'"), d.get("message").and_then(Json::as_str));
+    assert_eq!(Some(&Json::Num(1.0)), d.get("severity"));
+    assert_eq!(((1, 12), (1, 12)), range(d.get("range").unwrap()));
+
+    // no location, so at the start
+    assert_eq!(Some("Function 'main' missing"), lists[1][0].get("message").and_then(Json::as_str));
+    assert_eq!(((0, 0), (0, 0)), range(lists[1][0].get("range").unwrap()));
+
+    assert_eq!(Some("Variable 'x' already declared"), lists[2][0].get("message").and_then(Json::as_str));
+    assert_eq!(((1, 0), (1, 6)), range(lists[2][0].get("range").unwrap()));
+
+    assert!(lists[3].is_empty());
+    assert!(lists[4].is_empty());
+}
+
+#[test]
+fn diagnostics_of_included_files() {
+    let lib = "file:///work/lib.h";
+    let mut client = Client::new();
+    client.open(lib, "int twice(int x) {\n    return x *;\n}\n");
+    client.open(URI, PROG);
+    client.change(lib, LIB);
+    client.change(URI, PROG);
+    let (replies, _) = client.run();
+
+    // the header on its own, then as included by the program
+    let lists = published(&replies, lib);
+    assert_eq!(4, lists.len());
+    let d = lists[1][0];
+    assert_eq!(Some("Unexpected ';', expected an expression"), d.get("message").and_then(Json::as_str));
+    assert_eq!(((1, 14), (1, 15)), range(d.get("range").unwrap()));
+
+    // cleared once fixed
+    assert!(published(&replies, URI)[0].is_empty());
+    assert!(lists[3].is_empty());
+}
+
+#[test]
+fn definition() {
+    let lib = "file:///work/lib.h";
+    let mut client = Client::new();
+    client.open(lib, LIB);
+    client.open(URI, PROG);
+    let local = client.at("textDocument/definition", URI, 9, 11);
+    let global = client.at("textDocument/definition", URI, 8, 16);
+    let func = client.at("textDocument/definition", URI, 8, 9);
+    let param = client.at("textDocument/definition", lib, 1, 11);
+    let keyword = client.at("textDocument/definition", URI, 9, 6);
+    let (replies, _) = client.run();
+
+    let location = |id| {
+        let r = result(&replies, id);
+        (r.get("uri").and_then(Json::as_str).unwrap().to_owned(), range(r.get("range").unwrap()))
+    };
+    assert_eq!((URI.to_owned(), ((6, 8), (6, 9))), location(local));
+    assert_eq!((URI.to_owned(), ((3, 4), (3, 9))), location(global));
+    assert_eq!((lib.to_owned(), ((0, 4), (0, 9))), location(func));
+    assert_eq!((lib.to_owned(), ((0, 14), (0, 15))), location(param));
+    assert_eq!(&Json::Null, result(&replies, keyword));
+}
+
+#[test]
+fn hover() {
+    let mut client = Client::new();
+    client.open("file:///work/lib.h", LIB);
+    client.open(URI, PROG);
+    let func = client.at("textDocument/hover", URI, 8, 10);
+    let array = client.at("textDocument/hover", URI, 8, 14);
+    let none = client.at("textDocument/hover", URI, 8, 6);
+    let (replies, _) = client.run();
+
+    let value = |id| result(&replies, id).get("contents").and_then(|c| c.get("value")).and_then(Json::as_str);
+    assert_eq!(Some("```c\nint twice(int x)\n```"), value(func));
+    assert_eq!(Some("```c\nint *table\n```"), value(array));
+    assert_eq!(((8, 8), (8, 13)), range(result(&replies, func).get("range").unwrap()));
+    assert_eq!(None, value(none));
+}
+
+#[test]
+fn utf16_positions() {
+    let mut client = Client::new();
+    client.open(URI, "int main(void) {\n    int y;\n    y = 1;\n    printf(\"\u{e9}\u{1f600}\", y);\n    return 0;\n}\n");
+    // after one unit for the accent and two for the emoji
+    let hover = client.at("textDocument/hover", URI, 3, 18);
+    let (replies, _) = client.run();
+
+    assert_eq!(((3, 18), (3, 19)), range(result(&replies, hover).get("range").unwrap()));
+}
+
+#[test]
+fn document_symbols() {
+    let mut client = Client::new();
+    client.open("file:///work/lib.h", LIB);
+    client.open(URI, PROG);
+    let symbols = client.request("textDocument/documentSymbol", &format!(r#"{{"textDocument": {{"uri": "{}"}}}}"#, URI));
+    let (replies, _) = client.run();
+
+    let symbols = match *result(&replies, symbols) {
+        Json::Array(ref symbols) => symbols.clone(),
+        ref r => panic!("{}", r),
+    };
+    // not those of the included file
+    let names: Vec<(&str, &str, f64)> = symbols.iter().map(|s| (
+        s.get("name").and_then(Json::as_str).unwrap(),
+        s.get("detail").and_then(Json::as_str).unwrap(),
+        s.get("kind").and_then(Json::as_num).unwrap(),
+    )).collect();
+    assert_eq!(vec![("scale", "int scale", 13.0), ("table", "int *table", 13.0), ("main", "int main(void)", 12.0)], names);
+
+    assert_eq!(((5, 0), (10, 1)), range(symbols[2].get("range").unwrap()));
+    assert_eq!(((5, 4), (5, 8)), range(symbols[2].get("selectionRange").unwrap()));
+}
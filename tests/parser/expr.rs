@@ -38,3 +38,27 @@ fn expr_float() {
     assert!(actual.is_ok());
     assert_eq!(expected, format!("{:?}", actual.unwrap()));
 }
+
+#[test]
+fn expr_ternary() {
+    let actual = semic::parse_expr(r#"a || b ? 1 + 2 : c ? 3 : 4"#);
+    let expected = r#"((a || b) ? (1 + 2) : (c ? 3 : 4))"#;
+    assert!(actual.is_ok());
+    assert_eq!(expected, format!("{:?}", actual.unwrap()));
+}
+
+#[test]
+fn expr_comma() {
+    let actual = semic::parse_expr(r#"(f(a, b), c ? d, e : g)"#);
+    let expected = r#"(f(a, b), (c ? (d, e) : g))"#;
+    assert!(actual.is_ok());
+    assert_eq!(expected, format!("{:?}", actual.unwrap()));
+}
+
+#[test]
+fn expr_cast() {
+    let actual = semic::parse_expr(r#"(int) c + (float) (a / 2) * (char*) s"#);
+    let expected = r#"(((int) c) + (((float) (a / 2)) * ((char*) s)))"#;
+    assert!(actual.is_ok());
+    assert_eq!(expected, format!("{:?}", actual.unwrap()));
+}
@@ -15,7 +15,7 @@ fn func_empty() {
     let expected = CFunc {
         proto: CProto {
             ret: None,
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![]),
@@ -36,7 +36,7 @@ fn func_return_type() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![]),
@@ -55,8 +55,8 @@ fn func_param_type_single() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
-            params: vec![(CType::Int, "a")],
+            name: "main".to_owned(),
+            params: vec![(CType::Int, "a".to_owned())],
         },
         body: CStmt::Block((0,0), vec![]),
     };
@@ -74,8 +74,8 @@ fn func_param_type_mult() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
-            params: vec![(CType::Int, "a"), (CType::Char, "b")],
+            name: "main".to_owned(),
+            params: vec![(CType::Int, "a".to_owned()), (CType::Char, "b".to_owned())],
         },
         body: CStmt::Block((0,0), vec![]),
     };
@@ -95,10 +95,10 @@ fn func_decl_single_type_single_ident() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
-        body: CStmt::Block((0,0), vec![Box::new(CStmt::Decl((0,0), CType::Int, "x", None))]),
+        body: CStmt::Block((0,0), vec![Box::new(CStmt::Decl((0,0), CType::Int, "x".to_owned(), None))]),
     };
 
     assert!(actual.is_ok());
@@ -116,11 +116,11 @@ fn func_decl_single_type_single_array() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Int)), "x", Some(CExpr::Int((0,0), 7))))
+            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Int)), "x".to_owned(), Some(CExpr::Int((0,0), 7))))
         ]),
     };
 
@@ -139,12 +139,14 @@ fn func_decl_single_type_mult_ident() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Int, "x", None)),
-            Box::new(CStmt::Decl((0,0), CType::Int, "y", None))
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Int, "x".to_owned(), None)),
+                Box::new(CStmt::Decl((0,0), CType::Int, "y".to_owned(), None))
+            ]))
         ]),
     };
 
@@ -163,12 +165,14 @@ fn func_decl_single_type_mult_array() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Int)), "x", Some(CExpr::Int((0,0), 7)))),
-            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Int)), "y", Some(CExpr::Int((0,0), 8))))
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Int)), "x".to_owned(), Some(CExpr::Int((0,0), 7)))),
+                Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Int)), "y".to_owned(), Some(CExpr::Int((0,0), 8))))
+            ]))
         ]),
     };
 
@@ -188,12 +192,12 @@ fn func_decl_mult_type_single_ident() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Int, "x", None)),
-            Box::new(CStmt::Decl((0,0), CType::Char, "y", None))
+            Box::new(CStmt::Decl((0,0), CType::Int, "x".to_owned(), None)),
+            Box::new(CStmt::Decl((0,0), CType::Char, "y".to_owned(), None))
         ]),
     };
 
@@ -213,14 +217,18 @@ fn func_decl_mult_type_mult_ident() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Int, "x", None)),
-            Box::new(CStmt::Decl((0,0), CType::Int, "y", None)),
-            Box::new(CStmt::Decl((0,0), CType::Char, "a", None)),
-            Box::new(CStmt::Decl((0,0), CType::Char, "b", None))
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Int, "x".to_owned(), None)),
+                Box::new(CStmt::Decl((0,0), CType::Int, "y".to_owned(), None))
+            ])),
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Char, "a".to_owned(), None)),
+                Box::new(CStmt::Decl((0,0), CType::Char, "b".to_owned(), None))
+            ]))
         ]),
     };
 
@@ -241,12 +249,12 @@ fn func_decl_after_stmt() {
     let expected = CFunc {
         proto: CProto {
             ret: None,
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
             Box::new(CStmt::Return((0,0), None)),
-            Box::new(CStmt::Decl((0,0), CType::Char, "a", None)),
+            Box::new(CStmt::Decl((0,0), CType::Char, "a".to_owned(), None)),
             Box::new(CStmt::Return((0,0), None)),
         ]),
     };
@@ -266,12 +274,14 @@ fn func_decl_imm_init() {
     let expected = CFunc {
         proto: CProto {
             ret: None,
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Char, "a", None)),
-            Box::new(CStmt::Assign((0,0), "a", None, CExpr::Char((0,0), 'a'))),
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Char, "a".to_owned(), None)),
+                Box::new(CStmt::Assign((0,0), "a".to_owned(), None, CExpr::Char((0,0), 'a')))
+            ])),
         ]),
     };
 
@@ -290,12 +300,14 @@ fn func_decl_imm_init_string() {
     let expected = CFunc {
         proto: CProto {
             ret: None,
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Char)), "a", None)),
-            Box::new(CStmt::Assign((0,0), "a", None, CExpr::Str((0,0), "foobar".chars()))),
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Char)), "a".to_owned(), None)),
+                Box::new(CStmt::Assign((0,0), "a".to_owned(), None, CExpr::Str((0,0), "foobar".to_owned())))
+            ])),
         ]),
     };
 
@@ -314,7 +326,7 @@ fn func_no_decl_single_stmt() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
@@ -338,12 +350,12 @@ fn func_single_decl_single_stmt() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Int, "x", None)),
-            Box::new(CStmt::Assign((0,0), "x", None, CExpr::Int((0,0), 1)))
+            Box::new(CStmt::Decl((0,0), CType::Int, "x".to_owned(), None)),
+            Box::new(CStmt::Assign((0,0), "x".to_owned(), None, CExpr::Int((0,0), 1)))
         ]),
     };
 
@@ -363,12 +375,12 @@ fn func_single_array_single_stmt() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Char)), "x", Some(CExpr::Int((0,0), 7)))),
-            Box::new(CStmt::Assign((0,0), "x", Some(CExpr::Int((0,0), 6)), CExpr::Char((0,0), '\0')))]),
+            Box::new(CStmt::Decl((0,0), CType::Ref(Box::new(CType::Char)), "x".to_owned(), Some(CExpr::Int((0,0), 7)))),
+            Box::new(CStmt::Assign((0,0), "x".to_owned(), Some(CExpr::Int((0,0), 6)), CExpr::Char((0,0), '\0')))]),
     };
 
     assert!(actual.is_ok());
@@ -389,18 +401,20 @@ fn func_stmt_mult() {
     let expected = CFunc {
         proto: CProto {
             ret: Some(CType::Int),
-            name: "main",
+            name: "main".to_owned(),
             params: vec![],
         },
         body: CStmt::Block((0,0), vec![
-            Box::new(CStmt::Decl((0,0), CType::Int, "x", None)),
-            Box::new(CStmt::Decl((0,0), CType::Int, "y", None)),
-            Box::new(CStmt::Assign((0,0), "x", None, CExpr::Int((0,0), 1))),
-            Box::new(CStmt::Assign((0,0), "y", None, CExpr::Int((0,0), 2))),
+            Box::new(CStmt::Decls((0,0), vec![
+                Box::new(CStmt::Decl((0,0), CType::Int, "x".to_owned(), None)),
+                Box::new(CStmt::Decl((0,0), CType::Int, "y".to_owned(), None))
+            ])),
+            Box::new(CStmt::Assign((0,0), "x".to_owned(), None, CExpr::Int((0,0), 1))),
+            Box::new(CStmt::Assign((0,0), "y".to_owned(), None, CExpr::Int((0,0), 2))),
             Box::new(CStmt::Return((0,0), Some(CExpr::BinOp((0,0),
                 COp::Add,
-                Box::new(CExpr::Ident((0,0), "x")),
-                Box::new(CExpr::Ident((0,0), "y"))))))
+                Box::new(CExpr::Ident((0,0), "x".to_owned())),
+                Box::new(CExpr::Ident((0,0), "y".to_owned()))))))
         ]),
     };
 
@@ -1,6 +1,7 @@
 extern crate semic;
 
 use semic::ast::*;
+use semic::error::{CError, ErrorPrinter};
 
 #[test]
 fn prog_empty() {
@@ -22,7 +23,7 @@ fn prog_proto_void() {
 
     let expected = CProto {
         ret: None,
-        name: "foo",
+        name: "foo".to_owned(),
         params: vec![],
     };
 
@@ -40,8 +41,8 @@ fn prog_proto_types() {
 
     let expected = CProto {
         ret: Some(CType::Int),
-        name: "foo",
-        params: vec![(CType::Int, "a"), (CType::Char, "b")],
+        name: "foo".to_owned(),
+        params: vec![(CType::Int, "a".to_owned()), (CType::Char, "b".to_owned())],
     };
 
     assert!(actual.is_ok());
@@ -60,13 +61,13 @@ fn prog_proto_names_good() {
 
     let expected = CProto {
         ret: Some(CType::Int),
-        name: "foo42",
+        name: "foo42".to_owned(),
         params: vec![],
     };
 
     let expected2 = CProto {
         ret: Some(CType::Int),
-        name: "foo_42",
+        name: "foo_42".to_owned(),
         params: vec![],
     };
 
@@ -98,14 +99,106 @@ fn prog_proto_mult() {
 
     let expected = vec![CProto {
         ret: Some(CType::Int),
-        name: "foo",
-        params: vec![(CType::Int, "a")],
+        name: "foo".to_owned(),
+        params: vec![(CType::Int, "a".to_owned())],
     }, CProto {
         ret: Some(CType::Int),
-        name: "bar",
-        params: vec![(CType::Char, "b")],
+        name: "bar".to_owned(),
+        params: vec![(CType::Char, "b".to_owned())],
     }];
 
     assert!(actual.is_ok());
     assert_eq!(format!("{:?}", expected), format!("{:?}", actual.unwrap()));
 }
+
+#[test]
+fn prog_recover_errors() {
+    let prog = r#"
+    int x
+    int main () {
+        int i;
+        i = 1 +;
+        for (i;;) return;
+        return i
+    }
+    int foo () {
+        return 1 2;
+    }
+    "#;
+
+    let err = semic::parse_prog(prog).unwrap_err();
+    let diags = ErrorPrinter::new("foo.semic", prog).diagnostics(err);
+    let lines: Vec<usize> = diags.iter().map(|d| d.span.as_ref().unwrap().line).collect();
+
+    // one per line, missing ';' shows after the previous token
+    assert_eq!(vec![2, 5, 6, 7, 10], lines);
+}
+
+#[test]
+fn prog_recover_around_block() {
+    let prog = r#"
+    int main () {
+        int x = 1
+        if (x = 2) {
+            x = 3;
+        }
+        return x
+    }
+    "#;
+
+    let err = semic::parse_prog(prog).unwrap_err();
+    let diags = ErrorPrinter::new("foo.semic", prog).diagnostics(err);
+    let found: Vec<(usize, &str, Option<&str>)> = diags.iter().map(|d| {
+        (d.span.as_ref().unwrap().line, d.message.as_str(), d.notes.first().map(|n| n.message.as_str()))
+    }).collect();
+
+    // recovery stops at the next statement, neither skipping the block nor
+    // leaving the function early
+    assert_eq!(vec![
+        (3, "Missing ';'", None),
+        (4, "Unexpected '=', expected an operator or ')'", Some("use '==' to compare values, '=' is only for assignment")),
+        (7, "Missing ';'", Some("the last statement before '}' needs a ';' too")),
+    ], found);
+}
+
+#[test]
+fn prog_error_messages() {
+    let cases = vec![
+        ("int main () {\n    int x\n    x = 1;\n}", "Missing ';'", (2, 10), None),
+        ("int main () {\n    return 0\n}", "Missing ';'", (2, 13),
+         Some("the last statement before '}' needs a ';' too")),
+        ("int main () {\n    if (1 = 1) return 0;\n}", "Unexpected '=', expected an operator or ')'", (2, 11),
+         Some("use '==' to compare values, '=' is only for assignment")),
+        ("int main () {\n    return *;\n}", "Unexpected '*', expected an expression", (2, 12), None),
+        ("int main () {\n    @\n}", "Invalid character '@'", (2, 5), None),
+        ("int main () {\n    return 0;\n\n", "Unexpected end of file, expected a statement", (2, 14),
+         Some("a '{' is never closed")),
+        ("int main () {\n    return 0;\n}\n}\n", "Unexpected '}'", (4, 1), None),
+    ];
+
+    for (prog, msg, (line, column), hint) in cases {
+        let err = semic::parse_prog(prog).unwrap_err();
+        let diags = ErrorPrinter::new("foo.semic", prog).diagnostics(err);
+        assert_eq!(1, diags.len(), "{:?}", diags);
+
+        let d = &diags[0];
+        let span = d.span.as_ref().unwrap();
+        assert_eq!(msg, d.message);
+        assert_eq!((line, column), (span.line, span.column), "{}", msg);
+        assert_eq!(hint, d.notes.first().map(|n| n.message.as_str()));
+    }
+}
+
+#[test]
+fn prog_recover_single() {
+    let actual = semic::parse_prog(r#"
+    int main () {
+        int i
+    }
+    "#);
+
+    match actual {
+        Err(CError::WithHint(..)) => (),
+        x => panic!("expected one parse error, got {:?}", x),
+    }
+}
@@ -196,7 +196,7 @@ fn stmt_for_init() {
 
     let mut top = vec![];
     // init
-    top.push(Box::new(CStmt::Assign((0,0), "i", None, CExpr::Int((0,0), 0))));
+    top.push(Box::new(CStmt::Assign((0,0), "i".to_owned(), None, CExpr::Int((0,0), 0))));
     // cond
     let cond = CExpr::Int((0,0), 1);
     // inc
@@ -238,9 +238,9 @@ fn stmt_for_inc() {
     // inc
     let mut body = vec![];
     body.push(Box::new(CStmt::Return((0,0), None)));
-    body.push(Box::new(CStmt::Assign((0,0), "i", None,
+    body.push(Box::new(CStmt::Assign((0,0), "i".to_owned(), None,
                                      CExpr::BinOp((0,0), COp::Add,
-                                                  Box::new(CExpr::Ident((0,0), "i")),
+                                                  Box::new(CExpr::Ident((0,0), "i".to_owned())),
                                                   Box::new(CExpr::Int((0,0), 1))))));
     let body = Box::new(CStmt::Block((0,0), body));
     // expected
@@ -257,15 +257,15 @@ fn stmt_for_all() {
 
     let mut top = vec![];
     // init
-    top.push(Box::new(CStmt::Assign((0,0), "i", None, CExpr::Int((0,0), 0))));
+    top.push(Box::new(CStmt::Assign((0,0), "i".to_owned(), None, CExpr::Int((0,0), 0))));
     // cond
     let cond = CExpr::Int((0,0), 1);
     // inc
     let mut body = vec![];
     body.push(Box::new(CStmt::Return((0,0), None)));
-    body.push(Box::new(CStmt::Assign((0,0), "i", None,
+    body.push(Box::new(CStmt::Assign((0,0), "i".to_owned(), None,
                                      CExpr::BinOp((0,0), COp::Add,
-                                                  Box::new(CExpr::Ident((0,0), "i")),
+                                                  Box::new(CExpr::Ident((0,0), "i".to_owned())),
                                                   Box::new(CExpr::Int((0,0), 1))))));
     let body = Box::new(CStmt::Block((0,0), body));
     // expected
@@ -275,3 +275,65 @@ fn stmt_for_all() {
     assert!(actual.is_ok());
     assert_eq!(format!("{:?}", expected), format!("{:?}", actual.unwrap()));
 }
+
+#[test]
+fn stmt_for_comma() {
+    let actual = semic::parse_stmt(r#"for (i = 0, j = 1; i < j; i++, j--) return;"#);
+
+    let mut top = vec![];
+    // init
+    top.push(Box::new(CStmt::Assign((0,0), "i".to_owned(), None, CExpr::Int((0,0), 0))));
+    top.push(Box::new(CStmt::Assign((0,0), "j".to_owned(), None, CExpr::Int((0,0), 1))));
+    // cond
+    let cond = CExpr::BinOp((0,0), COp::Lt,
+                            Box::new(CExpr::Ident((0,0), "i".to_owned())),
+                            Box::new(CExpr::Ident((0,0), "j".to_owned())));
+    // inc
+    let mut body = vec![];
+    body.push(Box::new(CStmt::Return((0,0), None)));
+    body.push(Box::new(CStmt::Assign((0,0), "i".to_owned(), None,
+                                     CExpr::BinOp((0,0), COp::Add,
+                                                  Box::new(CExpr::Ident((0,0), "i".to_owned())),
+                                                  Box::new(CExpr::Int((0,0), 1))))));
+    body.push(Box::new(CStmt::Assign((0,0), "j".to_owned(), None,
+                                     CExpr::BinOp((0,0), COp::Sub,
+                                                  Box::new(CExpr::Ident((0,0), "j".to_owned())),
+                                                  Box::new(CExpr::Int((0,0), 1))))));
+    let body = Box::new(CStmt::Block((0,0), body));
+    // expected
+    top.push(Box::new(CStmt::While((0,0), cond, body)));
+    let expected = Box::new(CStmt::Block((0,0), top));
+
+    assert!(actual.is_ok());
+    assert_eq!(format!("{:?}", expected), format!("{:?}", actual.unwrap()));
+}
+
+#[test]
+fn stmt_do_while() {
+    let actual = semic::parse_stmt(r#"do { return; } while (1);"#);
+
+    let expected = CStmt::DoWhile((0,0),
+                                  Box::new(CStmt::Block(
+                                      (0,0),
+                                      vec![Box::new(CStmt::Return((0,0), None))])),
+                                  CExpr::Int((0,0), 1));
+
+    assert!(actual.is_ok());
+    assert_eq!(format!("{:?}", expected), format!("{:?}", actual.unwrap()));
+}
+
+#[test]
+fn stmt_switch() {
+    let actual = semic::parse_stmt(r#"switch (i) { case 1: case 'a': return; default: break; }"#);
+
+    let expected = CStmt::Switch((0,0),
+                                 CExpr::Ident((0,0), "i".to_owned()),
+                                 vec![Box::new(CStmt::Case((0,0), Some(CExpr::Int((0,0), 1)))),
+                                      Box::new(CStmt::Case((0,0), Some(CExpr::Char((0,0), 'a')))),
+                                      Box::new(CStmt::Return((0,0), None)),
+                                      Box::new(CStmt::Case((0,0), None)),
+                                      Box::new(CStmt::Break((0,0)))]);
+
+    assert!(actual.is_ok());
+    assert_eq!(format!("{:?}", expected), format!("{:?}", actual.unwrap()));
+}
@@ -0,0 +1,95 @@
+extern crate semic;
+
+use std::io;
+
+use semic::engine;
+use semic::error::{CError, ErrorPrinter};
+use semic::include;
+use semic::preprocess::{preprocess, Macros};
+
+fn text(src: &str) -> String {
+    preprocess(src, &mut Macros::new(), |_, _, _| ()).unwrap().text
+}
+
+fn messages(src: &str) -> Vec<String> {
+    let err = preprocess(src, &mut Macros::new(), |_, _, _| ()).unwrap_err();
+    ErrorPrinter::new("foo.semic", src).diagnostics(err).into_iter().map(|d| d.message).collect()
+}
+
+#[test]
+fn constants() {
+    assert_eq!("int a[ 10 ];\n", text("#define N 10\nint a[N];\n"));
+    // not in literals or longer names
+    assert_eq!("printf(\"N\", NN);\n", text("#define N 10\nprintf(\"N\", NN);\n"));
+    assert_eq!("x =  1 ;\nx = A;\n", text("#define A 1\nx = A;\n#undef A\nx = A;\n"));
+}
+
+#[test]
+fn with_arguments() {
+    assert_eq!("x =  ((1, 2) * 3) ;\n", text("#define MUL(a, b) (a * b)\nx = MUL((1, 2), 3);\n"));
+    // constants in arguments and bodies, across lines
+    assert_eq!("x =  ( 2  +  1 ) ;\n", text("#define ONE 1\n#define TWO 2\n#define ADD(a, b) (a + b)\nx = ADD(TWO,\n ONE);\n"));
+    assert_eq!("x =  (y * y) ;\n", text("#define SQUARE(x) \\\n    (x * x)\nx = SQUARE(y);\n"));
+    // only when called
+    assert_eq!("f = F;\n", text("#define F(x) x\nf = F;\n"));
+    assert_eq!("x =  1 ;\n", text("#define F() 1\nx = F();\n"));
+}
+
+#[test]
+fn not_recursive() {
+    assert_eq!(" x + 1  =  x + 1 ;\n", text("#define x x + 1\nx = x;\n"));
+    assert_eq!("  a   =   b  ;\n", text("#define a b\n#define b a\na = b;\n"));
+}
+
+#[test]
+fn conditions() {
+    let src = "#define A\n#ifdef A\n1\n#ifndef A\n2\n#else\n3\n#endif\n#else\n4\n#endif\n#ifdef B\n5\n#endif\n";
+    assert_eq!("1\n3\n", text(src));
+    // unknown directives are fine where unused
+    assert_eq!("", text("#ifdef B\n#if\n#endif\n"));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(vec!["Macro 'F' takes 2 arguments, given 1"], messages("#define F(a, b) a\nx = F(1);\n"));
+    assert_eq!(vec!["Unterminated use of macro 'F'"], messages("#define F(a) a\nx = F(1;\n"));
+    assert_eq!(vec!["Macro 'N' already defined"], messages("#define N 1\n#define N 1\n#define N 2\n"));
+    assert_eq!(vec!["Unknown preprocessor directive '#if'", "Unterminated '#ifdef'"],
+               messages("#if X\n#ifdef Y\n"));
+    assert_eq!(vec!["'#endif' without '#ifdef'"], messages("#endif\n"));
+    assert_eq!(vec!["Bad parameter '1' of macro 'F'"], messages("#define F(1) 1\n"));
+}
+
+#[test]
+fn locations() {
+    let src = "#define N 10\nint x;\nint main () {\n    return N / 0;\n}\n";
+    let (sources, ast) = include::load("foo.semic", src, |_| Err(io::Error::new(io::ErrorKind::NotFound, "")));
+    let err = engine::run_sources(&ast.unwrap(), &sources, &vec![], false, false, &engine::Context::new()).unwrap_err();
+    let diags = ErrorPrinter::from_map(sources).diagnostics(err);
+
+    let span = diags[0].span.as_ref().unwrap();
+    assert_eq!((4, 12), (span.line, span.column));
+    assert_eq!((4, 17), (span.end_line, span.end_column));
+}
+
+#[test]
+fn macros_in_headers() {
+    let src = "#include \"defs.h\"\n#include \"defs.h\"\nint main () {\n    return SQUARE(N);\n}\n";
+    let (sources, ast) = include::load("main.semic", src, |path| match path {
+        "defs.h" => Ok("#ifndef DEFS\n#define DEFS\n#define N 3\n#define SQUARE(x) ((x) * (x))\n#endif\n".to_owned()),
+        _ => Err(io::Error::new(io::ErrorKind::NotFound, "")),
+    });
+    let res = engine::run_sources(&ast.unwrap(), &sources, &vec![], false, false, &engine::Context::new());
+    assert_eq!("Ok(Some(9))", format!("{:?}", res));
+}
+
+#[test]
+fn not_formatted() {
+    match semic::format("int x;\n  #define N 1\n") {
+        Err(CError::ParseError(msg, loc)) => {
+            assert_eq!("Cannot format programs using the preprocessor", msg);
+            assert_eq!((9, 20), loc);
+        },
+        res => panic!("{:?}", res),
+    }
+}
@@ -0,0 +1,3 @@
+extern crate semic;
+
+mod macros;
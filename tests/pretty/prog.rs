@@ -0,0 +1,78 @@
+extern crate semic;
+
+use std::fs;
+
+use semic::pretty;
+
+#[test]
+fn canonical() {
+    let prog = "int g,h[2];\nint  f(int a,int *b[]);\n\n\nint main(){int i=0,j;\nfor(;i<3;i++)if(i==1)j=i;else{j=-i;}\n\n  do i--; while(i>0);\nswitch(i){case 0:break;default:return 1;}\nreturn (i+1)*2;}\n";
+
+    let expected = "int g, h[2];\n\
+int f(int a, int **b);\n\
+\n\
+int main(void) {\n\
+\x20   int i = 0, j;\n\
+\x20   for (; i < 3; i++) {\n\
+\x20       if (i == 1)\n\
+\x20           j = i;\n\
+\x20       else {\n\
+\x20           j = -i;\n\
+\x20       }\n\
+\x20   }\n\
+\n\
+\x20   do\n\
+\x20       i--;\n\
+\x20   while (i > 0);\n\
+\x20   switch (i) {\n\
+\x20   case 0:\n\
+\x20       break;\n\
+\x20   default:\n\
+\x20       return 1;\n\
+\x20   }\n\
+\x20   return (i + 1) * 2;\n\
+}\n";
+
+    let actual = semic::format(prog).unwrap();
+    assert_eq!(expected, actual);
+    assert_eq!(expected, semic::format(&actual).unwrap());
+}
+
+#[test]
+fn examples() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let prog = fs::read_to_string(&path).unwrap();
+        if semic::parse_prog(&prog).is_err() {
+            continue;
+        }
+
+        let formatted = semic::format(&prog).expect(path.to_str().unwrap());
+        assert_eq!(formatted, semic::format(&formatted).unwrap(), "{:?}", path);
+    }
+}
+
+#[test]
+fn expr_parens() {
+    let cases = vec![
+        ("1 + 2 * 3", "1 + 2 * 3"),
+        ("(1 + 2) * 3", "(1 + 2) * 3"),
+        ("1 - (2 - 3)", "1 - (2 - 3)"),
+        ("(1 - 2) - 3", "1 - 2 - 3"),
+        ("- -1", "-(-1)"),
+        ("!(a && b) || c", "!(a && b) || c"),
+        ("a ? b : (c, d)", "a ? b : (c, d)"),
+        ("(int) (f * 2.0)", "(int) (f * 2.0)"),
+        ("((x))", "x"),
+    ];
+
+    for (input, expected) in cases {
+        let expr = semic::parse_expr(input).unwrap();
+        assert_eq!(expected, pretty::format_expr(&expr), "{}", input);
+    }
+}
+
+#[test]
+fn unformattable() {
+    assert!(semic::format("int main() { return 0 }").is_err());
+}
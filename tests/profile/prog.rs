@@ -0,0 +1,78 @@
+extern crate semic;
+
+use semic::ast::*;
+use semic::build::*;
+use semic::engine::{self, Context};
+use semic::source::SourceMap;
+
+use engine::run_prog_reported;
+
+#[test]
+fn counts() {
+    let prog = "int sq(int n) {\n  return n * n;\n}\nint main () {\n  int i;\n  i = 0;\n  while (i < 3) {\n    i = i + sq(i);\n    i = i + 1;\n  }\n  return i;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut sources = SourceMap::new();
+    sources.add("foo.semic", prog);
+
+    let (res, (lines, calls)) = run_prog_reported(&ast, prog, |c| c.profile = true, |c| {
+        let profile = c.profile();
+        assert!(profile.funcs[0].total >= profile.funcs[1].total);
+
+        let listing = profile.listing(&sources);
+        assert!(listing.contains("        3    7 |   while (i < 3) {\n"));
+        assert!(listing.contains("       12 | }\n\n function"));
+
+        let calls: Vec<_> = profile.funcs.iter().map(|f| (f.name.clone(), f.calls)).collect();
+        (profile.lines(&sources, 0), calls)
+    });
+    assert!(res.is_ok());
+
+    // i goes 0, 1, 3, and the loop condition is checked once more
+    assert_eq!(vec![(2, 2), (5, 1), (6, 1), (7, 3), (8, 2), (9, 2), (11, 1)], lines);
+    assert_eq!(vec![("main".to_owned(), 1), ("sq".to_owned(), 2)], calls);
+}
+
+#[test]
+fn off_by_default() {
+    let prog = "int main () {\n  return 0;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let context = Context::new();
+    assert!(engine::run_prog(&ast, prog, &vec![], false, false, &context).is_ok());
+
+    let profile = context.profile();
+    assert!(profile.stmts.is_empty());
+    assert!(profile.funcs.is_empty());
+}
+
+#[test]
+fn declaration_with_initializer_once() {
+    let prog = "int main () {\n  int i = 0, n = 0;\n  while (i < 2) {\n    int j = i;\n    i = i + 1;\n  }\n  return i;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut sources = SourceMap::new();
+    sources.add("foo.semic", prog);
+
+    let (res, lines) = run_prog_reported(&ast, prog, |c| c.profile = true, |c| c.profile().lines(&sources, 0));
+    assert!(res.is_ok());
+    assert_eq!(vec![(2, 1), (3, 3), (4, 2), (5, 2), (7, 1)], lines);
+}
+
+#[test]
+fn built_statements_all_counted() {
+    // built nodes all share the location (0, 0)
+    let ast = vec![
+        func(proto(Some(CType::Int), "main", vec![]), vec![
+            decl_init(CType::Int, "i", int(1)),
+            decl(CType::Int, "j"),
+            assign("j", int(2)),
+            assign("i", binop(COp::Add, ident("i"), ident("j"))),
+            ret(Some(ident("i"))),
+        ]),
+    ];
+
+    let (res, count) = run_prog_reported(&ast, "", |c| c.profile = true, |c| c.profile().stmts.get(&0).cloned());
+    assert_eq!("Ok(Some(3))", format!("{:?}", res));
+    assert_eq!(Some(5), count);
+}
@@ -0,0 +1,41 @@
+use semic::repl::complete;
+
+fn names() -> Vec<String> {
+    vec!["argc".to_owned(), "argv".to_owned(), "count".to_owned(), "total".to_owned()]
+}
+
+fn strs(xs: &[&str]) -> Vec<String> {
+    xs.iter().map(|x| x.to_string()).collect()
+}
+
+#[test]
+fn commands() {
+    assert_eq!((0, strs(&["next"])), complete("n", 1, &names()));
+    assert_eq!((0, strs(&["continue"])), complete("co", 2, &names()));
+    assert_eq!((0, strs(&["next", "print", "trace", "continue", "break", "list", "quit"])), complete("", 0, &names()));
+    assert_eq!((2, strs(&["print"])), complete("  pr", 4, &names()));
+    assert_eq!((0, vec![]), complete("x", 1, &names()));
+}
+
+#[test]
+fn variables() {
+    assert_eq!((6, strs(&["argc", "argv"])), complete("print arg", 9, &names()));
+    assert_eq!((2, strs(&["total"])), complete("t t", 3, &names()));
+    assert_eq!((8, strs(&["count"])), complete("print/c c", 9, &names()));
+    assert_eq!((6, names()), complete("trace ", 6, &names()));
+}
+
+#[test]
+fn nothing_else() {
+    // only the first argument of commands taking a variable
+    assert_eq!(Vec::<String>::new(), complete("print x t", 9, &names()).1);
+    assert_eq!(Vec::<String>::new(), complete("break c", 7, &names()).1);
+    assert_eq!(Vec::<String>::new(), complete("next t", 6, &names()).1);
+}
+
+#[test]
+fn up_to_the_cursor() {
+    // the rest of the line is left alone
+    assert_eq!((6, strs(&["total"])), complete("print tot + 1", 9, &names()));
+    assert_eq!((0, strs(&["print"])), complete("pr count", 2, &names()));
+}
@@ -0,0 +1,106 @@
+extern crate semic;
+
+use semic::ast::{CType, CProgElem, CStmt, CExpr};
+use semic::env::SymTab;
+use semic::resolver::{self, Resolver, Scope};
+
+#[test]
+fn locals_and_globals() {
+    let prog = "int g;\nint f(int a) {\n  int b;\n  b = a + g;\n  int a;\n  a = x;\n  return b;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut globals = SymTab::new();
+    globals.insert("g", CType::Int, None, None, Some((0, 6)));
+
+    let res = resolver::resolve_prog(&ast, &globals);
+    let scopes = &res.funcs[0].1;
+
+    // redeclaring a name gives a new slot
+    let names: Vec<_> = scopes.locals.iter().map(|l| l.name).collect();
+    assert_eq!(vec!["a", "b", "a"], names);
+    assert_eq!(None, scopes.locals[0].decl);
+    assert_eq!(prog.find("int b"), scopes.locals[1].decl.map(|d| d.0));
+
+    let stmts = match ast[1] {
+        CProgElem::Func(_, ref f) => match f.body {
+            CStmt::Block(_, ref stmts) => stmts,
+            _ => panic!("expected block"),
+        },
+        _ => panic!("expected function"),
+    };
+
+    // b = a + g
+    assert_eq!(Some(Scope::Local(1)), scopes.of_stmt(&stmts[1]));
+    match *stmts[1] {
+        CStmt::Assign(_, _, _, CExpr::BinOp(_, _, ref e1, ref e2)) => {
+            assert_eq!(Some(Scope::Local(0)), scopes.of_expr(e1));
+            assert_eq!(Some(Scope::Global(0)), scopes.of_expr(e2));
+        },
+        _ => panic!("expected assignment"),
+    }
+
+    // a = x, after redeclaring a
+    assert_eq!(Some(Scope::Local(2)), scopes.of_stmt(&stmts[3]));
+    match *stmts[3] {
+        CStmt::Assign(_, _, _, ref e) => assert_eq!(Some(Scope::Undeclared), scopes.of_expr(e)),
+        _ => panic!("expected assignment"),
+    }
+}
+
+#[test]
+fn scope_at() {
+    let prog = "int g;\nint f(int a) {\n  int b;\n  b = a + g;\n  int a;\n  return b;\n}\nint main() {\n  return argc;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut globals = SymTab::new();
+    globals.insert("g", CType::Int, None, None, Some((0, 6)));
+
+    let res = resolver::resolve_prog(&ast, &globals);
+
+    let at = |s: &str| prog.find(s).unwrap();
+    assert_eq!(Scope::Undeclared, res.scope_at("b", at("int b")));
+    assert_eq!(Scope::Local(1), res.scope_at("b", at("b = a")));
+    assert_eq!(Scope::Local(0), res.scope_at("a", at("b = a")));
+    assert_eq!(Scope::Local(2), res.scope_at("a", at("return b")));
+    assert_eq!(Scope::Global(0), res.scope_at("g", at("b = a")));
+
+    // main is given the command line
+    assert_eq!(Scope::Local(0), res.scope_at("argc", at("return argc")));
+    assert_eq!(Scope::Undeclared, res.scope_at("b", at("return argc")));
+}
+
+#[test]
+fn snippet() {
+    let globals = SymTab::new();
+    let mut locals = SymTab::new();
+    locals.insert("i", CType::Int, None, None, Some((3, 9)));
+
+    let ast = semic::parse_stmt(" j = i; ").unwrap();
+
+    let mut resolver = Resolver::new(&globals);
+    let scopes = resolver.snippet_stmt(&ast, &locals);
+
+    // names from the symbol table get slots, others stay undeclared
+    assert_eq!(Some(Scope::Undeclared), scopes.of_stmt(&ast));
+    assert_eq!(vec![0], scopes.seeded);
+    assert_eq!("i", scopes.locals[0].name);
+    assert_eq!(Some((3, 9)), scopes.locals[0].decl);
+}
+
+#[test]
+fn block_scope() {
+    let prog = "int x;\nint main() {\n  if (0) int x;\n  x = 2;\n  while (x) {\n    x = 1;\n    int x;\n    x = 7;\n  }\n  return x;\n}\n";
+
+    let ast = semic::parse_prog(prog).unwrap();
+    let mut globals = SymTab::new();
+    globals.insert("x", CType::Int, None, None, Some((0, 6)));
+
+    let res = resolver::resolve_prog(&ast, &globals);
+
+    // declarations end with the statement or block they are in
+    let at = |s: &str| prog.find(s).unwrap();
+    assert_eq!(Scope::Global(0), res.scope_at("x", at("x = 2")));
+    assert_eq!(Scope::Global(0), res.scope_at("x", at("x = 1")));
+    assert_eq!(Scope::Local(3), res.scope_at("x", at("x = 7")));
+    assert_eq!(Scope::Global(0), res.scope_at("x", at("return x")));
+}
@@ -0,0 +1,96 @@
+extern crate semic;
+
+use semic::source::{SourceFile, SourceMap};
+
+#[test]
+fn lines() {
+    let file = SourceFile::new("a.semic", "int x;\r\nint y;\n\nx = 1;");
+
+    assert_eq!(4, file.line_count());
+    assert_eq!(Some(1), file.line(0));
+    assert_eq!(Some(1), file.line(7));
+    assert_eq!(Some(2), file.line(8));
+    assert_eq!(Some(3), file.line(15));
+    assert_eq!(Some(4), file.line(16));
+    // just past the end, where errors at the end of input are
+    assert_eq!(Some(4), file.line(22));
+    assert_eq!(None, file.line(23));
+
+    assert_eq!(Some("int x;"), file.line_text(1));
+    assert_eq!(Some(""), file.line_text(3));
+    assert_eq!(Some("x = 1;"), file.line_text(4));
+    assert_eq!(None, file.line_text(0));
+    assert_eq!(None, file.line_text(5));
+}
+
+#[test]
+fn utf8_columns() {
+    let src = "s = \"é€\"; x;\n";
+    let file = SourceFile::new("a.semic", src);
+
+    let x = src.find("x").unwrap();
+    assert_eq!(Some((1, 11)), file.line_col(x));
+    assert_eq!(Some(x), file.loc(1, 11));
+    // past the end of the line is its end
+    assert_eq!(Some(src.len() - 1), file.loc(1, 50));
+    // inside a character
+    assert_eq!(None, file.line_col(src.find("é").unwrap() + 1));
+}
+
+#[test]
+fn spans() {
+    let src = "int main() {\n  return foo + 1;\n}";
+    let file = SourceFile::new("a.semic", src);
+
+    let start = src.find("foo").unwrap();
+    let span = file.span((start, start + 7)).unwrap();
+    assert_eq!((2, 10, 2, 17), (span.line, span.column, span.end_line, span.end_column));
+
+    // an empty span covers the word there, or a single character
+    let span = file.span((start, start)).unwrap();
+    assert_eq!((10, 13), (span.column, span.end_column));
+    let span = file.span((start + 4, start + 4)).unwrap();
+    assert_eq!((14, 15), (span.column, span.end_column));
+
+    let span = file.span((src.find("{").unwrap(), src.len())).unwrap();
+    assert_eq!((1, 3), (span.line, span.end_line));
+}
+
+#[test]
+fn underline() {
+    let src = "int main() {\n  return foo + 1;\n}";
+    let file = SourceFile::new("a.semic", src);
+
+    let start = src.find("foo").unwrap();
+    let span = file.span((start, start + 7)).unwrap();
+    assert_eq!(" │   return foo + 1;\n │          ^^^^^^^\n", file.underline(&span));
+
+    // spans over several lines are underlined to the end of the first
+    let span = file.span((src.find("main").unwrap(), src.len())).unwrap();
+    assert_eq!(" │ int main() {\n │     ^^^^^^^^\n", file.underline(&span));
+}
+
+#[test]
+fn map() {
+    let mut map = SourceMap::new();
+    let a = map.add("a.semic", "int x;\n");
+    let b = map.add("b.semic", "int y;\n");
+
+    assert!(a != b);
+    assert_eq!(Some(b), map.find("b.semic"));
+    assert_eq!(None, map.find("c.semic"));
+    assert_eq!("int x;\n", map.file(a).unwrap().src);
+    assert_eq!(2, map.files().len());
+
+    // each file has its own locations, including the one past its end
+    assert_eq!(8, map.base(b));
+    assert_eq!(Some((a, 7)), map.locate(7));
+    assert_eq!(Some((b, 0)), map.locate(8));
+    assert_eq!(Some((b, 4)), map.locate(12));
+    assert_eq!(None, map.locate(16));
+    assert_eq!(Some((b, 1)), map.line(12));
+
+    let (file, span) = map.span((12, 13)).unwrap();
+    assert_eq!(b, file);
+    assert_eq!((4, 1, 5), (span.offset, span.line, span.column));
+}
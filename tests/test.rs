@@ -4,3 +4,17 @@ pub mod parser;
 pub mod checker;
 pub mod engine;
 pub mod env;
+pub mod error;
+pub mod lint;
+pub mod resolver;
+pub mod profile;
+pub mod coverage;
+pub mod trace;
+pub mod pretty;
+pub mod build;
+pub mod source;
+pub mod include;
+pub mod preprocess;
+pub mod lsp;
+pub mod dap;
+pub mod repl;
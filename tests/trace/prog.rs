@@ -0,0 +1,100 @@
+extern crate semic;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use semic::source::SourceMap;
+use semic::trace::Tracer;
+
+use engine::run_prog_reported;
+
+/// Output shared with the test after the tracer is handed over
+#[derive(Clone)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Trace of a program, which both backends have to agree on
+fn trace(prog: &str, json: bool) -> String {
+    let ast = semic::parse_prog(prog).unwrap();
+
+    let mut sources = SourceMap::new();
+    sources.add("foo.semic", prog);
+
+    let buf = Buffer(Rc::new(RefCell::new(vec![])));
+    let (res, out) = run_prog_reported(&ast, prog,
+        |c| c.trace(Tracer::new(&sources, Box::new(buf.clone()), json)),
+        |_| String::from_utf8(buf.0.borrow_mut().drain(..).collect()).unwrap());
+    assert!(res.is_ok());
+    out
+}
+
+#[test]
+fn statements_and_assignments() {
+    let prog = "int f(int n) {\n  return n + 1;\n}\nint main () {\n  int a[2];\n  a[1] = f(2);\n  return a[1];\n}\n";
+
+    let expected = "    5 | int a[2];\n\
+\x20   6 | a[1] = f(2);\n\
+\x20   2 |   return n + 1;\n\
+\x20     |   a[1] = 3\n\
+\x20   7 | return a[1];\n";
+
+    assert_eq!(expected, trace(prog, false));
+}
+
+#[test]
+fn json_lines() {
+    let prog = "int main () {\n  char c;\n  c = 'x'; return 0;\n}\n";
+
+    let expected = vec![
+        r#"{"event":"stmt","line":2,"depth":0,"text":"char c;"}"#,
+        r#"{"event":"stmt","line":3,"depth":0,"text":"c = 'x';"}"#,
+        r#"{"event":"assign","line":3,"depth":0,"name":"c","index":null,"value":"'x'"}"#,
+        r#"{"event":"stmt","line":3,"depth":0,"text":"return 0;"}"#,
+    ];
+
+    let out = trace(prog, true);
+    assert_eq!(expected, out.lines().collect::<Vec<_>>());
+}
+
+#[test]
+fn declaration_with_initializer_once() {
+    let prog = "int f(int n) {\n  return n > 0 ? f(n - 1) : 0;\n}\nint main () {\n  int i = 0, j = 1;\n  return f(1);\n}\n";
+
+    let expected = "    5 | int i = 0, j = 1;\n\
+\x20     |   i = 0\n\
+\x20     |   j = 1\n\
+\x20   6 | return f(1);\n\
+\x20   2 |   return n > 0 ? f(n - 1) : 0;\n\
+\x20   2 |     return n > 0 ? f(n - 1) : 0;\n";
+
+    assert_eq!(expected, trace(prog, false));
+}
+
+#[test]
+fn for_loop_header() {
+    let prog = "int main () {\n  int i;\n  for (i = 0; i < 1; i = i + 1) {\n    i = i;\n  }\n  return i;\n}\n";
+
+    // the loop shows its header only, not the rest of the line
+    let expected = "    2 | int i;\n\
+\x20   3 | i = 0;\n\
+\x20     |   i = 0\n\
+\x20   3 | for (i = 0; i < 1; i = i + 1) {\n\
+\x20   4 | i = i;\n\
+\x20     |   i = 0\n\
+\x20   3 | i = i + 1\n\
+\x20     |   i = 1\n\
+\x20   3 | for (i = 0; i < 1; i = i + 1) {\n\
+\x20   6 | return i;\n";
+
+    assert_eq!(expected, trace(prog, false));
+}